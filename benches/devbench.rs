@@ -1,6 +1,21 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 use image::imageops::FilterType;
-use scale_benchmarks::{cpu_algo::CPUAlgoUpscaler, upscaler::UpscaleSquareImage};
+use image::RgbImage;
+use scale_benchmarks::{
+    cnn_model::{random_srvgg, Architecture, CnnModel},
+    cpu_algo::{CPUAlgoUpscaler, ResampleKernel},
+    cpu_simd::SimdLevel,
+    edge_directed::{EdgeDirected, EdgeDirectedUpscaler},
+    gpu_cnn::GPUCnnUpscaler,
+    gpu_compute::GPUComputeUpscaler,
+    gpu_shading::GPUShadingUpscaler,
+    gpu_shading_cfg::GpuShadingConfig,
+    pixel_art::{PixelArt, PixelArtUpscaler},
+    raisr::{FilterBank, RaisrUpscaler},
+    shader_library::BuiltinShader,
+    upscaler::UpscaleImage,
+};
+use std::path::PathBuf;
 
 fn cpu_algo(c: &mut Criterion) {
     let scaler = CPUAlgoUpscaler::new(2.0, FilterType::Lanczos3);
@@ -19,12 +34,250 @@ fn cpu_algo(c: &mut Criterion) {
     c.bench_function("nearest", |b| b.iter(|| scaler.upscale().unwrap()));
 }
 
-// fn cpu_nn(c: &mut Criterion) {
-//     let scaler = ONNXNeuralUpscaler::from_model("models/realesr-general-wdn-x4v3.pth.onnx").unwrap();
-//     scaler.upscale().unwrap();
-    
-//     c.bench_function("compact-x4", |b| b.iter(|| scaler.upscale().unwrap()));
-// }
+fn cpu_kernels(c: &mut Criterion) {
+    let kernels = [
+        ("box", ResampleKernel::Box),
+        ("triangle", ResampleKernel::Triangle),
+        ("hermite", ResampleKernel::HERMITE),
+        ("cubic_b_spline", ResampleKernel::CUBIC_B_SPLINE),
+        ("catmullrom", ResampleKernel::CATMULL_ROM),
+        ("mitchell", ResampleKernel::MITCHELL),
+        ("lanczos2", ResampleKernel::LANCZOS2),
+        ("lanczos3", ResampleKernel::LANCZOS3),
+        ("lanczos4", ResampleKernel::LANCZOS4),
+        ("hann3", ResampleKernel::Hann { lobes: 3 }),
+        ("hamming3", ResampleKernel::Hamming { lobes: 3 }),
+        ("blackman3", ResampleKernel::Blackman { lobes: 3 }),
+    ];
 
-criterion_group!(benches, cpu_algo);
+    let mut group = c.benchmark_group("kernel");
+    for (name, kernel) in kernels {
+        let scaler = CPUAlgoUpscaler::with_kernel(2.0, kernel);
+        group.bench_function(name, |b| b.iter(|| scaler.upscale().unwrap()));
+    }
+    group.finish();
+}
+
+/// Sweeps thread counts from 1 to the number of CPUs
+fn cpu_threads(c: &mut Criterion) {
+    let max_threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let image = RgbImage::from_fn(1024, 1024, |x, y| {
+        image::Rgb([x as u8, y as u8, (x ^ y) as u8])
+    })
+    .into();
+
+    let kernels = [
+        ("threads_lanczos3", ResampleKernel::LANCZOS3),
+        ("threads_mitchell", ResampleKernel::MITCHELL),
+    ];
+    for (name, kernel) in kernels {
+        let mut group = c.benchmark_group(name);
+        for threads in 1..=max_threads {
+            let mut scaler = CPUAlgoUpscaler::with_kernel(2.0, kernel)
+                .with_threads(threads)
+                .unwrap();
+            scaler.load(&image).unwrap();
+            group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, _| {
+                b.iter(|| scaler.upscale().unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+/// Compares instruction sets of the separable convolution, weight tables are built on `load`
+fn cpu_simd(c: &mut Criterion) {
+    let image = RgbImage::from_fn(1024, 1024, |x, y| {
+        image::Rgb([x as u8, y as u8, (x ^ y) as u8])
+    })
+    .into();
+
+    let mut group = c.benchmark_group("simd_lanczos3");
+    for simd in [SimdLevel::Scalar, SimdLevel::Sse41, SimdLevel::Avx2] {
+        if simd.supported() != simd {
+            continue;
+        }
+        let mut scaler =
+            CPUAlgoUpscaler::with_kernel(2.0, ResampleKernel::LANCZOS3).with_simd(simd);
+        scaler.load(&image).unwrap();
+        group.bench_function(format!("{simd:?}").to_lowercase(), |b| {
+            b.iter(|| scaler.upscale().unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn edge_directed(c: &mut Criterion) {
+    let image = RgbImage::from_fn(256, 256, |x, y| {
+        image::Rgb([x as u8, y as u8, (x ^ y) as u8])
+    })
+    .into();
+
+    let mut group = c.benchmark_group("edge_directed");
+    for (name, method) in [
+        ("nedi", EdgeDirected::Nedi),
+        ("icbi", EdgeDirected::ICBI),
+        ("dcci", EdgeDirected::Dcci),
+    ] {
+        let mut scaler = EdgeDirectedUpscaler::new(method, 2.0);
+        scaler.load(&image).unwrap();
+        group.bench_function(name, |b| b.iter(|| scaler.upscale().unwrap()));
+    }
+    group.finish();
+}
+
+/// Filtering costs the same for any bank with the default hashing, so an untrained one is used
+fn raisr(c: &mut Criterion) {
+    let image = RgbImage::from_fn(256, 256, |x, y| {
+        image::Rgb([x as u8, y as u8, (x ^ y) as u8])
+    })
+    .into();
+    let mut scaler = RaisrUpscaler::new(FilterBank::identity(2).unwrap(), 2.0);
+    scaler.load(&image).unwrap();
+    c.bench_function("raisr", |b| b.iter(|| scaler.upscale().unwrap()));
+}
+
+/// Sprite-sized input at every native factor
+fn pixel_art(c: &mut Criterion) {
+    let image = RgbImage::from_fn(256, 256, |x, y| {
+        image::Rgb([(x / 8 * 32) as u8, (y / 8 * 32) as u8, 0])
+    })
+    .into();
+
+    let mut group = c.benchmark_group("pixel_art");
+    for algorithm in [PixelArt::Epx, PixelArt::Eagle] {
+        for &factor in algorithm.factors() {
+            let mut scaler = PixelArtUpscaler::new(algorithm, factor as f32);
+            scaler.load(&image).unwrap();
+            let name = format!("{algorithm:?}_{factor}x").to_lowercase();
+            group.bench_function(name, |b| b.iter(|| scaler.upscale().unwrap()));
+        }
+    }
+    group.finish();
+}
+
+/// Reads GPU settings from a JSON file at `GPU_SHADING_CONFIG`, if set
+fn gpu_config() -> GpuShadingConfig {
+    match std::env::var("GPU_SHADING_CONFIG") {
+        Ok(path) => GpuShadingConfig::from_file(path).unwrap(),
+        Err(_) => GpuShadingConfig::default(),
+    }
+}
+
+fn gpu_shading(c: &mut Criterion) {
+    let image = RgbImage::new(512, 512).into();
+    let mut scaler = GPUShadingUpscaler::from_image_with_config(
+        "shaders/passthrough.wgsl",
+        &image,
+        2.0,
+        gpu_config(),
+    )
+    .unwrap();
+    c.bench_function("passthrough", |b| b.iter(|| scaler.upscale().unwrap()));
+    c.bench_function("passthrough_inplace", |b| {
+        b.iter(|| {
+            scaler.upscale_inplace().unwrap();
+        })
+    });
+
+    let scaler =
+        GPUShadingUpscaler::from_image_with_config("fsr", &image, 2.0, gpu_config()).unwrap();
+    c.bench_function("fsr", |b| b.iter(|| scaler.upscale().unwrap()));
+
+    let mut group = c.benchmark_group("shader_library");
+    for shader in BuiltinShader::ALL {
+        let scaler =
+            GPUShadingUpscaler::from_image_with_config(shader, &image, 2.0, gpu_config()).unwrap();
+        group.bench_function(shader.name(), |b| b.iter(|| scaler.upscale().unwrap()));
+    }
+    group.finish();
+
+    // mpv user shaders such as FSRCNNX or Anime4K, listed in `MPV_SHADERS` like `PATH`
+    let mut shaders = vec![PathBuf::from("shaders/mpv_unsharp_luma.glsl")];
+    shaders.extend(
+        std::env::var_os("MPV_SHADERS")
+            .iter()
+            .flat_map(std::env::split_paths),
+    );
+    for shader in shaders {
+        let name = shader.file_stem().unwrap().to_string_lossy().into_owned();
+        let scaler =
+            GPUShadingUpscaler::from_image_with_config(shader, &image, 2.0, gpu_config()).unwrap();
+        c.bench_function(&name, |b| b.iter(|| scaler.upscale().unwrap()));
+    }
+}
+
+/// Render and compute pipelines running the same kernel
+fn gpu_compute(c: &mut Criterion) {
+    let image = RgbImage::new(512, 512).into();
+    for shader in GPUComputeUpscaler::SHADERS {
+        let mut group = c.benchmark_group(format!("pipeline_{}", shader.name()));
+        let render =
+            GPUShadingUpscaler::from_image_with_config(shader, &image, 2.0, gpu_config()).unwrap();
+        group.bench_function("render", |b| b.iter(|| render.upscale().unwrap()));
+        let mut compute = GPUComputeUpscaler::with_config(shader, 2.0, gpu_config()).unwrap();
+        compute.load(&image).unwrap();
+        group.bench_function("compute", |b| b.iter(|| compute.upscale().unwrap()));
+        group.finish();
+    }
+}
+
+/// Network shaped like `realesr-general-x4v3` with random weights
+fn gpu_cnn(c: &mut Criterion) {
+    let image = RgbImage::from_fn(128, 128, |x, y| {
+        image::Rgb([x as u8, y as u8, (x ^ y) as u8])
+    })
+    .into();
+    let model =
+        CnnModel::from_tensors(&random_srvgg(64, 32, 4, 0), Architecture::SrvggCompact).unwrap();
+    let mut scaler = GPUCnnUpscaler::with_config(model, 4.0, gpu_config()).unwrap();
+    scaler.load(&image).unwrap();
+    c.bench_function("srvgg_compact_x4", |b| b.iter(|| scaler.upscale().unwrap()));
+}
+
+#[cfg(feature = "onnx")]
+fn cpu_nn(c: &mut Criterion) {
+    use scale_benchmarks::{onnx::ONNXNeuralUpscaler, onnx_test_model::TestModel};
+
+    let model_path = std::env::temp_dir().join("scale-benchmarks-nearest-x2.onnx");
+    TestModel::nearest(2)
+        .fixed_size(512, 512)
+        .save(&model_path)
+        .unwrap();
+
+    let scaler = ONNXNeuralUpscaler::from_model(&model_path).unwrap();
+    scaler.upscale().unwrap();
+
+    c.bench_function("nearest-x2-onnx", |b| b.iter(|| scaler.upscale().unwrap()));
+}
+
+#[cfg(feature = "onnx")]
+criterion_group!(
+    benches,
+    cpu_algo,
+    cpu_kernels,
+    cpu_threads,
+    cpu_simd,
+    edge_directed,
+    raisr,
+    pixel_art,
+    gpu_shading,
+    gpu_compute,
+    gpu_cnn,
+    cpu_nn
+);
+#[cfg(not(feature = "onnx"))]
+criterion_group!(
+    benches,
+    cpu_algo,
+    cpu_kernels,
+    cpu_threads,
+    cpu_simd,
+    edge_directed,
+    raisr,
+    pixel_art,
+    gpu_shading,
+    gpu_compute,
+    gpu_cnn
+);
 criterion_main!(benches);
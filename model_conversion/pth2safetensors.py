@@ -0,0 +1,45 @@
+import argparse
+import torch
+from safetensors.torch import save_file
+
+
+def get_args() -> argparse.Namespace:
+    parser = argparse.ArgumentParser(
+        prog="Pth2Safetensors",
+        description="Converts a PyTorch state dict (e.g. SRVGGNetCompact or FSRCNN) to safetensors",
+    )
+
+    parser.add_argument("input", type=str, help="Path to a PyTorch checkpoint")
+    parser.add_argument(
+        "--output",
+        type=str,
+        default="",
+        help="Path to the final safetensors file (default is {input}.safetensors)",
+    )
+
+    args = parser.parse_args()
+
+    if args.output == "":
+        args.output = args.input + ".safetensors"
+
+    return args
+
+
+if __name__ == "__main__":
+    args = get_args()
+
+    state_dict = torch.load(args.input, map_location="cpu", weights_only=True)
+
+    # Training checkpoints nest the weights, `CnnModel` expects them at the top level
+    for key in ["params_ema", "params", "state_dict"]:
+        if key in state_dict:
+            print(f"[info] Using weights under `{key}`")
+            state_dict = state_dict[key]
+            break
+
+    tensors = {name: tensor.float().contiguous() for name, tensor in state_dict.items()}
+    print(f"[info] Converting {len(tensors)} tensors...", flush=True)
+
+    save_file(tensors, args.output)
+
+    print(f"[info] Safetensors written to `{args.output}`")
@@ -1,4 +1,7 @@
 import argparse
+import json
+import os
+import onnx
 import torch
 from spandrel import ImageModelDescriptor, ModelLoader
 
@@ -21,6 +24,12 @@ def get_args() -> argparse.Namespace:
         help="Path to the final ONNX file (default is {input}.onnx)",
     )
 
+    parser.add_argument(
+        "--sidecar",
+        action="store_true",
+        help="Also write model properties to a JSON file next to the ONNX file",
+    )
+
     args = parser.parse_args()
 
     if args.output == "":
@@ -54,4 +63,30 @@ if __name__ == "__main__":
             output_names=["output"],
         )
 
+    # Properties read by `ModelMetadata` on the Rust side
+    properties = {
+        "name": model.architecture.name,
+        "scale": model.scale,
+        "layout": "nchw",
+        "channels": "rgb",
+        "input": "unit",
+        "output": "unit",
+        "tile_size": args.size,
+        "pad_to_multiple": model.size_requirements.multiple_of,
+    }
+
+    onnx_model = onnx.load(args.output)
+    onnx.helper.set_model_props(
+        onnx_model,
+        {key: json.dumps(value) for key, value in properties.items()},
+    )
+    onnx.save(onnx_model, args.output)
+
     print(f"[info] ONNX model written to `{args.output}`")
+
+    if args.sidecar:
+        sidecar_path = os.path.splitext(args.output)[0] + ".json"
+        with open(sidecar_path, "w") as sidecar:
+            json.dump(properties, sidecar, indent=4)
+
+        print(f"[info] Model properties written to `{sidecar_path}`")
@@ -0,0 +1,32 @@
+// Cubic B-spline interpolation in four bilinear taps
+//
+// The B-spline weights are all positive, so each pair of neighbouring taps along an axis merges into one
+// linear sample placed between them by their relative weight (GPU Gems 2, chapter 20).
+
+@group(0) @binding(0) var r_color: texture_2d<f32>;
+@group(0) @binding(1) var r_sampler: sampler;
+
+fn sample(texel: vec2<f32>, size: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(r_color, r_sampler, texel / size, 0.0);
+}
+
+@fragment fn main(@builtin(position) _sv_position: vec4<f32>, @location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(r_color));
+    let position = coords * size - 0.5;
+    let fp = floor(position);
+    let f = position - fp;
+
+    let w0 = (1.0 - f) * (1.0 - f) * (1.0 - f) / 6.0;
+    let w1 = (4.0 - 6.0 * f * f + 3.0 * f * f * f) / 6.0;
+    let w3 = f * f * f / 6.0;
+    let w2 = 1.0 - w0 - w1 - w3;
+
+    // Weights and texel-space centres of the merged taps
+    let g0 = w0 + w1;
+    let g1 = w2 + w3;
+    let h0 = fp - 0.5 + w1 / g0;
+    let h1 = fp + 1.5 + w3 / g1;
+
+    return g0.y * (g0.x * sample(vec2<f32>(h0.x, h0.y), size) + g1.x * sample(vec2<f32>(h1.x, h0.y), size))
+        + g1.y * (g0.x * sample(vec2<f32>(h0.x, h1.y), size) + g1.x * sample(vec2<f32>(h1.x, h1.y), size));
+}
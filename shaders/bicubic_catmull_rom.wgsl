@@ -0,0 +1,35 @@
+// Catmull-Rom interpolation in nine bilinear taps
+//
+// The outer weights are negative, so only the two inner taps along each axis merge into one linear sample,
+// leaving three per axis instead of the four the B-spline gets away with.
+
+@group(0) @binding(0) var r_color: texture_2d<f32>;
+@group(0) @binding(1) var r_sampler: sampler;
+
+fn sample(x: f32, y: f32, size: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(r_color, r_sampler, vec2<f32>(x, y) / size, 0.0);
+}
+
+@fragment fn main(@builtin(position) _sv_position: vec4<f32>, @location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(r_color));
+    let position = coords * size - 0.5;
+    let fp = floor(position);
+    let f = position - fp;
+
+    let w0 = f * (-0.5 + f * (1.0 - 0.5 * f));
+    let w1 = 1.0 + f * f * (-2.5 + 1.5 * f);
+    let w2 = f * (0.5 + f * (2.0 - 1.5 * f));
+    let w3 = f * f * (-0.5 + 0.5 * f);
+
+    // Texel-space centres of the outer taps and the merged inner one
+    let w12 = w1 + w2;
+    let t0 = fp - 0.5;
+    let t12 = fp + 0.5 + w2 / w12;
+    let t3 = fp + 2.5;
+
+    let row0 = w0.x * sample(t0.x, t0.y, size) + w12.x * sample(t12.x, t0.y, size) + w3.x * sample(t3.x, t0.y, size);
+    let row12 =
+        w0.x * sample(t0.x, t12.y, size) + w12.x * sample(t12.x, t12.y, size) + w3.x * sample(t3.x, t12.y, size);
+    let row3 = w0.x * sample(t0.x, t3.y, size) + w12.x * sample(t12.x, t3.y, size) + w3.x * sample(t3.x, t3.y, size);
+    return w0.y * row0 + w12.y * row12 + w3.y * row3;
+}
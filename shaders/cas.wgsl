@@ -0,0 +1,49 @@
+// Bilinear upscaling sharpened by AMD FidelityFX Contrast Adaptive Sharpening
+//
+// Port of `CasFilter` from ffx_cas.h (MIT, Copyright (c) 2019 Advanced Micro Devices, Inc.). Instead of its
+// scaling path the 3x3 neighbourhood is sampled bilinearly a source texel apart around the output pixel.
+//
+// Sharpening from 0.0 to 1.0
+// @param sharpness = 0.5
+
+@group(0) @binding(0) var r_color: texture_2d<f32>;
+@group(0) @binding(1) var r_sampler: sampler;
+
+fn sample(coords: vec2<f32>, x: f32, y: f32) -> vec3<f32> {
+    let offset = vec2<f32>(x, y) * r_uniforms.source_size.zw;
+    return textureSampleLevel(r_color, r_sampler, coords + offset, 0.0).rgb;
+}
+
+@fragment fn main(@builtin(position) _sv_position: vec4<f32>, @location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+    //  a b c
+    //  d e f
+    //  g h i
+    let a = sample(coords, -1.0, -1.0);
+    let b = sample(coords, 0.0, -1.0);
+    let c = sample(coords, 1.0, -1.0);
+    let d = sample(coords, -1.0, 0.0);
+    let center = textureSampleLevel(r_color, r_sampler, coords, 0.0);
+    let e = center.rgb;
+    let f = sample(coords, 1.0, 0.0);
+    let g = sample(coords, -1.0, 1.0);
+    let h = sample(coords, 0.0, 1.0);
+    let i = sample(coords, 1.0, 1.0);
+
+    // Soft min and max of the cross plus those of the whole neighbourhood
+    let mn_cross = min(min(min(d, e), min(f, b)), h);
+    let mn = mn_cross + min(mn_cross, min(min(a, c), min(g, i)));
+    let mx_cross = max(max(max(d, e), max(f, b)), h);
+    let mx = mx_cross + max(mx_cross, max(max(a, c), max(g, i)));
+
+    // Smooth minimum distance to signal limit divided by smooth max
+    let amp = sqrt(saturate(min(mn, 2.0 - mx) / max(mx, vec3<f32>(1e-5))));
+
+    // Filter shape
+    //  0 w 0
+    //  w 1 w
+    //  0 w 0
+    let peak = -1.0 / mix(8.0, 5.0, saturate(r_uniforms.sharpness));
+    let w = amp * peak;
+    let color = saturate((b * w + d * w + f * w + h * w + e) / (1.0 + 4.0 * w));
+    return vec4<f32>(color, center.a);
+}
@@ -0,0 +1,149 @@
+// AMD FidelityFX Super Resolution 1.0, Edge Adaptive Spatial Upsampling
+//
+// Port of `FsrEasuF` from ffx_fsr1.h (MIT, Copyright (c) 2021 Advanced Micro Devices, Inc.). Texels are read
+// with clamped `textureLoad` instead of gathers and the approximate reciprocals are exact.
+
+@group(0) @binding(0) var r_color: texture_2d<f32>;
+@group(0) @binding(1) var r_sampler: sampler;
+
+// Clamp-to-edge fetch of the 12-tap kernel
+//    b c
+//  e f g h
+//  i j k l
+//    n o
+fn fetch(position: vec2<i32>) -> vec3<f32> {
+    let last = vec2<i32>(textureDimensions(r_color)) - 1;
+    return textureLoad(r_color, clamp(position, vec2<i32>(0), last), 0).rgb;
+}
+
+// Luma times 2
+fn luma(color: vec3<f32>) -> f32 {
+    return color.b * 0.5 + (color.r * 0.5 + color.g);
+}
+
+struct Edge {
+    dir: vec2<f32>,
+    len: f32,
+}
+
+// Accumulates direction and length of the '+' around `c`, weighted bilinearly
+//    a
+//  b c d
+//    e
+fn edge(accumulated: Edge, w: f32, a: f32, b: f32, c: f32, d: f32, e: f32) -> Edge {
+    var result = accumulated;
+
+    let dc = d - c;
+    let cb = c - b;
+    let len_x = max(abs(dc), abs(cb));
+    let dir_x = d - b;
+    result.dir.x += dir_x * w;
+    var shape_x = select(0.0, saturate(abs(dir_x) / len_x), len_x > 0.0);
+    shape_x *= shape_x;
+    result.len += shape_x * w;
+
+    let ec = e - c;
+    let ca = c - a;
+    let len_y = max(abs(ec), abs(ca));
+    let dir_y = e - a;
+    result.dir.y += dir_y * w;
+    var shape_y = select(0.0, saturate(abs(dir_y) / len_y), len_y > 0.0);
+    shape_y *= shape_y;
+    result.len += shape_y * w;
+
+    return result;
+}
+
+// Weight of a tap at `offset` under the rotated, stretched approximation of Lanczos2
+fn tap_weight(offset: vec2<f32>, dir: vec2<f32>, len: vec2<f32>, lob: f32, clp: f32) -> f32 {
+    var v = vec2<f32>(offset.x * dir.x + offset.y * dir.y, offset.x * -dir.y + offset.y * dir.x);
+    v *= len;
+    let d2 = min(dot(v, v), clp);
+
+    //  (25/16 * (2/5 * x^2 - 1)^2 - (25/16 - 1)) * (1/4 * x^2 - 1)^2
+    var w_b = 2.0 / 5.0 * d2 - 1.0;
+    var w_a = lob * d2 - 1.0;
+    w_b *= w_b;
+    w_a *= w_a;
+    w_b = 25.0 / 16.0 * w_b - (25.0 / 16.0 - 1.0);
+    return w_b * w_a;
+}
+
+@fragment fn main(@builtin(position) _sv_position: vec4<f32>, @location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(r_color));
+    var pp = coords * size - 0.5;
+    let fp = floor(pp);
+    pp -= fp;
+    let f_position = vec2<i32>(fp);
+
+    let b = fetch(f_position + vec2<i32>(0, -1));
+    let c = fetch(f_position + vec2<i32>(1, -1));
+    let e = fetch(f_position + vec2<i32>(-1, 0));
+    let f = fetch(f_position);
+    let g = fetch(f_position + vec2<i32>(1, 0));
+    let h = fetch(f_position + vec2<i32>(2, 0));
+    let i = fetch(f_position + vec2<i32>(-1, 1));
+    let j = fetch(f_position + vec2<i32>(0, 1));
+    let k = fetch(f_position + vec2<i32>(1, 1));
+    let l = fetch(f_position + vec2<i32>(2, 1));
+    let n = fetch(f_position + vec2<i32>(0, 2));
+    let o = fetch(f_position + vec2<i32>(1, 2));
+
+    let b_l = luma(b);
+    let c_l = luma(c);
+    let e_l = luma(e);
+    let f_l = luma(f);
+    let g_l = luma(g);
+    let h_l = luma(h);
+    let i_l = luma(i);
+    let j_l = luma(j);
+    let k_l = luma(k);
+    let l_l = luma(l);
+    let n_l = luma(n);
+    let o_l = luma(o);
+
+    // Direction and edge length, bilinearly interpolated from the four centre texels
+    var acc = Edge(vec2<f32>(0.0), 0.0);
+    acc = edge(acc, (1.0 - pp.x) * (1.0 - pp.y), b_l, e_l, f_l, g_l, j_l);
+    acc = edge(acc, pp.x * (1.0 - pp.y), c_l, f_l, g_l, h_l, k_l);
+    acc = edge(acc, (1.0 - pp.x) * pp.y, f_l, i_l, j_l, k_l, n_l);
+    acc = edge(acc, pp.x * pp.y, g_l, j_l, k_l, l_l, o_l);
+
+    // Normalize, falling back to horizontal close to zero
+    var dir = acc.dir;
+    let dir_r = dot(dir, dir);
+    let zero = dir_r < 1.0 / 32768.0;
+    dir = select(dir * inverseSqrt(dir_r), vec2<f32>(1.0, 0.0), zero);
+
+    // Transform from {0 to 2} to {0 to 1} range, and shape with square
+    var len = acc.len * 0.5;
+    len *= len;
+
+    // Stretch kernel {1.0 vert|horz, to sqrt(2.0) on diagonal}
+    let stretch = dot(dir, dir) / max(abs(dir.x), abs(dir.y));
+    let len2 = vec2<f32>(1.0 + (stretch - 1.0) * len, 1.0 - 0.5 * len);
+
+    // The window shifts from +/-{sqrt(2.0) to slightly beyond 2.0} with the amount of edge
+    let lob = 0.5 + ((1.0 / 4.0 - 0.04) - 0.5) * len;
+    let clp = 1.0 / lob;
+
+    var color = vec3<f32>(0.0);
+    var weight = 0.0;
+    var taps = array<vec2<f32>, 12>(
+        vec2<f32>(0.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(-1.0, 1.0), vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 0.0), vec2<f32>(-1.0, 0.0), vec2<f32>(1.0, 1.0), vec2<f32>(2.0, 1.0),
+        vec2<f32>(2.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 2.0), vec2<f32>(0.0, 2.0),
+    );
+    var colors = array<vec3<f32>, 12>(b, c, i, j, f, e, k, l, h, g, o, n);
+    for (var tap = 0; tap < 12; tap++) {
+        let w = tap_weight(taps[tap] - pp, dir, len2, lob, clp);
+        color += colors[tap] * w;
+        weight += w;
+    }
+
+    // Normalize and dering with the four nearest texels
+    let min4 = min(min(f, g), min(j, k));
+    let max4 = max(max(f, g), max(j, k));
+    let alpha = textureSample(r_color, r_sampler, coords).a;
+    return vec4<f32>(min(max4, max(min4, color / weight)), alpha);
+}
@@ -0,0 +1,69 @@
+// AMD FidelityFX Super Resolution 1.0, Robust Contrast Adaptive Sharpening
+//
+// Port of `FsrRcasF` from ffx_fsr1.h (MIT, Copyright (c) 2021 Advanced Micro Devices, Inc.) with denoising
+// enabled. Runs at output resolution on the result of EASU.
+//
+// Sharpening in stops, 0.0 is the strongest and every stop halves it
+// @param sharpness = 0.2
+
+@group(0) @binding(0) var r_color: texture_2d<f32>;
+@group(0) @binding(1) var r_sampler: sampler;
+
+// Largest negative lobe, keeps the filter from blowing up
+const RCAS_LIMIT: f32 = 0.25 - 1.0 / 16.0;
+
+fn fetch(position: vec2<i32>) -> vec4<f32> {
+    let last = vec2<i32>(textureDimensions(r_color)) - 1;
+    return textureLoad(r_color, clamp(position, vec2<i32>(0), last), 0);
+}
+
+// Luma times 2
+fn luma(color: vec3<f32>) -> f32 {
+    return color.b * 0.5 + (color.r * 0.5 + color.g);
+}
+
+fn max3(a: f32, b: f32, c: f32) -> f32 {
+    return max(a, max(b, c));
+}
+
+fn min3(a: f32, b: f32, c: f32) -> f32 {
+    return min(a, min(b, c));
+}
+
+@fragment fn main(@builtin(position) _sv_position: vec4<f32>, @location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+    //    b
+    //  d e f
+    //    h
+    let sp = vec2<i32>(coords * vec2<f32>(textureDimensions(r_color)));
+    let b = fetch(sp + vec2<i32>(0, -1)).rgb;
+    let d = fetch(sp + vec2<i32>(-1, 0)).rgb;
+    let center = fetch(sp);
+    let e = center.rgb;
+    let f = fetch(sp + vec2<i32>(1, 0)).rgb;
+    let h = fetch(sp + vec2<i32>(0, 1)).rgb;
+
+    // Noise detection
+    let b_l = luma(b);
+    let d_l = luma(d);
+    let e_l = luma(e);
+    let f_l = luma(f);
+    let h_l = luma(h);
+    let range = max3(max3(b_l, d_l, e_l), f_l, h_l) - min3(min3(b_l, d_l, e_l), f_l, h_l);
+    var nz = 0.25 * b_l + 0.25 * d_l + 0.25 * f_l + 0.25 * h_l - e_l;
+    nz = select(0.0, saturate(abs(nz) / range), range > 0.0);
+    nz = -0.5 * nz + 1.0;
+
+    // Min and max of ring
+    let mn4 = min(min(b, d), min(f, h));
+    let mx4 = max(max(b, d), max(f, h));
+
+    // Lobe limits that keep the sharpened centre within [0, 1]
+    let hit_min = min(mn4, e) / max(4.0 * mx4, vec3<f32>(1e-30));
+    let hit_max = (1.0 - max(mx4, e)) / min(4.0 * mn4 - 4.0, vec3<f32>(-1e-30));
+    let lobe_rgb = max(-hit_min, hit_max);
+    var lobe = max(-RCAS_LIMIT, min(max3(lobe_rgb.r, lobe_rgb.g, lobe_rgb.b), 0.0)) * exp2(-r_uniforms.sharpness);
+    lobe *= nz;
+
+    let color = (lobe * (b + d + h + f) + e) / (4.0 * lobe + 1.0);
+    return vec4<f32>(color, center.a);
+}
@@ -0,0 +1,47 @@
+// Lanczos interpolation with `lobes` lobes, renormalized over the taps inside the image
+
+@group(0) @binding(0) var r_color: texture_2d<f32>;
+@group(0) @binding(1) var r_sampler: sampler;
+
+override lobes: i32 = 2;
+
+const PI: f32 = 3.14159265358979;
+
+fn weight(x: f32) -> f32 {
+    if (abs(x) < 1e-5) {
+        return 1.0;
+    }
+    let a = f32(lobes);
+    if (abs(x) >= a) {
+        return 0.0;
+    }
+    let px = PI * x;
+    return a * sin(px) * sin(px / a) / (px * px);
+}
+
+@fragment fn main(@builtin(position) _sv_position: vec4<f32>, @location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(r_color));
+    let position = coords * vec2<f32>(size) - 0.5;
+    let fp = floor(position);
+    let origin = vec2<i32>(fp);
+
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var y = 1 - lobes; y <= lobes; y++) {
+        let row = origin.y + y;
+        if (row < 0 || row >= size.y) {
+            continue;
+        }
+        let wy = weight(fp.y + f32(y) - position.y);
+        for (var x = 1 - lobes; x <= lobes; x++) {
+            let column = origin.x + x;
+            if (column < 0 || column >= size.x) {
+                continue;
+            }
+            let w = wy * weight(fp.x + f32(x) - position.x);
+            sum += w * textureLoad(r_color, vec2<i32>(column, row), 0);
+            total += w;
+        }
+    }
+    return sum / total;
+}
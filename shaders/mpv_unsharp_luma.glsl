@@ -0,0 +1,33 @@
+// Example mpv user shader: doubles luma with an unsharp mask
+//
+// Exercises saved textures, extra bindings and size expressions of the //!HOOK format.
+
+//!DESC Luma blur
+//!HOOK LUMA
+//!BIND HOOKED
+//!SAVE BLURRED
+//!COMPONENTS 1
+
+vec4 hook() {
+    float sum = 0.0;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            sum += HOOKED_texOff(vec2(x, y)).x;
+        }
+    }
+    return vec4(sum / 9.0, 0.0, 0.0, 1.0);
+}
+
+//!DESC Luma unsharp doubling
+//!HOOK LUMA
+//!BIND HOOKED
+//!BIND BLURRED
+//!WIDTH HOOKED.w 2 *
+//!HEIGHT 2 HOOKED.h *
+//!COMPONENTS 1
+
+vec4 hook() {
+    float luma = HOOKED_tex(HOOKED_pos).x;
+    float blurred = BLURRED_tex(BLURRED_pos).x;
+    return vec4(clamp(luma + 0.5 * (luma - blurred), 0.0, 1.0), 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform texture2D r_color;
+layout(set = 0, binding = 1) uniform sampler r_sampler;
+
+layout(location = 0) in vec2 coords;
+layout(location = 0) out vec4 color;
+
+void main() {
+    vec4 texel = texture(sampler2D(r_color, r_sampler), coords);
+    color = texel.bgra;
+}
@@ -0,0 +1,20 @@
+// Sharp bilinear for pixel art
+//
+// Equivalent to a nearest-neighbour upscale by the integer part of the scale followed by bilinear filtering of
+// the rest, so texels stay crisp squares with a one-pixel blend at their borders.
+
+@group(0) @binding(0) var r_color: texture_2d<f32>;
+@group(0) @binding(1) var r_sampler: sampler;
+
+@fragment fn main(@builtin(position) _sv_position: vec4<f32>, @location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(r_color));
+    let prescale = max(floor(r_uniforms.scale), vec2<f32>(1.0));
+    let texel = coords * size;
+    let texel_floored = floor(texel);
+
+    // Flat inside the texel, ramping linearly over its last `0.5 / prescale` to each side
+    let region = 0.5 - 0.5 / prescale;
+    let center_dist = texel - texel_floored - 0.5;
+    let f = (center_dist - clamp(center_dist, -region, region)) * prescale + 0.5;
+    return textureSampleLevel(r_color, r_sampler, (texel_floored + f) / size, 0.0);
+}
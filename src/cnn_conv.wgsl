@@ -0,0 +1,49 @@
+// Same-padded convolution with optional PReLU over channels packed four to a texel
+//
+// Feature maps are [groups][height][width] vec4s, weights are [output group][input group][tap] matrices
+// whose columns are the input channels of the group.
+
+struct Layer {
+    width: u32,
+    height: u32,
+    input_groups: u32,
+    output_groups: u32,
+    radius: i32,
+    prelu: u32,
+}
+
+@group(0) @binding(0) var<uniform> layer: Layer;
+@group(0) @binding(1) var<storage, read> features_in: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> features_out: array<vec4<f32>>;
+@group(0) @binding(3) var<storage, read> weights: array<mat4x4<f32>>;
+@group(0) @binding(4) var<storage, read> bias: array<vec4<f32>>;
+@group(0) @binding(5) var<storage, read> slopes: array<vec4<f32>>;
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= layer.width || id.y >= layer.height || id.z >= layer.output_groups) {
+        return;
+    }
+
+    let side = u32(2 * layer.radius + 1);
+    let size = vec2<i32>(i32(layer.width), i32(layer.height));
+    var sum = bias[id.z];
+    for (var group = 0u; group < layer.input_groups; group++) {
+        let plane = group * layer.width * layer.height;
+        var tap = (id.z * layer.input_groups + group) * side * side;
+        for (var dy = -layer.radius; dy <= layer.radius; dy++) {
+            for (var dx = -layer.radius; dx <= layer.radius; dx++) {
+                let position = vec2<i32>(id.xy) + vec2<i32>(dx, dy);
+                if (all(position >= vec2<i32>(0)) && all(position < size)) {
+                    sum += weights[tap] * features_in[plane + u32(position.y) * layer.width + u32(position.x)];
+                }
+                tap++;
+            }
+        }
+    }
+
+    if (layer.prelu != 0u) {
+        sum = select(sum * slopes[id.z], sum, sum >= vec4<f32>(0.0));
+    }
+    features_out[(id.z * layer.height + id.y) * layer.width + id.x] = sum;
+}
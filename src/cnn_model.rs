@@ -0,0 +1,993 @@
+//! Compact convolutional super-resolution networks and their CPU reference
+//!
+//! SRVGGNetCompact (Real-ESRGAN's `realesr-general-x4v3`, `realesr-animevideov3`) and FSRCNN both
+//! reduce to a stack of same-padded convolutions with PReLU followed by a pixel shuffle. FSRCNN's
+//! strided deconvolution is rewritten into that form on load, every output phase becomes a channel
+//! of a plain convolution.
+//!
+//! Weights are read from safetensors or from the simpler format of [`write_tensors`], under the
+//! names of the PyTorch state dicts of the reference implementations.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+use image::Rgba32FImage;
+use rayon::prelude::*;
+use safetensors::{Dtype, SafeTensors};
+
+use crate::{
+    cpu_algo::{ResampleKernel, Resampler},
+    error::Error,
+};
+
+const MAGIC: &[u8; 8] = b"CNNTENS1";
+
+/// Prefixes PyTorch checkpoints nest state dicts under
+const PREFIXES: [&str; 4] = ["", "params_ema.", "params.", "module."];
+
+/// Dense `f32` tensor in row-major order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+/// Named tensors of a state dict
+pub type Tensors = BTreeMap<String, Tensor>;
+
+/// Reads tensors from a safetensors file or from the format of [`write_tensors`]
+///
+/// Safetensors may store `F32`, `F64`, `F16` or `BF16` values, all are widened to `f32`.
+pub fn read_tensors(path: impl AsRef<Path>) -> Result<Tensors, Error> {
+    let bytes = std::fs::read(path)?;
+    match bytes.strip_prefix(MAGIC) {
+        Some(body) => parse_tensors(body),
+        None => parse_safetensors(&bytes),
+    }
+}
+
+/// Writes tensors as a signature, a `u32` count and for every tensor its name, rank and shape, then
+/// data
+///
+/// Integers are little-endian `u32`, names are prefixed by their byte length and data is
+/// little-endian `f32`.
+pub fn write_tensors(path: impl AsRef<Path>, tensors: &Tensors) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(tensors.len() as u32).to_le_bytes())?;
+    for (name, tensor) in tensors {
+        writer.write_all(&(name.len() as u32).to_le_bytes())?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(&(tensor.shape.len() as u32).to_le_bytes())?;
+        for &dim in &tensor.shape {
+            writer.write_all(&(dim as u32).to_le_bytes())?;
+        }
+        for value in &tensor.data {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(writer.flush()?)
+}
+
+/// Number of values a tensor of `shape` holds, `None` if it overflows
+fn elements(shape: &[usize]) -> Option<usize> {
+    shape
+        .iter()
+        .try_fold(1, |count: usize, &dim| count.checked_mul(dim))
+}
+
+/// Checks that the data of `tensors` fills their shapes
+fn check_filled(layer: &str, tensors: [&Tensor; 2]) -> Result<(), Error> {
+    match tensors
+        .iter()
+        .find(|tensor| elements(&tensor.shape) != Some(tensor.data.len()))
+    {
+        Some(tensor) => Err(invalid(&format!(
+            "{layer}: {} values don't fill shape {:?}",
+            tensor.data.len(),
+            tensor.shape
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn parse_tensors(mut reader: &[u8]) -> Result<Tensors, Error> {
+    let read_u32 = |reader: &mut &[u8]| -> Result<usize, Error> {
+        let mut bytes = [0; 4];
+        reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes) as usize)
+    };
+
+    let mut tensors = Tensors::new();
+    for _ in 0..read_u32(&mut reader)? {
+        let length = read_u32(&mut reader)?;
+        let name = reader
+            .get(..length)
+            .ok_or_else(|| invalid("truncated tensor name"))?;
+        let name =
+            String::from_utf8(name.to_vec()).map_err(|_| invalid("tensor name is not UTF-8"))?;
+        reader = &reader[length..];
+
+        let rank = read_u32(&mut reader)?;
+        let shape = (0..rank)
+            .map(|_| read_u32(&mut reader))
+            .collect::<Result<Vec<_>, _>>()?;
+        let bytes = elements(&shape)
+            .and_then(|count| count.checked_mul(4))
+            .ok_or_else(|| invalid(&format!("{name}: shape {shape:?} is too large")))?;
+        let data = reader
+            .get(..bytes)
+            .ok_or_else(|| invalid(&format!("{name}: truncated data")))?;
+        let data = data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        reader = &reader[bytes..];
+        tensors.insert(name, Tensor { shape, data });
+    }
+    Ok(tensors)
+}
+
+fn parse_safetensors(bytes: &[u8]) -> Result<Tensors, Error> {
+    let mut tensors = Tensors::new();
+    for (name, view) in SafeTensors::deserialize(bytes)?.tensors() {
+        let bytes = view.data();
+        let data = match view.dtype() {
+            Dtype::F32 => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            Dtype::F64 => bytes
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32)
+                .collect(),
+            Dtype::F16 => bytes
+                .chunks_exact(2)
+                .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+                .collect(),
+            Dtype::BF16 => bytes
+                .chunks_exact(2)
+                .map(|b| bf16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+                .collect(),
+            dtype => return Err(invalid(&format!("{name}: unsupported dtype {dtype:?}"))),
+        };
+        tensors.insert(
+            name,
+            Tensor {
+                shape: view.shape().to_vec(),
+                data,
+            },
+        );
+    }
+    Ok(tensors)
+}
+
+/// Widens IEEE 754 half precision
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = (bits as u32 >> 15) << 31;
+    let exponent = (bits as u32 >> 10) & 0x1f;
+    let mantissa = bits as u32 & 0x3ff;
+    match exponent {
+        0 => {
+            let magnitude = mantissa as f32 * (-24f32).exp2();
+            if sign == 0 {
+                magnitude
+            } else {
+                -magnitude
+            }
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | mantissa << 13),
+        _ => f32::from_bits(sign | (exponent + 112) << 23 | mantissa << 13),
+    }
+}
+
+/// Widens bfloat16, the upper half of an `f32`
+fn bf16_to_f32(bits: u16) -> f32 {
+    f32::from_bits((bits as u32) << 16)
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidWeights(reason.to_string())
+}
+
+/// Network family, named after its reference PyTorch implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Architecture {
+    /// Real-ESRGAN's SRVGGNetCompact with PReLU activations, tensors `body.{n}.weight` and
+    /// `body.{n}.bias`
+    SrvggCompact,
+
+    /// FSRCNN on studio-range luma, tensors `first_part.{n}`, `mid_part.{n}` and `last_part`
+    ///
+    /// The deconvolution has stride `factor`, padding of half its kernel and output padding `factor
+    /// - 1`.
+    Fsrcnn { factor: u32 },
+}
+
+/// Colour channels a network sees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NetworkInput {
+    Rgb,
+
+    /// BT.601 studio-range luma, chroma comes from a Catmull-Rom upscale
+    Luma,
+}
+
+/// Same-padded convolution, optionally followed by PReLU
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Conv {
+    pub(crate) inputs: usize,
+    pub(crate) outputs: usize,
+    pub(crate) radius: usize,
+
+    /// `[outputs][inputs][side][side]` with `side = 2 * radius + 1`
+    pub(crate) weights: Vec<f32>,
+    pub(crate) bias: Vec<f32>,
+
+    /// Per-output negative slopes, `None` for a linear layer
+    pub(crate) slopes: Option<Vec<f32>>,
+}
+
+impl Conv {
+    pub(crate) fn side(&self) -> usize {
+        2 * self.radius + 1
+    }
+
+    fn from_tensors(tensors: &Tensors, layer: &str) -> Result<Self, Error> {
+        let weight = find(tensors, &format!("{layer}.weight"))?;
+        let bias = find(tensors, &format!("{layer}.bias"))?;
+        let &[outputs, inputs, side, width] = weight.shape.as_slice() else {
+            return Err(invalid(&format!(
+                "{layer}: expected a 4D kernel, got {:?}",
+                weight.shape
+            )));
+        };
+        if side != width || side % 2 == 0 {
+            return Err(invalid(&format!(
+                "{layer}: kernel has to be square with odd side, got {side}x{width}"
+            )));
+        }
+        if bias.shape != [outputs] {
+            return Err(invalid(&format!(
+                "{layer}: expected {outputs} biases, got {:?}",
+                bias.shape
+            )));
+        }
+        check_filled(layer, [weight, bias])?;
+        Ok(Self {
+            inputs,
+            outputs,
+            radius: side / 2,
+            weights: weight.data.clone(),
+            bias: bias.data.clone(),
+            slopes: None,
+        })
+    }
+
+    /// Rewrites a transposed convolution with stride `factor`, padding `kernel / 2` and output
+    /// padding `factor - 1` as a convolution whose `factor²` outputs per channel are shuffled into
+    /// place
+    fn from_transposed(tensors: &Tensors, layer: &str, factor: usize) -> Result<Self, Error> {
+        let weight = find(tensors, &format!("{layer}.weight"))?;
+        let bias = find(tensors, &format!("{layer}.bias"))?;
+        let &[inputs, outputs, kernel, width] = weight.shape.as_slice() else {
+            return Err(invalid(&format!(
+                "{layer}: expected a 4D kernel, got {:?}",
+                weight.shape
+            )));
+        };
+        if kernel != width || kernel % 2 == 0 || bias.shape != [outputs] {
+            return Err(invalid(&format!(
+                "{layer}: unexpected shapes {:?} and {:?}",
+                weight.shape, bias.shape
+            )));
+        }
+        check_filled(layer, [weight, bias])?;
+
+        // Output `q * factor + phase` gathers input `q - t` through tap `t * factor + phase +
+        // padding`
+        let padding = kernel / 2;
+        let radius = padding.div_ceil(factor);
+        let side = 2 * radius + 1;
+        let tap = |k: usize, phase: usize| {
+            let tap = (radius as isize - k as isize) * factor as isize + (phase + padding) as isize;
+            (0..kernel as isize).contains(&tap).then_some(tap as usize)
+        };
+
+        let phases = factor * factor;
+        let mut weights = vec![0.0; outputs * phases * inputs * side * side];
+        for (channel, taps) in weights.chunks_exact_mut(inputs * side * side).enumerate() {
+            let (output, phase_y, phase_x) = (
+                channel / phases,
+                channel % phases / factor,
+                channel % factor,
+            );
+            for (index, value) in taps.iter_mut().enumerate() {
+                let (input, k) = (index / (side * side), index % (side * side));
+                if let (Some(y), Some(x)) = (tap(k / side, phase_y), tap(k % side, phase_x)) {
+                    *value = weight.data[((input * outputs + output) * kernel + y) * kernel + x];
+                }
+            }
+        }
+
+        Ok(Self {
+            inputs,
+            outputs: outputs * phases,
+            radius,
+            weights,
+            bias: bias
+                .data
+                .iter()
+                .flat_map(|&b| std::iter::repeat_n(b, phases))
+                .collect(),
+            slopes: None,
+        })
+    }
+
+    /// Applies the layer to planar `[inputs][height][width]` features
+    fn apply(&self, features: &[f32], width: usize, height: usize) -> Vec<f32> {
+        let (side, area, radius) = (self.side(), width * height, self.radius as isize);
+        let mut output = vec![0.0; self.outputs * area];
+        output
+            .par_chunks_mut(area)
+            .enumerate()
+            .for_each(|(channel, plane)| {
+                plane.fill(self.bias[channel]);
+                let weights = &self.weights[channel * self.inputs * side * side..]
+                    [..self.inputs * side * side];
+                for (input, taps) in features
+                    .chunks_exact(area)
+                    .zip(weights.chunks_exact(side * side))
+                {
+                    for (k, &weight) in taps.iter().enumerate() {
+                        let (dy, dx) = ((k / side) as isize - radius, (k % side) as isize - radius);
+                        // Columns whose tap stays inside the row, the rest reads zero padding
+                        let (start, end) = (
+                            (-dx).max(0) as usize,
+                            (width as isize - dx).clamp(0, width as isize) as usize,
+                        );
+                        for y in 0..height {
+                            let source_y = y as isize + dy;
+                            if start >= end || source_y < 0 || source_y >= height as isize {
+                                continue;
+                            }
+                            let source = &input[source_y as usize * width..]
+                                [(start as isize + dx) as usize..];
+                            for (value, &sample) in plane[y * width + start..y * width + end]
+                                .iter_mut()
+                                .zip(source)
+                            {
+                                *value += weight * sample;
+                            }
+                        }
+                    }
+                }
+                if let Some(slopes) = &self.slopes {
+                    plane
+                        .iter_mut()
+                        .filter(|v| **v < 0.0)
+                        .for_each(|v| *v *= slopes[channel]);
+                }
+            });
+        output
+    }
+}
+
+/// Looks `name` up, also under the prefixes of training checkpoints
+fn find<'a>(tensors: &'a Tensors, name: &str) -> Result<&'a Tensor, Error> {
+    PREFIXES
+        .iter()
+        .find_map(|prefix| tensors.get(&format!("{prefix}{name}")))
+        .ok_or_else(|| invalid(&format!("missing tensor {name}")))
+}
+
+/// Reads layers `{prefix}.0`, `{prefix}.1`, ... of convolutions, each optionally followed by its
+/// PReLU
+fn conv_stack(tensors: &Tensors, prefix: &str, convs: &mut Vec<Conv>) -> Result<(), Error> {
+    let mut index = 0;
+    while let Ok(weight) = find(tensors, &format!("{prefix}.{index}.weight")) {
+        match (weight.shape.len(), convs.last_mut()) {
+            (4, _) => convs.push(Conv::from_tensors(tensors, &format!("{prefix}.{index}"))?),
+            (1, Some(conv @ Conv { slopes: None, .. }))
+                if [1, conv.outputs].contains(&weight.data.len()) =>
+            {
+                conv.slopes = Some(
+                    weight
+                        .data
+                        .iter()
+                        .cycle()
+                        .take(conv.outputs)
+                        .copied()
+                        .collect(),
+                );
+            }
+            _ => {
+                return Err(invalid(&format!(
+                    "{prefix}.{index}: unexpected shape {:?}",
+                    weight.shape
+                )))
+            }
+        }
+        index += 1;
+    }
+    if find(tensors, &format!("{prefix}.{}.weight", index + 1)).is_ok() {
+        return Err(invalid(&format!(
+            "{prefix}.{index} has no weights, only PReLU activations are supported"
+        )));
+    }
+    Ok(())
+}
+
+/// Convolutional network followed by a pixel shuffle
+#[derive(Debug, Clone, PartialEq)]
+pub struct CnnModel {
+    pub(crate) convs: Vec<Conv>,
+    factor: u32,
+    input: NetworkInput,
+
+    /// Adds the nearest-neighbour upscale of the input to the output
+    residual: bool,
+}
+
+impl CnnModel {
+    /// Reads weights of `architecture` from a safetensors file or the format of [`write_tensors`]
+    pub fn load(path: impl AsRef<Path>, architecture: Architecture) -> Result<Self, Error> {
+        Self::from_tensors(&read_tensors(path)?, architecture)
+    }
+
+    pub fn from_tensors(tensors: &Tensors, architecture: Architecture) -> Result<Self, Error> {
+        let mut convs = Vec::new();
+        let model = match architecture {
+            Architecture::SrvggCompact => {
+                conv_stack(tensors, "body", &mut convs)?;
+                let (first, last) = match (convs.first(), convs.last()) {
+                    (Some(first), Some(last)) => (first, last),
+                    _ => return Err(invalid("missing tensor body.0.weight")),
+                };
+                if first.inputs != 3 || last.slopes.is_some() {
+                    return Err(invalid("expected 3 input channels and a linear last layer"));
+                }
+                let factor = (last.outputs as f64 / 3.0).sqrt().round() as u32;
+                if factor == 0 || 3 * (factor * factor) as usize != last.outputs {
+                    return Err(invalid(&format!(
+                        "{} outputs aren't a pixel shuffle of RGB",
+                        last.outputs
+                    )));
+                }
+                Self {
+                    convs,
+                    factor,
+                    input: NetworkInput::Rgb,
+                    residual: true,
+                }
+            }
+            Architecture::Fsrcnn { factor } => {
+                if !(1..=16).contains(&factor) {
+                    return Err(invalid(&format!("factor {factor} out of range")));
+                }
+                conv_stack(tensors, "first_part", &mut convs)?;
+                conv_stack(tensors, "mid_part", &mut convs)?;
+                convs.push(Conv::from_transposed(
+                    tensors,
+                    "last_part",
+                    factor as usize,
+                )?);
+                if convs[0].inputs != 1
+                    || convs
+                        .last()
+                        .is_some_and(|last| last.outputs != (factor * factor) as usize)
+                {
+                    return Err(invalid("expected a single luma channel"));
+                }
+                Self {
+                    convs,
+                    factor,
+                    input: NetworkInput::Luma,
+                    residual: false,
+                }
+            }
+        };
+
+        for (layer, pair) in model.convs.windows(2).enumerate() {
+            if pair[0].outputs != pair[1].inputs {
+                let (outputs, inputs) = (pair[0].outputs, pair[1].inputs);
+                return Err(invalid(&format!(
+                    "layer {layer} outputs {outputs} channels, the next one takes {inputs}"
+                )));
+            }
+        }
+        Ok(model)
+    }
+
+    /// Returns the upscaling factor of the pixel shuffle
+    pub fn factor(&self) -> u32 {
+        self.factor
+    }
+
+    pub fn input(&self) -> NetworkInput {
+        self.input
+    }
+
+    /// Returns how many input pixels around a pixel affect its output
+    pub fn receptive_radius(&self) -> u32 {
+        self.convs.iter().map(|conv| conv.radius as u32).sum()
+    }
+
+    pub(crate) fn residual(&self) -> bool {
+        self.residual
+    }
+
+    /// Returns the number of channels the network takes and outputs
+    pub(crate) fn channels(&self) -> usize {
+        match self.input {
+            NetworkInput::Rgb => 3,
+            NetworkInput::Luma => 1,
+        }
+    }
+
+    /// Upscales `image` on the CPU, the reference for
+    /// [`GPUCnnUpscaler`](crate::gpu_cnn::GPUCnnUpscaler)
+    pub fn run(&self, image: &Rgba32FImage) -> Rgba32FImage {
+        let (width, height) = image.dimensions();
+        let output = self.forward(&self.input_planes(image), width as usize, height as usize);
+        self.compose(image, &output)
+    }
+
+    /// Splits `image` into planar `[channels][height][width]` network input
+    pub(crate) fn input_planes(&self, image: &Rgba32FImage) -> Vec<f32> {
+        match self.input {
+            NetworkInput::Rgb => (0..3)
+                .flat_map(|c| image.pixels().map(move |p| p[c]))
+                .collect(),
+            NetworkInput::Luma => image.pixels().map(|p| luma(p.0)).collect(),
+        }
+    }
+
+    /// Runs the network on planar input, returns planar output `factor` times larger
+    pub(crate) fn forward(&self, planes: &[f32], width: usize, height: usize) -> Vec<f32> {
+        let mut features = planes.to_vec();
+        for conv in &self.convs {
+            features = conv.apply(&features, width, height);
+        }
+
+        let factor = self.factor as usize;
+        let (wide, area) = (width * factor, width * height);
+        let mut output = vec![0.0; self.channels() * area * factor * factor];
+        for (index, value) in output.iter_mut().enumerate() {
+            let (channel, y, x) = (
+                index / (area * factor * factor),
+                index / wide % (height * factor),
+                index % wide,
+            );
+            let phase = (y % factor) * factor + x % factor;
+            let source = (y / factor) * width + x / factor;
+            *value = features[(channel * factor * factor + phase) * area + source];
+            if self.residual {
+                *value += planes[channel * area + source];
+            }
+        }
+        output
+    }
+
+    /// Turns planar network output back into an image `factor` times larger than `image`
+    pub(crate) fn compose(&self, image: &Rgba32FImage, planes: &[f32]) -> Rgba32FImage {
+        let factor = self.factor;
+        let (width, height) = (image.width() * factor, image.height() * factor);
+        let area = (width * height) as usize;
+        match self.input {
+            NetworkInput::Rgb => Rgba32FImage::from_fn(width, height, |x, y| {
+                let index = (y * width + x) as usize;
+                let alpha = image.get_pixel(x / factor, y / factor)[3];
+                let [r, g, b] = [0, 1, 2].map(|c| planes[c * area + index].clamp(0.0, 1.0));
+                image::Rgba([r, g, b, alpha])
+            }),
+            NetworkInput::Luma => {
+                let resampler = Resampler::new(
+                    ResampleKernel::CATMULL_ROM,
+                    image.dimensions(),
+                    (width, height),
+                );
+                let mut upscaled = resampler.apply(image);
+                for (pixel, &y) in upscaled.pixels_mut().zip(planes) {
+                    // Every RGB channel moves by the same amount along the luma axis of
+                    // studio-range YCbCr
+                    let delta = (y - luma(pixel.0)) * 255.0 / 219.0;
+                    for c in 0..3 {
+                        pixel[c] = (pixel[c] + delta).clamp(0.0, 1.0);
+                    }
+                    pixel[3] = pixel[3].clamp(0.0, 1.0);
+                }
+                upscaled
+            }
+        }
+    }
+}
+
+/// BT.601 studio-range luma of linear-coded `[0, 1]` RGB, as FSRCNN was trained on
+fn luma([r, g, b, _]: [f32; 4]) -> f32 {
+    (16.0 + 65.481 * r + 128.553 * g + 24.966 * b) / 255.0
+}
+
+/// Deterministic value in `[-scale, scale]`
+#[cfg(any(test, feature = "bench-helpers"))]
+fn noise(seed: u32, index: usize, scale: f32) -> f32 {
+    let mut x = (index as u64) << 32 | seed as u64;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    ((x >> 40) as f32 / (1u64 << 23) as f32 - 1.0) * scale
+}
+
+#[cfg(any(test, feature = "bench-helpers"))]
+fn random_tensor(shape: Vec<usize>, seed: u32, scale: f32) -> Tensor {
+    let data = (0..shape.iter().product())
+        .map(|i| noise(seed, i, scale))
+        .collect();
+    Tensor { shape, data }
+}
+
+/// PReLU slopes around PyTorch's initial 0.25
+#[cfg(any(test, feature = "bench-helpers"))]
+fn random_slopes(count: usize, seed: u32) -> Tensor {
+    let data = (0..count).map(|i| 0.25 + noise(seed, i, 0.2)).collect();
+    Tensor {
+        shape: vec![count],
+        data,
+    }
+}
+
+/// Randomly initialized SRVGGNetCompact weights with `convs` hidden layers of `features` channels
+///
+/// Useful for tests and benchmarks, `realesr-general-x4v3` is `random_srvgg(64, 32, 4, _)`.
+#[cfg(any(test, feature = "bench-helpers"))]
+pub fn random_srvgg(features: usize, convs: usize, factor: u32, seed: u32) -> Tensors {
+    let mut tensors = Tensors::new();
+    let mut channels = 3;
+    for layer in 0..=convs {
+        let outputs = if layer == convs {
+            3 * (factor * factor) as usize
+        } else {
+            features
+        };
+        let seed = seed.wrapping_add(layer as u32 * 3);
+        let scale = (1.0 / (channels * 9) as f32).sqrt();
+        tensors.insert(
+            format!("body.{}.weight", 2 * layer),
+            random_tensor(vec![outputs, channels, 3, 3], seed, scale),
+        );
+        tensors.insert(
+            format!("body.{}.bias", 2 * layer),
+            random_tensor(vec![outputs], seed + 1, 0.1),
+        );
+        if layer < convs {
+            tensors.insert(
+                format!("body.{}.weight", 2 * layer + 1),
+                random_slopes(features, seed + 2),
+            );
+        }
+        channels = features;
+    }
+    tensors
+}
+
+/// Randomly initialized FSRCNN weights with `d` features, `s` shrunk features and `m` mapping
+/// layers
+#[cfg(any(test, feature = "bench-helpers"))]
+pub fn random_fsrcnn(d: usize, s: usize, m: usize, seed: u32) -> Tensors {
+    let mut tensors = Tensors::new();
+    let mut add =
+        |name: String, outputs: usize, inputs: usize, side: usize, prelu: Option<String>| {
+            let seed = seed.wrapping_add(tensors.len() as u32);
+            let scale = (1.0 / (inputs * side * side) as f32).sqrt();
+            tensors.insert(
+                format!("{name}.weight"),
+                random_tensor(vec![outputs, inputs, side, side], seed, scale),
+            );
+            tensors.insert(
+                format!("{name}.bias"),
+                random_tensor(vec![outputs], seed + 1, 0.1),
+            );
+            if let Some(prelu) = prelu {
+                tensors.insert(prelu, random_slopes(outputs, seed + 2));
+            }
+        };
+
+    add(
+        "first_part.0".into(),
+        d,
+        1,
+        5,
+        Some("first_part.1.weight".into()),
+    );
+    add(
+        "mid_part.0".into(),
+        s,
+        d,
+        1,
+        Some("mid_part.1.weight".into()),
+    );
+    for layer in 0..m {
+        add(
+            format!("mid_part.{}", 2 + 2 * layer),
+            s,
+            s,
+            3,
+            Some(format!("mid_part.{}.weight", 3 + 2 * layer)),
+        );
+    }
+    add(
+        format!("mid_part.{}", 2 + 2 * m),
+        d,
+        s,
+        1,
+        Some(format!("mid_part.{}.weight", 3 + 2 * m)),
+    );
+
+    // Transposed convolution kernels are `[inputs][outputs][kernel][kernel]`
+    let scale = (1.0 / (d * 9) as f32).sqrt();
+    tensors.insert(
+        "last_part.weight".into(),
+        random_tensor(vec![d, 1, 9, 9], seed.wrapping_sub(1), scale),
+    );
+    tensors.insert(
+        "last_part.bias".into(),
+        Tensor {
+            shape: vec![1],
+            data: vec![0.5],
+        },
+    );
+    tensors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_image(width: u32, height: u32) -> Rgba32FImage {
+        Rgba32FImage::from_fn(width, height, |x, y| {
+            image::Rgba([
+                x as f32 / width as f32,
+                y as f32 / height as f32,
+                ((x * 7 + y * 3) % 5) as f32 / 4.0,
+                1.0,
+            ])
+        })
+    }
+
+    /// PyTorch's `ConvTranspose2d` of a single channel, with the FSRCNN padding
+    fn transposed(
+        tensors: &Tensors,
+        input: &[f32],
+        width: usize,
+        height: usize,
+        factor: usize,
+    ) -> Vec<f32> {
+        let (weight, bias) = (&tensors["last_part.weight"], &tensors["last_part.bias"]);
+        let (inputs, kernel) = (weight.shape[0], weight.shape[2]);
+        let padding = kernel / 2;
+        let (wide, tall) = (width * factor, height * factor);
+        let mut output = vec![bias.data[0]; wide * tall];
+        for c in 0..inputs {
+            for y in 0..height {
+                for x in 0..width {
+                    for ky in 0..kernel {
+                        for kx in 0..kernel {
+                            let oy = (y * factor + ky) as isize - padding as isize;
+                            let ox = (x * factor + kx) as isize - padding as isize;
+                            if (0..tall as isize).contains(&oy) && (0..wide as isize).contains(&ox)
+                            {
+                                let w = weight.data[(c * kernel + ky) * kernel + kx];
+                                output[oy as usize * wide + ox as usize] +=
+                                    w * input[(c * height + y) * width + x];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn subpixel_matches_transposed_convolution() {
+        let tensors = random_fsrcnn(4, 2, 1, 7);
+        let (width, height) = (7, 5);
+        let features: Vec<f32> = (0..4 * width * height).map(|i| noise(3, i, 1.0)).collect();
+        for factor in 1..=4 {
+            let conv = Conv::from_transposed(&tensors, "last_part", factor).unwrap();
+            let phases = conv.apply(&features, width, height);
+            let expected = transposed(&tensors, &features, width, height, factor);
+            for (index, &e) in expected.iter().enumerate() {
+                let (y, x) = (index / (width * factor), index % (width * factor));
+                let phase = (y % factor) * factor + x % factor;
+                let actual = phases[phase * width * height + (y / factor) * width + x / factor];
+                assert!(
+                    (e - actual).abs() < 1e-5,
+                    "{factor}x at ({x}, {y}): expected {e}, got {actual}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn zero_network_is_nearest() {
+        let mut tensors = random_srvgg(4, 1, 2, 0);
+        tensors.values_mut().for_each(|t| t.data.fill(0.0));
+        let model = CnnModel::from_tensors(&tensors, Architecture::SrvggCompact).unwrap();
+        assert_eq!((model.factor(), model.receptive_radius()), (2, 2));
+
+        let image = test_image(5, 3);
+        let upscaled = model.run(&image);
+        assert_eq!(upscaled.dimensions(), (10, 6));
+        for (x, y, pixel) in upscaled.enumerate_pixels() {
+            assert_eq!(pixel, image.get_pixel(x / 2, y / 2));
+        }
+    }
+
+    #[test]
+    fn fsrcnn_keeps_chroma() {
+        let mut tensors = random_fsrcnn(4, 2, 1, 1);
+        tensors
+            .values_mut()
+            .filter(|t| t.shape != [1])
+            .for_each(|t| t.data.fill(0.0));
+        let model = CnnModel::from_tensors(&tensors, Architecture::Fsrcnn { factor: 3 }).unwrap();
+        // 5x5 extraction, one 3x3 mapping layer and the deconvolution reaching 2 input pixels at 3x
+        assert_eq!(model.receptive_radius(), 5);
+
+        // Only the bias of the output layer is left, the network outputs flat luma and colours keep
+        // their chroma
+        let image = test_image(6, 4);
+        let upscaled = model.run(&image);
+        let cheap = Resampler::new(ResampleKernel::CATMULL_ROM, (6, 4), (18, 12)).apply(&image);
+        let mut unclamped = 0;
+        for (actual, expected) in upscaled.pixels().zip(cheap.pixels()) {
+            if actual.0[..3].iter().all(|&c| c > 0.0 && c < 1.0) {
+                assert!((luma(actual.0) - 0.5).abs() < 1e-5, "{:?}", actual.0);
+                let (r, g, b) = (
+                    actual[0] - expected[0],
+                    actual[1] - expected[1],
+                    actual[2] - expected[2],
+                );
+                assert!(
+                    (r - g).abs() < 1e-5 && (g - b).abs() < 1e-5,
+                    "{:?} vs {:?}",
+                    actual.0,
+                    expected.0
+                );
+                unclamped += 1;
+            }
+        }
+        assert!(unclamped > 100, "{unclamped}");
+    }
+
+    #[test]
+    fn rejects_malformed_stacks() {
+        let mut tensors = random_srvgg(4, 2, 2, 0);
+        tensors.remove("body.1.weight");
+        assert!(matches!(
+            CnnModel::from_tensors(&tensors, Architecture::SrvggCompact),
+            Err(Error::InvalidWeights(_))
+        ));
+
+        let tensors = random_srvgg(4, 2, 2, 0);
+        assert!(CnnModel::from_tensors(&tensors, Architecture::Fsrcnn { factor: 2 }).is_err());
+
+        let mut tensors = random_fsrcnn(4, 2, 1, 0);
+        tensors.get_mut("mid_part.0.weight").unwrap().shape = vec![2, 3, 1, 1];
+        assert!(CnnModel::from_tensors(&tensors, Architecture::Fsrcnn { factor: 2 }).is_err());
+
+        for layer in ["body.0.weight", "body.2.bias"] {
+            let mut tensors = random_srvgg(4, 1, 2, 0);
+            tensors.get_mut(layer).unwrap().data.pop();
+            assert!(matches!(
+                CnnModel::from_tensors(&tensors, Architecture::SrvggCompact),
+                Err(Error::InvalidWeights(_))
+            ));
+        }
+        let mut tensors = random_fsrcnn(4, 2, 1, 0);
+        tensors.get_mut("last_part.weight").unwrap().data.pop();
+        assert!(matches!(
+            CnnModel::from_tensors(&tensors, Architecture::Fsrcnn { factor: 2 }),
+            Err(Error::InvalidWeights(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_oversized_shapes() {
+        // One tensor named `w` of rank 3 whose value count overflows
+        let mut bytes = [1u32, 1].map(u32::to_le_bytes).concat();
+        bytes.push(b'w');
+        bytes.extend(
+            [3, u32::MAX, u32::MAX, u32::MAX]
+                .map(u32::to_le_bytes)
+                .concat(),
+        );
+        assert!(matches!(
+            parse_tensors(&bytes),
+            Err(Error::InvalidWeights(_))
+        ));
+    }
+
+    #[test]
+    fn tensor_files_roundtrip() {
+        let directory = std::env::temp_dir().join(format!("cnn_model_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let tensors: Tensors = random_srvgg(4, 1, 2, 5)
+            .into_iter()
+            .map(|(name, t)| (format!("params_ema.{name}"), t))
+            .collect();
+
+        let native = directory.join("weights.bin");
+        write_tensors(&native, &tensors).unwrap();
+        assert_eq!(read_tensors(&native).unwrap(), tensors);
+
+        // Half precision safetensors, every random weight is exact in f16 after the roundtrip below
+        let halves: BTreeMap<String, (Vec<usize>, Vec<u8>)> = tensors
+            .iter()
+            .map(|(name, t)| {
+                let bytes = t
+                    .data
+                    .iter()
+                    .flat_map(|&v| f32_to_f16(v).to_le_bytes())
+                    .collect();
+                (name.clone(), (t.shape.clone(), bytes))
+            })
+            .collect();
+        let views = halves.iter().map(|(name, (shape, bytes))| {
+            (
+                name.clone(),
+                safetensors::tensor::TensorView::new(Dtype::F16, shape.clone(), bytes).unwrap(),
+            )
+        });
+        let safetensors_path = directory.join("weights.safetensors");
+        std::fs::write(
+            &safetensors_path,
+            safetensors::serialize(views, &None).unwrap(),
+        )
+        .unwrap();
+        let read = read_tensors(&safetensors_path).unwrap();
+        for (name, tensor) in &tensors {
+            for (&expected, &actual) in tensor.data.iter().zip(&read[name].data) {
+                assert!(
+                    (expected - actual).abs() <= expected.abs() * 1e-3 + 1e-7,
+                    "{name}: {expected} vs {actual}"
+                );
+            }
+        }
+
+        let model = CnnModel::load(&safetensors_path, Architecture::SrvggCompact).unwrap();
+        assert_eq!(model.factor(), 2);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    /// Narrows to half precision, rounding toward zero, for normal values only
+    fn f32_to_f16(value: f32) -> u16 {
+        let bits = value.to_bits();
+        let sign = (bits >> 16) & 0x8000;
+        let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+        if exponent <= 0 {
+            return sign as u16;
+        }
+        (sign | (exponent as u32) << 10 | (bits >> 13) & 0x3ff) as u16
+    }
+
+    #[test]
+    fn half_precision() {
+        let cases = [
+            (0x3c00, 1.0),
+            (0xc000, -2.0),
+            (0x0001, 5.960_464_5e-8),
+            (0x7bff, 65504.0),
+            (0x8000, -0.0),
+        ];
+        for (bits, value) in cases {
+            assert_eq!(f16_to_f32(bits), value, "{bits:#06x}");
+        }
+        assert!(f16_to_f32(0x7c00).is_infinite() && f16_to_f32(0x7e00).is_nan());
+    }
+}
@@ -0,0 +1,38 @@
+// Pixel shuffle of the last feature map, optionally adding the nearest-neighbour upscaled input
+//
+// Channel `c * factor² + phase` of input pixel (x, y) lands in channel `c` of the output pixel at
+// `(x, y) * factor + (phase % factor, phase / factor)`.
+
+struct Shuffle {
+    width: u32,
+    height: u32,
+    factor: u32,
+    channels: u32,
+    residual: u32,
+}
+
+@group(0) @binding(0) var<uniform> shuffle: Shuffle;
+@group(0) @binding(1) var<storage, read> features: array<f32>;
+@group(0) @binding(2) var<storage, read> network_input: array<vec4<f32>>;
+@group(0) @binding(3) var<storage, read_write> upscaled: array<vec4<f32>>;
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let wide = shuffle.width * shuffle.factor;
+    if (id.x >= wide || id.y >= shuffle.height * shuffle.factor) {
+        return;
+    }
+
+    let source = (id.y / shuffle.factor) * shuffle.width + id.x / shuffle.factor;
+    let phase = (id.y % shuffle.factor) * shuffle.factor + id.x % shuffle.factor;
+    let area = shuffle.width * shuffle.height;
+    var pixel = vec4<f32>(0.0);
+    for (var c = 0u; c < shuffle.channels; c++) {
+        let channel = c * shuffle.factor * shuffle.factor + phase;
+        pixel[c] = features[((channel / 4u) * area + source) * 4u + channel % 4u];
+    }
+    if (shuffle.residual != 0u) {
+        pixel += network_input[source];
+    }
+    upscaled[id.y * wide + id.x] = pixel;
+}
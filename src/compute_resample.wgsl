@@ -0,0 +1,134 @@
+// Separable-kernel resampling into a storage texture
+//
+// Every workgroup first caches the source texels under its block of output pixels in workgroup memory, then
+// each invocation filters one pixel from the cache. Taps the cache doesn't cover, as when downscaling, read
+// the texture directly.
+
+struct Uniforms {
+    offset: vec2<i32>,
+    resized: vec2<i32>,
+    ratio: vec2<f32>,
+    clear_color: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> r_uniforms: Uniforms;
+@group(0) @binding(1) var r_color: texture_2d<f32>;
+@group(0) @binding(2) var r_output: texture_storage_2d<rgba8unorm, write>;
+
+// 0 triangle, 1 cubic B-spline, 2 Catmull-Rom, 3 Lanczos with `support` lobes
+override kernel: u32 = 0u;
+override support: i32 = 1;
+// Drops taps outside the image instead of repeating the edge texels
+override renormalize: bool = false;
+// Encodes the linear result for an sRGB output
+override srgb: bool = false;
+
+const WORKGROUP: i32 = 16;
+// Texels under a block when upscaling, plus the kernel reaching three texels to each side
+const TILE: i32 = 24;
+const PI: f32 = 3.14159265358979;
+
+var<workgroup> tile: array<vec4<f32>, 576>;
+
+fn weight(x: f32) -> f32 {
+    let a = abs(x);
+    if (kernel == 0u) {
+        return max(1.0 - a, 0.0);
+    }
+    if (kernel == 1u) {
+        if (a < 1.0) {
+            return (0.5 * a - 1.0) * a * a + 2.0 / 3.0;
+        }
+        return select(0.0, (2.0 - a) * (2.0 - a) * (2.0 - a) / 6.0, a < 2.0);
+    }
+    if (kernel == 2u) {
+        if (a < 1.0) {
+            return (1.5 * a - 2.5) * a * a + 1.0;
+        }
+        return select(0.0, ((-0.5 * a + 2.5) * a - 4.0) * a + 2.0, a < 2.0);
+    }
+    if (a < 1e-5) {
+        return 1.0;
+    }
+    if (a >= f32(support)) {
+        return 0.0;
+    }
+    let px = PI * a;
+    return f32(support) * sin(px) * sin(px / f32(support)) / (px * px);
+}
+
+// Position in source texel space of an output pixel's centre
+fn source_position(pixel: vec2<i32>) -> vec2<f32> {
+    return (vec2<f32>(pixel) + 0.5) * r_uniforms.ratio - 0.5;
+}
+
+fn encode(color: vec4<f32>) -> vec4<f32> {
+    if (!srgb) {
+        return color;
+    }
+    let c = saturate(color.rgb);
+    let encoded = select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, 12.92 * c, c <= vec3<f32>(0.0031308));
+    return vec4<f32>(encoded, color.a);
+}
+
+@compute @workgroup_size(16, 16)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let last = vec2<i32>(textureDimensions(r_color)) - 1;
+
+    // Clamp-to-edge texels from the first tap of the block's first pixel on
+    let block = vec2<i32>(group_id.xy) * WORKGROUP - r_uniforms.offset;
+    let tile_origin = vec2<i32>(floor(source_position(block))) - support + 1;
+    for (var i = i32(local_index); i < TILE * TILE; i += WORKGROUP * WORKGROUP) {
+        let texel = clamp(tile_origin + vec2<i32>(i % TILE, i / TILE), vec2<i32>(0), last);
+        tile[i] = textureLoad(r_color, texel, 0);
+    }
+    workgroupBarrier();
+
+    let canvas_pixel = vec2<i32>(global_id.xy);
+    if (any(canvas_pixel >= vec2<i32>(textureDimensions(r_output)))) {
+        return;
+    }
+    let pixel = canvas_pixel - r_uniforms.offset;
+    if (any(pixel < vec2<i32>(0)) || any(pixel >= r_uniforms.resized)) {
+        textureStore(r_output, canvas_pixel, encode(r_uniforms.clear_color));
+        return;
+    }
+
+    // Weights along each axis, zero for taps left out
+    let position = source_position(pixel);
+    let origin = vec2<i32>(floor(position)) - support + 1;
+    var weights_x: array<f32, 6>;
+    var weights_y: array<f32, 6>;
+    var total = vec2<f32>(0.0);
+    for (var i = 0; i < 2 * support; i++) {
+        let tap = origin + i;
+        let outside = (tap < vec2<i32>(0) | tap > last) & vec2<bool>(renormalize);
+        let offset = vec2<f32>(tap) - position;
+        let w = select(vec2<f32>(weight(offset.x), weight(offset.y)), vec2<f32>(0.0), outside);
+        weights_x[i] = w.x;
+        weights_y[i] = w.y;
+        total += w;
+    }
+
+    var sum = vec4<f32>(0.0);
+    for (var y = 0; y < 2 * support; y++) {
+        var row = vec4<f32>(0.0);
+        for (var x = 0; x < 2 * support; x++) {
+            let tap = origin + vec2<i32>(x, y);
+            let cached = tap - tile_origin;
+            var texel: vec4<f32>;
+            if (all(cached >= vec2<i32>(0)) && all(cached < vec2<i32>(TILE))) {
+                texel = tile[cached.y * TILE + cached.x];
+            } else {
+                texel = textureLoad(r_color, clamp(tap, vec2<i32>(0), last), 0);
+            }
+            row += weights_x[x] * texel;
+        }
+        sum += weights_y[y] * row;
+    }
+    textureStore(r_output, canvas_pixel, encode(sum / (total.x * total.y)));
+}
@@ -1,63 +1,483 @@
-use image::{imageops::FilterType, DynamicImage, RgbImage};
+use std::{f32::consts::PI, sync::Arc};
 
-use crate::{error::Error, upscaler::UpscaleSquareImage};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, RgbImage, Rgba32FImage};
+
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+
+use crate::{
+    cpu_simd::{self, SimdLevel},
+    error::Error,
+    scale::Scale,
+    upscaler::UpscaleImage,
+};
+
+/// Continuous filter for separable resampling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResampleKernel {
+    /// Area average when downscaling, nearest neighbour when upscaling
+    Box,
+
+    /// Linear interpolation
+    Triangle,
+
+    /// Mitchell-Netravali cubics, see associated constants for common `(b, c)`
+    Mitchell { b: f32, c: f32 },
+
+    /// Sinc windowed by a wider sinc
+    Lanczos { lobes: u32 },
+
+    /// Sinc with a raised cosine window
+    Hann { lobes: u32 },
+
+    /// Sinc with a raised cosine window that doesn't reach zero
+    Hamming { lobes: u32 },
+
+    /// Sinc with a three-term cosine window
+    Blackman { lobes: u32 },
+
+    /// Gaussian with σ of half a sample cut off at 3, as `image`'s [`FilterType::Gaussian`]
+    Gaussian,
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl ResampleKernel {
+    pub const HERMITE: Self = Self::Mitchell { b: 0.0, c: 0.0 };
+    pub const CUBIC_B_SPLINE: Self = Self::Mitchell { b: 1.0, c: 0.0 };
+    pub const CATMULL_ROM: Self = Self::Mitchell { b: 0.0, c: 0.5 };
+    pub const MITCHELL: Self = Self::Mitchell {
+        b: 1.0 / 3.0,
+        c: 1.0 / 3.0,
+    };
+    pub const LANCZOS2: Self = Self::Lanczos { lobes: 2 };
+    pub const LANCZOS3: Self = Self::Lanczos { lobes: 3 };
+    pub const LANCZOS4: Self = Self::Lanczos { lobes: 4 };
+
+    /// Radius beyond which the kernel is zero, in samples of the coarser grid
+    pub fn support(&self) -> f32 {
+        match *self {
+            Self::Box => 0.5,
+            Self::Triangle => 1.0,
+            Self::Gaussian => 3.0,
+            Self::Mitchell { b, c } if b == 0.0 && c == 0.0 => 1.0,
+            Self::Mitchell { .. } => 2.0,
+            Self::Lanczos { lobes }
+            | Self::Hann { lobes }
+            | Self::Hamming { lobes }
+            | Self::Blackman { lobes } => lobes.max(1) as f32,
+        }
+    }
+
+    /// Unnormalized kernel value at distance `x`
+    pub fn weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+        let support = self.support();
+        // Window term of windowed sincs, `t` goes from 0 at the centre to 1 at the edge
+        let window = |t: f32| match self {
+            Self::Lanczos { .. } => sinc(t),
+            Self::Hann { .. } => 0.5 + 0.5 * (PI * t).cos(),
+            Self::Hamming { .. } => 0.54 + 0.46 * (PI * t).cos(),
+            _ => 0.42 + 0.5 * (PI * t).cos() + 0.08 * (2.0 * PI * t).cos(),
+        };
+
+        match *self {
+            Self::Box => (x < 0.5) as u8 as f32,
+            Self::Triangle => (1.0 - x).max(0.0),
+            Self::Gaussian if x >= support => 0.0,
+            Self::Gaussian => (-2.0 * x * x).exp(),
+            Self::Mitchell { b, c } => {
+                let value = if x < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                        + (6.0 - 2.0 * b)
+                } else if x < 2.0 {
+                    (-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x.powi(2)
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c)
+                } else {
+                    0.0
+                };
+                value / 6.0
+            }
+            _ if x >= support => 0.0,
+            _ => sinc(x) * window(x / support),
+        }
+    }
+}
+
+impl From<FilterType> for ResampleKernel {
+    /// Kernel of the same shape as `image`'s filter, results differ by rounding
+    fn from(filter: FilterType) -> Self {
+        match filter {
+            FilterType::Nearest => Self::Box,
+            FilterType::Triangle => Self::Triangle,
+            FilterType::CatmullRom => Self::CATMULL_ROM,
+            FilterType::Gaussian => Self::Gaussian,
+            FilterType::Lanczos3 => Self::LANCZOS3,
+        }
+    }
+}
+
+/// Normalized kernel taps of every output sample along one axis
+///
+/// Every output sample has the same number of taps, shorter windows near
+/// the edges are zero-padded, so `start + taps` never exceeds the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisWeights {
+    /// First source sample of every output sample
+    pub starts: Vec<usize>,
+
+    /// Taps per output sample
+    pub taps: usize,
+
+    /// `taps` weights per output sample
+    pub weights: Vec<f32>,
+}
+
+impl AxisWeights {
+    pub fn new(kernel: ResampleKernel, source: u32, target: u32) -> Self {
+        Self::shifted(kernel, source, target, 0.0)
+    }
+
+    /// Weights sampling `offset` source samples away from the centre-aligned positions
+    pub fn shifted(kernel: ResampleKernel, source: u32, target: u32, offset: f32) -> Self {
+        let (source, target) = (source.max(1) as usize, target.max(1) as usize);
+        let ratio = source as f32 / target as f32;
+        // Kernel is stretched over more source samples when downscaling
+        let stretch = ratio.max(1.0);
+        let support = kernel.support() * stretch;
+
+        let windows: Vec<(f32, usize, usize)> = (0..target)
+            .map(|i| {
+                let center = (i as f32 + 0.5) * ratio + offset;
+                let low = ((center - support).floor().max(0.0) as usize).min(source - 1);
+                let high = ((center + support).ceil() as usize).clamp(low + 1, source);
+                (center, low, high)
+            })
+            .collect();
+        let taps = windows
+            .iter()
+            .map(|&(_, low, high)| high - low)
+            .max()
+            .unwrap_or(1);
+
+        let mut starts = Vec::with_capacity(target);
+        let mut weights = vec![0.0; target * taps];
+        for (&(center, low, high), weights) in windows.iter().zip(weights.chunks_exact_mut(taps)) {
+            let start = low.min(source - taps);
+            for j in low..high {
+                weights[j - start] = kernel.weight((j as f32 + 0.5 - center) / stretch);
+            }
+
+            let sum: f32 = weights.iter().sum();
+            if sum != 0.0 {
+                weights.iter_mut().for_each(|weight| *weight /= sum);
+            } else {
+                // Window fell between kernel's non-zero samples, nearest source sample is used
+                weights[(center as usize).clamp(low, high - 1) - start] = 1.0;
+            }
+            starts.push(start);
+        }
+
+        Self {
+            starts,
+            taps,
+            weights,
+        }
+    }
+
+    /// Returns first source sample and weights of output sample `index`
+    pub fn taps(&self, index: usize) -> (usize, &[f32]) {
+        (
+            self.starts[index],
+            &self.weights[index * self.taps..][..self.taps],
+        )
+    }
+}
+
+/// Separable resampler of 4-channel float images between fixed dimensions
+///
+/// Weight tables are computed once on creation, so a resampler should be
+/// reused for images of the same dimensions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resampler {
+    kernel: ResampleKernel,
+    source: (u32, u32),
+    target: (u32, u32),
+    offset: (f32, f32),
+    horizontal: AxisWeights,
+    vertical: AxisWeights,
+    simd: SimdLevel,
+}
+
+impl Resampler {
+    /// Resampler using the best instruction set of the running CPU
+    pub fn new(kernel: ResampleKernel, source: (u32, u32), target: (u32, u32)) -> Self {
+        Self::shifted(kernel, source, target, (0.0, 0.0))
+    }
+
+    /// Resampler sampling `offset` source pixels away from the centre-aligned positions
+    pub fn shifted(
+        kernel: ResampleKernel,
+        source: (u32, u32),
+        target: (u32, u32),
+        offset: (f32, f32),
+    ) -> Self {
+        Self {
+            kernel,
+            source,
+            target,
+            offset,
+            horizontal: AxisWeights::shifted(kernel, source.0, target.0, offset.0),
+            vertical: AxisWeights::shifted(kernel, source.1, target.1, offset.1),
+            simd: SimdLevel::detect(),
+        }
+    }
+
+    /// Limits instruction set, levels the CPU lacks fall back to lower ones
+    pub fn with_simd(mut self, simd: SimdLevel) -> Self {
+        self.simd = simd.supported();
+        self
+    }
+
+    /// Returns instruction set in use
+    pub fn simd(&self) -> SimdLevel {
+        self.simd
+    }
+
+    /// Checks whether the resampler was made, unshifted, for these parameters
+    pub fn fits(&self, kernel: ResampleKernel, source: (u32, u32), target: (u32, u32)) -> bool {
+        (self.kernel, self.source, self.target, self.offset) == (kernel, source, target, (0.0, 0.0))
+    }
+
+    /// Resamples `image`, which has to have the source dimensions
+    pub fn apply(&self, image: &Rgba32FImage) -> Rgba32FImage {
+        self.run(image, None)
+    }
+
+    /// Resamples `image` with rows split across `pool`, bit-exact with [`Resampler::apply`]
+    pub fn apply_parallel(&self, image: &Rgba32FImage, pool: &ThreadPool) -> Rgba32FImage {
+        self.run(image, Some(pool))
+    }
+
+    fn horizontal_row(&self, source: &[[f32; 4]], target: &mut [[f32; 4]]) {
+        cpu_simd::horizontal_row(self.simd, &self.horizontal, source, target);
+    }
+
+    fn vertical_row(&self, rows: &[[f32; 4]], y: usize, target: &mut [[f32; 4]]) {
+        cpu_simd::vertical_row(self.simd, &self.vertical, rows, y, target);
+    }
+
+    fn run(&self, image: &Rgba32FImage, pool: Option<&ThreadPool>) -> Rgba32FImage {
+        assert_eq!(
+            image.dimensions(),
+            self.source,
+            "image doesn't match resampler"
+        );
+        let (width, height) = (self.target.0 as usize, self.target.1 as usize);
+        let source_width = self.source.0 as usize;
+        let pixels: &[[f32; 4]] = bytemuck::cast_slice(image.as_raw());
+
+        // Horizontal pass into `width` x source height
+        let mut rows = vec![[0.0f32; 4]; width * self.source.1 as usize];
+        let horizontal =
+            |(source, target): (&[[f32; 4]], &mut [[f32; 4]])| self.horizontal_row(source, target);
+        match pool {
+            Some(pool) => pool.install(|| {
+                pixels
+                    .par_chunks_exact(source_width)
+                    .zip(rows.par_chunks_exact_mut(width))
+                    .for_each(horizontal)
+            }),
+            None => pixels
+                .chunks_exact(source_width)
+                .zip(rows.chunks_exact_mut(width))
+                .for_each(horizontal),
+        }
+
+        // Vertical pass, whole rows at a time
+        let mut output = vec![[0.0f32; 4]; width * height];
+        let vertical = |(y, target): (usize, &mut [[f32; 4]])| self.vertical_row(&rows, y, target);
+        match pool {
+            Some(pool) => pool.install(|| {
+                output
+                    .par_chunks_exact_mut(width)
+                    .enumerate()
+                    .for_each(vertical)
+            }),
+            None => output
+                .chunks_exact_mut(width)
+                .enumerate()
+                .for_each(vertical),
+        }
+
+        Rgba32FImage::from_raw(self.target.0, self.target.1, bytemuck::cast_vec(output))
+            .expect("buffer matches dimensions")
+    }
+}
+
+/// Resamples `image` to `(width, height)` with `kernel`, keeping its colour type
+pub fn resample(
+    image: &DynamicImage,
+    (width, height): (u32, u32),
+    kernel: ResampleKernel,
+) -> DynamicImage {
+    let resampler = Resampler::new(kernel, image.dimensions(), (width.max(1), height.max(1)));
+    let resampled = resampler.apply(&cpu_simd::to_rgba32f(resampler.simd, image));
+    cpu_simd::from_rgba32f(resampler.simd, resampled, image.color())
+}
 
 #[derive(Debug, Clone)]
 pub struct CPUAlgoUpscaler {
     image: DynamicImage,
     upscaled_image: DynamicImage,
     scale_mode: FilterType,
-    scale_factor: f32,
+    kernel: Option<ResampleKernel>,
+    pool: Option<Arc<ThreadPool>>,
+    simd: SimdLevel,
+    resampler: Option<Resampler>,
+    scale: Scale,
 }
 
 impl Default for CPUAlgoUpscaler {
     fn default() -> Self {
         let default_res = 512;
-        let scale_factor = 2.0;
+        let scale = Scale::Uniform(2.0);
 
         let image = RgbImage::new(default_res, default_res);
-        let new_res = (default_res as f32 * scale_factor) as u32;
-        let upscaled_image = RgbImage::new(new_res, new_res);
+        let (new_width, new_height) = scale.plan((default_res, default_res)).canvas;
+        let upscaled_image = RgbImage::new(new_width, new_height);
 
         Self {
             image: image.into(),
             upscaled_image: upscaled_image.into(),
             scale_mode: FilterType::Nearest,
-            scale_factor,
+            kernel: None,
+            pool: None,
+            simd: SimdLevel::detect(),
+            resampler: None,
+            scale,
         }
     }
 }
 
 impl CPUAlgoUpscaler {
-    pub fn new(scale_factor: f32, scale_mode: FilterType) -> Self {
+    pub fn new(scale: impl Into<Scale>, scale_mode: FilterType) -> Self {
         let mut scaler = Self {
             scale_mode,
-            scale_factor,
+            scale: scale.into(),
             ..Default::default()
         };
-        let upscaled_resolution = scaler.upscaled_resolution();
-        scaler.upscaled_image = RgbImage::new(upscaled_resolution, upscaled_resolution).into();
+        let (upscaled_width, upscaled_height) = scaler.upscaled_dimensions();
+        scaler.upscaled_image = RgbImage::new(upscaled_width, upscaled_height).into();
+        scaler
+    }
+
+    /// Upscaler resampling with a [`ResampleKernel`] instead of `image`'s filters
+    pub fn with_kernel(scale: impl Into<Scale>, kernel: ResampleKernel) -> Self {
+        let mut scaler = Self {
+            kernel: Some(kernel),
+            ..Self::new(scale, FilterType::Nearest)
+        };
+        scaler.prepare_resampler();
         scaler
     }
+
+    /// Limits instruction set of [`ResampleKernel`] resampling, output stays the same
+    pub fn with_simd(mut self, simd: SimdLevel) -> Self {
+        self.simd = simd.supported();
+        self.resampler = None;
+        self.prepare_resampler();
+        self
+    }
+
+    /// Resampling source and target dimensions of the loaded image
+    fn resample_dims(&self) -> ((u32, u32), (u32, u32)) {
+        let plan = self.plan();
+        let source = match plan.is_stretch() {
+            true => self.image.dimensions(),
+            false => (plan.crop.width, plan.crop.height),
+        };
+        (source, (plan.resized.0.max(1), plan.resized.1.max(1)))
+    }
+
+    /// Builds weight tables for the loaded image unless cached ones fit
+    fn prepare_resampler(&mut self) {
+        let Some(kernel) = self.kernel else {
+            return;
+        };
+        let (source, target) = self.resample_dims();
+        if !self
+            .resampler
+            .as_ref()
+            .is_some_and(|resampler| resampler.fits(kernel, source, target))
+        {
+            self.resampler = Some(Resampler::new(kernel, source, target).with_simd(self.simd));
+        }
+    }
+
+    /// Splits [`ResampleKernel`] resampling across `threads` threads, 0 picks one per CPU
+    ///
+    /// Output doesn't depend on the thread count. Upscalers using `image`'s filters are refused,
+    /// as those only run on one thread.
+    pub fn with_threads(mut self, threads: usize) -> Result<Self, Error> {
+        if self.kernel.is_none() {
+            return Err(Error::ThreadsWithoutKernel);
+        }
+        self.pool = Some(Arc::new(
+            ThreadPoolBuilder::new().num_threads(threads).build()?,
+        ));
+        Ok(self)
+    }
+
+    fn resize(&self, image: &DynamicImage, (width, height): (u32, u32)) -> DynamicImage {
+        let Some(kernel) = self.kernel else {
+            return image.resize_exact(width, height, self.scale_mode);
+        };
+
+        let (source, target) = (image.dimensions(), (width.max(1), height.max(1)));
+        let built;
+        let resampler = match &self.resampler {
+            Some(resampler) if resampler.fits(kernel, source, target) => resampler,
+            _ => {
+                built = Resampler::new(kernel, source, target).with_simd(self.simd);
+                &built
+            }
+        };
+        let pixels = cpu_simd::to_rgba32f(resampler.simd, image);
+        let resampled = match &self.pool {
+            Some(pool) => resampler.apply_parallel(&pixels, pool),
+            None => resampler.apply(&pixels),
+        };
+        cpu_simd::from_rgba32f(resampler.simd, resampled, image.color())
+    }
 }
 
-impl UpscaleSquareImage for CPUAlgoUpscaler {
+impl UpscaleImage for CPUAlgoUpscaler {
     type Error = Error;
 
     fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error> {
-        if image.width() != image.height() {
-            return Err(Error::UnsquareImage);
-        }
         self.image = image.clone();
+        self.prepare_resampler();
         Ok(())
     }
 
     fn upscale(&self) -> Result<DynamicImage, Self::Error> {
-        Ok(self.image.resize(
-            self.upscaled_resolution(),
-            self.upscaled_resolution(),
-            self.scale_mode,
-        ))
+        let plan = self.plan();
+        let (width, height) = plan.resized;
+        let resized = if plan.is_stretch() {
+            self.resize(&self.image, (width, height))
+        } else {
+            self.resize(&plan.crop_image(&self.image), (width, height))
+        };
+        Ok(plan.compose(resized))
     }
 
     fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error> {
@@ -72,11 +492,180 @@ impl UpscaleSquareImage for CPUAlgoUpscaler {
         Ok(&self.upscaled_image)
     }
 
-    fn upscale_factor(&self) -> f32 {
-        self.scale_factor
+    fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    fn original_dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn kernel_support(&self) -> u32 {
+        let radius = match (self.kernel, self.scale_mode) {
+            (Some(kernel), _) => kernel.support(),
+            (None, FilterType::Nearest | FilterType::Triangle) => 1.0,
+            (None, FilterType::CatmullRom) => 2.0,
+            (None, FilterType::Gaussian | FilterType::Lanczos3) => 3.0,
+        };
+        // Downscaling stretches kernels over more source pixels
+        let (x, y) = self.upscale_factors();
+        (radius / x.min(y).min(1.0)).ceil() as u32 + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiling::convert;
+
+    const KERNELS: [ResampleKernel; 11] = [
+        ResampleKernel::Box,
+        ResampleKernel::Triangle,
+        ResampleKernel::HERMITE,
+        ResampleKernel::CUBIC_B_SPLINE,
+        ResampleKernel::MITCHELL,
+        ResampleKernel::LANCZOS2,
+        ResampleKernel::LANCZOS4,
+        ResampleKernel::Hann { lobes: 3 },
+        ResampleKernel::Hamming { lobes: 3 },
+        ResampleKernel::Blackman { lobes: 3 },
+        ResampleKernel::Gaussian,
+    ];
+
+    #[test]
+    fn kernels_vanish_at_support() {
+        for kernel in KERNELS {
+            assert!(kernel.weight(kernel.support()).abs() < 1e-6, "{kernel:?}");
+        }
+
+        // Interpolating kernels keep source samples
+        for kernel in [
+            ResampleKernel::HERMITE,
+            ResampleKernel::CATMULL_ROM,
+            ResampleKernel::LANCZOS3,
+        ] {
+            assert_eq!(kernel.weight(0.0), 1.0);
+            assert!(kernel.weight(1.0).abs() < 1e-6, "{kernel:?}");
+        }
+        assert!((ResampleKernel::MITCHELL.weight(0.0) - 8.0 / 9.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn constant_stays_constant() {
+        let image: DynamicImage = RgbImage::from_pixel(13, 9, image::Rgb([200, 37, 91])).into();
+        for kernel in KERNELS {
+            for dimensions in [(13, 9), (39, 27), (5, 4), (20, 3)] {
+                let resampled = resample(&image, dimensions, kernel).to_rgb8();
+                assert_eq!(resampled.dimensions(), dimensions);
+                assert!(
+                    resampled.pixels().all(|pixel| pixel.0 == [200, 37, 91]),
+                    "{kernel:?} {dimensions:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn threads_are_bit_exact() {
+        let image: DynamicImage = RgbImage::from_fn(61, 47, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 5) as u8, (x * y) as u8])
+        })
+        .into();
+        let single = CPUAlgoUpscaler::with_kernel(2.5, ResampleKernel::LANCZOS3);
+        let mut expected = single.clone();
+        expected.load(&image).unwrap();
+        let expected = expected.upscale().unwrap();
+
+        for threads in 1..=4 {
+            let mut scaler = single.clone().with_threads(threads).unwrap();
+            scaler.load(&image).unwrap();
+            assert_eq!(scaler.upscale().unwrap(), expected, "{threads} threads");
+        }
+    }
+
+    #[test]
+    fn threads_need_a_kernel() {
+        let image: DynamicImage = RgbImage::from_fn(61, 47, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 5) as u8, (x * y) as u8])
+        })
+        .into();
+        for filter in [
+            FilterType::Nearest,
+            FilterType::Triangle,
+            FilterType::CatmullRom,
+            FilterType::Gaussian,
+            FilterType::Lanczos3,
+        ] {
+            assert!(matches!(
+                CPUAlgoUpscaler::new(2.0, filter).with_threads(2),
+                Err(Error::ThreadsWithoutKernel)
+            ));
+
+            let mut single = CPUAlgoUpscaler::with_kernel(2.0, filter.into());
+            single.load(&image).unwrap();
+            let mut threaded = single.clone().with_threads(2).unwrap();
+            threaded.load(&image).unwrap();
+            assert_eq!(
+                threaded.upscale().unwrap(),
+                single.upscale().unwrap(),
+                "{filter:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn simd_is_bit_exact() {
+        for (width, height) in [(61, 47), (8, 3), (1, 5)] {
+            let image = RgbImage::from_fn(width, height, |x, y| {
+                image::Rgb([(x * 4) as u8, (y * 5) as u8, (x * y) as u8])
+            });
+            let image = DynamicImage::from(image).to_rgba32f();
+            for kernel in [ResampleKernel::LANCZOS3, ResampleKernel::Box] {
+                for target in [(width * 2 + 1, height * 3), (width / 2 + 1, height + 2)] {
+                    let resampler = Resampler::new(kernel, (width, height), target);
+                    let expected = resampler.clone().with_simd(SimdLevel::Scalar).apply(&image);
+                    for simd in [SimdLevel::Sse41, SimdLevel::Avx2] {
+                        let resampled = resampler.clone().with_simd(simd).apply(&image);
+                        assert!(resampled == expected, "{simd:?} {kernel:?} {target:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn conversions_are_bit_exact() {
+        // Ringing overshoots, exact halves and values just below them, NaN and infinities
+        let samples: Vec<f32> = (0..=2 * 255 + 40)
+            .map(|step| (step as f32 - 20.0) / 510.0)
+            .chain((0..=255).map(|byte| (byte as f32 + 0.5) / 255.0))
+            .chain((0..=255).map(|byte| ((byte as f32 + 0.5) / 255.0).next_down()))
+            .chain([f32::NAN, f32::INFINITY, f32::NEG_INFINITY, -0.0, 1.0])
+            .collect();
+        let width = samples.len().div_ceil(4) as u32;
+        let mut raw = samples;
+        raw.resize(width as usize * 4, 0.5);
+        let floats = Rgba32FImage::from_raw(width, 1, raw).unwrap();
+
+        for color in [image::ColorType::Rgb8, image::ColorType::Rgba8] {
+            let expected = convert(floats.clone().into(), color);
+            let back = expected.to_rgba32f();
+            for simd in [SimdLevel::Scalar, SimdLevel::Sse41, SimdLevel::Avx2] {
+                let simd = simd.supported();
+                let narrowed = cpu_simd::from_rgba32f(simd, floats.clone(), color);
+                assert_eq!(narrowed, expected, "{simd:?} {color:?}");
+                let widened = cpu_simd::to_rgba32f(simd, &expected);
+                assert!(widened == back, "{simd:?} {color:?}");
+            }
+        }
     }
 
-    fn original_resolution(&self) -> u32 {
-        self.image.width()
+    #[test]
+    fn box_averages_area() {
+        let image: DynamicImage =
+            image::GrayImage::from_fn(4, 2, |x, _| image::Luma([[0, 100, 50, 250][x as usize]]))
+                .into();
+        let resampled = resample(&image, (2, 1), ResampleKernel::Box).to_luma8();
+        assert_eq!(resampled.as_raw(), &[50, 150]);
     }
 }
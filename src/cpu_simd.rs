@@ -0,0 +1,451 @@
+//! Vectorized rows of the separable convolution in [`Resampler`](crate::cpu_algo::Resampler),
+//! and of the 8-bit conversions around it
+//!
+//! Every path multiplies and adds in the same order as the scalar one. Fused multiply-add is
+//! left out on purpose: it rounds once where the scalar path rounds twice, so its output would
+//! differ from the other levels in the last bit.
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use image::{ColorType, DynamicImage, GenericImageView, RgbImage, Rgba32FImage, RgbaImage};
+
+use crate::{cpu_algo::AxisWeights, tiling::convert};
+
+/// Instruction set used by the convolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SimdLevel {
+    Scalar,
+
+    /// One pixel per 128-bit register, SSE4.1 widening, blends and rounding for 8-bit pixels
+    Sse41,
+
+    /// Two pixels per 256-bit register, AVX2 permutes and broadcasts for weights
+    Avx2,
+}
+
+impl SimdLevel {
+    /// Best instruction set supported by the running CPU
+    pub fn detect() -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Self::Avx2;
+            }
+            if is_x86_feature_detected!("sse4.1") {
+                return Self::Sse41;
+            }
+        }
+        Self::Scalar
+    }
+
+    /// Returns `self` if the running CPU supports it, otherwise the best level below
+    pub fn supported(self) -> Self {
+        self.min(Self::detect())
+    }
+}
+
+/// Convolves `source` row into `target` row with horizontal `weights`
+///
+/// `level` has to be [supported](SimdLevel::supported).
+pub(crate) fn horizontal_row(
+    level: SimdLevel,
+    weights: &AxisWeights,
+    source: &[[f32; 4]],
+    target: &mut [[f32; 4]],
+) {
+    match level {
+        // SAFETY: the caller guarantees the CPU supports `level`
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx2 => unsafe { horizontal_avx2(weights, source, target) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Sse41 => unsafe { horizontal_sse41(weights, source, target) },
+        _ => horizontal_scalar(weights, source, target),
+    }
+}
+
+/// Convolves rows of `width` pixels in `rows` into output row `y` with vertical `weights`
+///
+/// `level` has to be [supported](SimdLevel::supported).
+pub(crate) fn vertical_row(
+    level: SimdLevel,
+    weights: &AxisWeights,
+    rows: &[[f32; 4]],
+    y: usize,
+    target: &mut [[f32; 4]],
+) {
+    match level {
+        // SAFETY: the caller guarantees the CPU supports `level`
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx2 => unsafe { vertical_avx2(weights, rows, y, target) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Sse41 => unsafe { vertical_sse41(weights, rows, y, target) },
+        _ => vertical_scalar(weights, rows, y, target),
+    }
+}
+
+/// Converts `image` to float pixels, bit-exact with [`DynamicImage::to_rgba32f`]
+///
+/// Only 8-bit RGB and RGBA are vectorized. `level` has to be [supported](SimdLevel::supported).
+pub(crate) fn to_rgba32f(level: SimdLevel, image: &DynamicImage) -> Rgba32FImage {
+    let (source, channels) = match image {
+        DynamicImage::ImageRgb8(image) => (image.as_raw(), 3),
+        DynamicImage::ImageRgba8(image) => (image.as_raw(), 4),
+        _ => return image.to_rgba32f(),
+    };
+    let (width, height) = image.dimensions();
+    let mut pixels = vec![[0.0f32; 4]; source.len() / channels];
+    match level {
+        // SAFETY: the caller guarantees the CPU supports `level`
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx2 => unsafe { expand_avx2(source, channels, &mut pixels) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Sse41 => unsafe { expand_sse41(source, channels, &mut pixels) },
+        _ => expand_scalar(source, channels, &mut pixels),
+    }
+    Rgba32FImage::from_raw(width, height, bytemuck::cast_vec(pixels))
+        .expect("buffer matches dimensions")
+}
+
+/// Converts float pixels to `color`, bit-exact with [`convert`]
+///
+/// Only 8-bit RGB and RGBA are vectorized. `level` has to be [supported](SimdLevel::supported).
+pub(crate) fn from_rgba32f(
+    level: SimdLevel,
+    image: Rgba32FImage,
+    color: ColorType,
+) -> DynamicImage {
+    let channels = match color {
+        ColorType::Rgb8 => 3,
+        ColorType::Rgba8 => 4,
+        _ => return convert(image.into(), color),
+    };
+    let (width, height) = image.dimensions();
+    let pixels: &[[f32; 4]] = bytemuck::cast_slice(image.as_raw());
+    let mut bytes = vec![0; pixels.len() * channels];
+    match level {
+        // SAFETY: the caller guarantees the CPU supports `level`
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx2 => unsafe { narrow_avx2(pixels, channels, &mut bytes) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Sse41 => unsafe { narrow_sse41(pixels, channels, &mut bytes) },
+        _ => narrow_scalar(pixels, channels, &mut bytes),
+    }
+    let image = match channels {
+        3 => RgbImage::from_raw(width, height, bytes).map(DynamicImage::from),
+        _ => RgbaImage::from_raw(width, height, bytes).map(DynamicImage::from),
+    };
+    image.expect("buffer matches dimensions")
+}
+
+fn horizontal_scalar(weights: &AxisWeights, source: &[[f32; 4]], target: &mut [[f32; 4]]) {
+    for (x, pixel) in target.iter_mut().enumerate() {
+        let (start, taps) = weights.taps(x);
+        let mut sum = [0.0f32; 4];
+        for (sample, &weight) in source[start..start + taps.len()].iter().zip(taps) {
+            for channel in 0..4 {
+                sum[channel] += sample[channel] * weight;
+            }
+        }
+        *pixel = sum;
+    }
+}
+
+/// Source rows under the taps of output row `y`
+fn tap_rows<'a>(
+    weights: &'a AxisWeights,
+    rows: &'a [[f32; 4]],
+    y: usize,
+    width: usize,
+) -> (&'a [[f32; 4]], &'a [f32]) {
+    let (start, taps) = weights.taps(y);
+    (&rows[start * width..(start + taps.len()) * width], taps)
+}
+
+fn vertical_scalar(weights: &AxisWeights, rows: &[[f32; 4]], y: usize, target: &mut [[f32; 4]]) {
+    let width = target.len();
+    let (rows, taps) = tap_rows(weights, rows, y, width);
+    target.fill([0.0; 4]);
+    for (row, &weight) in rows.chunks_exact(width).zip(taps) {
+        for (pixel, sample) in target.iter_mut().zip(row) {
+            for channel in 0..4 {
+                pixel[channel] += sample[channel] * weight;
+            }
+        }
+    }
+}
+
+/// `image`'s conversion of 8-bit samples, with opaque alpha for RGB
+fn expand_scalar(source: &[u8], channels: usize, target: &mut [[f32; 4]]) {
+    for (pixel, bytes) in target.iter_mut().zip(source.chunks_exact(channels)) {
+        *pixel = [0, 1, 2, 3].map(|channel| bytes.get(channel).map_or(1.0, |&b| b as f32 / 255.0));
+    }
+}
+
+/// `image`'s conversion of float samples, NaN turns white
+fn quantize(value: f32) -> u8 {
+    let clamped = if value < 1.0 { value.max(0.0) } else { 1.0 };
+    (clamped * 255.0).round() as u8
+}
+
+fn narrow_scalar(source: &[[f32; 4]], channels: usize, target: &mut [u8]) {
+    for (pixel, bytes) in source.iter().zip(target.chunks_exact_mut(channels)) {
+        for (byte, &value) in bytes.iter_mut().zip(pixel) {
+            *byte = quantize(value);
+        }
+    }
+}
+
+/// Pixels that vector loads and stores of `width` bytes per `pixels` pixels can cover
+///
+/// RGB steps are shorter than the accesses, so the last pixel is always left to the scalar path.
+fn vector_pixels(total: usize, channels: usize, pixels: usize) -> usize {
+    match channels {
+        4 => total / pixels * pixels,
+        _ => total.saturating_sub(1) / pixels * pixels,
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse4.1")]
+unsafe fn horizontal_sse41(weights: &AxisWeights, source: &[[f32; 4]], target: &mut [[f32; 4]]) {
+    for (x, pixel) in target.iter_mut().enumerate() {
+        let (start, taps) = weights.taps(x);
+        let mut sum = _mm_setzero_ps();
+        for (sample, &weight) in source[start..start + taps.len()].iter().zip(taps) {
+            sum = _mm_add_ps(
+                sum,
+                _mm_mul_ps(_mm_loadu_ps(sample.as_ptr()), _mm_set1_ps(weight)),
+            );
+        }
+        _mm_storeu_ps(pixel.as_mut_ptr(), sum);
+    }
+}
+
+/// Adds one tap row at a time across the whole output row, like the scalar path
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse4.1")]
+unsafe fn vertical_sse41(
+    weights: &AxisWeights,
+    rows: &[[f32; 4]],
+    y: usize,
+    target: &mut [[f32; 4]],
+) {
+    let width = target.len();
+    let (rows, taps) = tap_rows(weights, rows, y, width);
+    target.fill([0.0; 4]);
+    for (row, &weight) in rows.chunks_exact(width).zip(taps) {
+        let weight = _mm_set1_ps(weight);
+        for (pixel, sample) in target.iter_mut().zip(row) {
+            let sum = _mm_add_ps(
+                _mm_loadu_ps(pixel.as_ptr()),
+                _mm_mul_ps(_mm_loadu_ps(sample.as_ptr()), weight),
+            );
+            _mm_storeu_ps(pixel.as_mut_ptr(), sum);
+        }
+    }
+}
+
+/// Widens one pixel of 8-bit samples at a time
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse4.1")]
+unsafe fn expand_sse41(source: &[u8], channels: usize, target: &mut [[f32; 4]]) {
+    let vector = vector_pixels(target.len(), channels, 1);
+    let (scale, opaque) = (_mm_set1_ps(255.0), _mm_set1_ps(1.0));
+    for (index, pixel) in target[..vector].iter_mut().enumerate() {
+        let bytes = source.as_ptr().add(index * channels).cast::<i32>();
+        let widened = _mm_cvtepu8_epi32(_mm_cvtsi32_si128(bytes.read_unaligned()));
+        let mut value = _mm_div_ps(_mm_cvtepi32_ps(widened), scale);
+        if channels == 3 {
+            value = _mm_blend_ps::<0b1000>(value, opaque);
+        }
+        _mm_storeu_ps(pixel.as_mut_ptr(), value);
+    }
+    expand_scalar(
+        &source[vector * channels..],
+        channels,
+        &mut target[vector..],
+    );
+}
+
+/// Clamps, scales and rounds half away from zero one pixel at a time
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse4.1")]
+unsafe fn narrow_sse41(source: &[[f32; 4]], channels: usize, target: &mut [u8]) {
+    let vector = vector_pixels(source.len(), channels, 1);
+    let (zero, half, one, scale) = (
+        _mm_setzero_ps(),
+        _mm_set1_ps(0.5),
+        _mm_set1_ps(1.0),
+        _mm_set1_ps(255.0),
+    );
+    for (index, pixel) in source[..vector].iter().enumerate() {
+        let value = _mm_loadu_ps(pixel.as_ptr());
+        // NaN fails the comparison and turns white
+        let clamped = _mm_blendv_ps(one, _mm_max_ps(value, zero), _mm_cmplt_ps(value, one));
+        let scaled = _mm_mul_ps(clamped, scale);
+        let whole = _mm_round_ps::<{ _MM_FROUND_TO_ZERO | _MM_FROUND_NO_EXC }>(scaled);
+        let carry = _mm_and_ps(_mm_cmpge_ps(_mm_sub_ps(scaled, whole), half), one);
+        let words = _mm_cvttps_epi32(_mm_add_ps(whole, carry));
+        let bytes = _mm_packus_epi16(_mm_packus_epi32(words, words), words);
+        let output = target.as_mut_ptr().add(index * channels).cast::<i32>();
+        output.write_unaligned(_mm_cvtsi128_si32(bytes));
+    }
+    narrow_scalar(
+        &source[vector..],
+        channels,
+        &mut target[vector * channels..],
+    );
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn horizontal_avx2(weights: &AxisWeights, source: &[[f32; 4]], target: &mut [[f32; 4]]) {
+    // Spreads the low weight over the low pixel and the high one over the high pixel
+    let spread = _mm256_setr_epi32(0, 0, 0, 0, 1, 1, 1, 1);
+    let last = target.len().saturating_sub(1);
+    let mut pairs = target.chunks_exact_mut(2);
+    for (index, pair) in pairs.by_ref().enumerate() {
+        let ((start_low, taps_low), (start_high, taps_high)) =
+            (weights.taps(2 * index), weights.taps(2 * index + 1));
+        let low = &source[start_low..start_low + taps_low.len()];
+        let high = &source[start_high..start_high + taps_high.len()];
+
+        let mut sum = _mm256_setzero_ps();
+        for ((low, high), (&weight_low, &weight_high)) in
+            low.iter().zip(high).zip(taps_low.iter().zip(taps_high))
+        {
+            let samples = _mm256_loadu2_m128(high.as_ptr(), low.as_ptr());
+            let weights = _mm_unpacklo_ps(_mm_set_ss(weight_low), _mm_set_ss(weight_high));
+            let weights = _mm256_permutevar8x32_ps(_mm256_castps128_ps256(weights), spread);
+            sum = _mm256_add_ps(sum, _mm256_mul_ps(samples, weights));
+        }
+        _mm256_storeu_ps(pair.as_mut_ptr().cast(), sum);
+    }
+
+    if let [pixel] = pairs.into_remainder() {
+        let (start, taps) = weights.taps(last);
+        let mut sum = _mm_setzero_ps();
+        for (sample, &weight) in source[start..start + taps.len()].iter().zip(taps) {
+            let weight = _mm_broadcastss_ps(_mm_set_ss(weight));
+            sum = _mm_add_ps(sum, _mm_mul_ps(_mm_loadu_ps(sample.as_ptr()), weight));
+        }
+        _mm_storeu_ps(pixel.as_mut_ptr(), sum);
+    }
+}
+
+/// Adds one tap row at a time across the whole output row, two pixels per register
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn vertical_avx2(
+    weights: &AxisWeights,
+    rows: &[[f32; 4]],
+    y: usize,
+    target: &mut [[f32; 4]],
+) {
+    let width = target.len();
+    let (rows, taps) = tap_rows(weights, rows, y, width);
+    target.fill([0.0; 4]);
+    for (row, &weight) in rows.chunks_exact(width).zip(taps) {
+        let weight = _mm256_broadcastss_ps(_mm_set_ss(weight));
+        let mut pairs = target.chunks_exact_mut(2);
+        let mut samples = row.chunks_exact(2);
+        for (pair, sample) in pairs.by_ref().zip(samples.by_ref()) {
+            let sum = _mm256_add_ps(
+                _mm256_loadu_ps(pair.as_ptr().cast()),
+                _mm256_mul_ps(_mm256_loadu_ps(sample.as_ptr().cast()), weight),
+            );
+            _mm256_storeu_ps(pair.as_mut_ptr().cast(), sum);
+        }
+
+        if let ([pixel], [sample]) = (pairs.into_remainder(), samples.remainder()) {
+            let sum = _mm_add_ps(
+                _mm_loadu_ps(pixel.as_ptr()),
+                _mm_mul_ps(
+                    _mm_loadu_ps(sample.as_ptr()),
+                    _mm256_castps256_ps128(weight),
+                ),
+            );
+            _mm_storeu_ps(pixel.as_mut_ptr(), sum);
+        }
+    }
+}
+
+/// Widens two pixels of 8-bit samples at a time
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn expand_avx2(source: &[u8], channels: usize, target: &mut [[f32; 4]]) {
+    let vector = vector_pixels(target.len(), channels, 2);
+    let (scale, opaque) = (_mm256_set1_ps(255.0), _mm256_set1_ps(1.0));
+    // Moves the second RGB pixel up to the fifth byte
+    let gaps = _mm_setr_epi8(0, 1, 2, -1, 3, 4, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1);
+    for (index, pair) in target[..vector].chunks_exact_mut(2).enumerate() {
+        let bytes = source.as_ptr().add(index * 2 * channels).cast::<__m128i>();
+        let mut bytes = _mm_loadl_epi64(bytes);
+        if channels == 3 {
+            bytes = _mm_shuffle_epi8(bytes, gaps);
+        }
+        let widened = _mm256_cvtepu8_epi32(bytes);
+        let mut value = _mm256_div_ps(_mm256_cvtepi32_ps(widened), scale);
+        if channels == 3 {
+            value = _mm256_blend_ps::<0b1000_1000>(value, opaque);
+        }
+        _mm256_storeu_ps(pair.as_mut_ptr().cast(), value);
+    }
+    expand_scalar(
+        &source[vector * channels..],
+        channels,
+        &mut target[vector..],
+    );
+}
+
+/// Clamps, scales and rounds half away from zero two pixels at a time
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn narrow_avx2(source: &[[f32; 4]], channels: usize, target: &mut [u8]) {
+    let vector = vector_pixels(source.len(), channels, 2);
+    let (zero, half, one, scale) = (
+        _mm256_setzero_ps(),
+        _mm256_set1_ps(0.5),
+        _mm256_set1_ps(1.0),
+        _mm256_set1_ps(255.0),
+    );
+    // Packing works within 128-bit lanes, so the pixels end up in the first and fifth words
+    let (join, squeeze) = (
+        _mm256_setr_epi32(0, 4, 0, 0, 0, 0, 0, 0),
+        _mm_setr_epi8(0, 1, 2, 4, 5, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1),
+    );
+    for (index, pair) in source[..vector].chunks_exact(2).enumerate() {
+        let value = _mm256_loadu_ps(pair.as_ptr().cast());
+        // NaN fails the comparison and turns white
+        let clamped = _mm256_blendv_ps(
+            one,
+            _mm256_max_ps(value, zero),
+            _mm256_cmp_ps::<_CMP_LT_OQ>(value, one),
+        );
+        let scaled = _mm256_mul_ps(clamped, scale);
+        let whole = _mm256_round_ps::<{ _MM_FROUND_TO_ZERO | _MM_FROUND_NO_EXC }>(scaled);
+        let carry = _mm256_and_ps(
+            _mm256_cmp_ps::<_CMP_GE_OQ>(_mm256_sub_ps(scaled, whole), half),
+            one,
+        );
+        let words = _mm256_cvttps_epi32(_mm256_add_ps(whole, carry));
+        let bytes = _mm256_packus_epi16(_mm256_packus_epi32(words, words), words);
+        let mut bytes = _mm256_castsi256_si128(_mm256_permutevar8x32_epi32(bytes, join));
+        if channels == 3 {
+            bytes = _mm_shuffle_epi8(bytes, squeeze);
+        }
+        let output = target
+            .as_mut_ptr()
+            .add(index * 2 * channels)
+            .cast::<__m128i>();
+        _mm_storel_epi64(output, bytes);
+    }
+    narrow_scalar(
+        &source[vector..],
+        channels,
+        &mut target[vector * channels..],
+    );
+}
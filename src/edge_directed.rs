@@ -0,0 +1,668 @@
+//! Edge-directed interpolation, every step doubles both sides
+//!
+//! A step keeps source pixels at even coordinates of the doubled grid and fills the rest in two
+//! passes: first the pixels between four diagonal sources, then the pixels between four horizontal
+//! and vertical ones. Like the reference implementations, sources land on output pixel `2 * x`
+//! rather than the centre-aligned `2 * x + 0.5`, so every step moves the image half an output
+//! pixel up and left. [`EdgeDirectedUpscaler`] resamples the cascade back by the sum of those
+//! shifts.
+
+use image::{DynamicImage, GenericImageView, RgbImage, Rgba, Rgba32FImage};
+
+use crate::{
+    cpu_algo::{ResampleKernel, Resampler},
+    error::Error,
+    scale::Scale,
+    tiling::convert,
+    upscaler::UpscaleImage,
+};
+
+/// Edge-directed interpolation method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeDirected {
+    /// New Edge-Directed Interpolation (Li and Orchard, 2001)
+    ///
+    /// Fits interpolation weights to the covariance of the coarser grid around every pixel.
+    Nedi,
+
+    /// Iterative Curvature-Based Interpolation (Giachetti and Asuni, 2011)
+    ///
+    /// Interpolates along the direction of lower curvature, then refines towards continuous
+    /// curvature.
+    Icbi { iterations: u32 },
+
+    /// Directional Cubic Convolution Interpolation (Zhou, Shen and Dai, 2012)
+    ///
+    /// Cubic convolution along a dominant edge, otherwise both directions weighted by their
+    /// gradients.
+    Dcci,
+}
+
+/// Gradient ratio above which DCCI interpolates along one direction only
+const DCCI_EDGE_RATIO: f32 = 1.15;
+
+/// Exponent of DCCI's gradient weights
+const DCCI_WEIGHT_EXPONENT: i32 = 5;
+
+/// Luma range, in 8-bit steps, below which NEDI averages instead of fitting weights
+const NEDI_FLAT_RANGE: f32 = 8.0;
+
+/// Ridge added to NEDI's covariance diagonal, relative to its trace
+const NEDI_RIDGE: f64 = 1e-4;
+
+/// Curvature difference, in 8-bit steps, below which ICBI's first guess averages both directions
+const ICBI_CURVATURE_THRESHOLD: f32 = 16.0;
+
+/// Damping of ICBI's simultaneous refinement updates
+const ICBI_STEP: f64 = 0.5;
+
+impl EdgeDirected {
+    pub const ICBI: Self = Self::Icbi { iterations: 8 };
+
+    /// Doubles both sides of `image`
+    pub fn double(&self, image: &Rgba32FImage) -> Rgba32FImage {
+        let mut grid = Grid::spread(image);
+        for lattice in [Lattice::Diagonal, Lattice::Axis] {
+            grid.fill(*self, lattice);
+        }
+        grid.into_image()
+    }
+
+    /// Source pixels around a pixel that affect its doubled pixels
+    fn reach(&self) -> u32 {
+        // Each of the two passes reads up to this many doubled pixels away
+        match self {
+            Self::Nedi => 9,
+            Self::Icbi { iterations } => 3 + 2 * iterations,
+            Self::Dcci => 3,
+        }
+    }
+}
+
+/// Pixels a pass fills, with their two interpolation directions
+#[derive(Debug, Clone, Copy)]
+enum Lattice {
+    /// Odd coordinates, between four diagonal neighbours
+    Diagonal,
+
+    /// Odd coordinate sums, between four horizontal and vertical neighbours
+    Axis,
+}
+
+type Point = (i64, i64);
+
+fn offset((x, y): Point, (dx, dy): Point, times: i64) -> Point {
+    (x + dx * times, y + dy * times)
+}
+
+impl Lattice {
+    fn directions(self) -> [Point; 2] {
+        match self {
+            Self::Diagonal => [(1, 1), (1, -1)],
+            Self::Axis => [(1, 0), (0, 1)],
+        }
+    }
+
+    fn contains(self, (x, y): Point) -> bool {
+        match self {
+            Self::Diagonal => x % 2 == 1 && y % 2 == 1,
+            Self::Axis => (x + y) % 2 == 1,
+        }
+    }
+}
+
+/// Doubled image with the pixels known so far
+struct Grid {
+    width: i64,
+    height: i64,
+    pixels: Vec<[f32; 4]>,
+}
+
+fn luma([r, g, b, _]: [f32; 4]) -> f32 {
+    255.0 * (0.299 * r + 0.587 * g + 0.114 * b)
+}
+
+/// Clamps `value` into `0..length` keeping its parity, `length` is even
+fn clamp_parity(value: i64, length: i64) -> i64 {
+    if value < 0 {
+        value.rem_euclid(2)
+    } else if value >= length {
+        length - 2 + (value - length).rem_euclid(2)
+    } else {
+        value
+    }
+}
+
+impl Grid {
+    fn spread(image: &Rgba32FImage) -> Self {
+        let (width, height) = (image.width() as i64 * 2, image.height() as i64 * 2);
+        let mut pixels = vec![[0.0; 4]; (width * height) as usize];
+        for (x, y, Rgba(pixel)) in image.enumerate_pixels() {
+            pixels[(2 * y as i64 * width + 2 * x as i64) as usize] = *pixel;
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Out-of-bounds points clamp onto pixels of the same lattice
+    fn clamp(&self, (x, y): Point) -> Point {
+        (clamp_parity(x, self.width), clamp_parity(y, self.height))
+    }
+
+    fn get(&self, point: Point) -> [f32; 4] {
+        let (x, y) = self.clamp(point);
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    fn luma(&self, point: Point) -> f32 {
+        luma(self.get(point))
+    }
+
+    fn fill(&mut self, method: EdgeDirected, lattice: Lattice) {
+        let holes: Vec<Point> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&point| lattice.contains(point))
+            .collect();
+        let update = |grid: &mut Self, interpolate: &dyn Fn(&Self, Point) -> [f32; 4]| {
+            let values: Vec<_> = holes
+                .iter()
+                .map(|&point| interpolate(grid, point))
+                .collect();
+            for (&(x, y), value) in holes.iter().zip(values) {
+                grid.pixels[(y * grid.width + x) as usize] = value;
+            }
+        };
+        match method {
+            EdgeDirected::Nedi => update(self, &|grid, point| grid.nedi(point, lattice)),
+            EdgeDirected::Dcci => update(self, &|grid, point| grid.dcci(point, lattice)),
+            EdgeDirected::Icbi { iterations } => {
+                update(self, &|grid, point| grid.fcbi(point, lattice));
+                for _ in 0..iterations {
+                    update(self, &|grid, point| grid.refine(point, lattice));
+                }
+            }
+        }
+    }
+
+    /// Four-tap cubic convolution along `direction`
+    fn cubic(&self, point: Point, direction: Point) -> [f32; 4] {
+        let taps = [(-3, -1.0), (-1, 9.0), (1, 9.0), (3, -1.0)]
+            .map(|(times, weight)| (self.get(offset(point, direction, times)), weight / 16.0));
+        std::array::from_fn(|channel| {
+            taps.iter()
+                .map(|(pixel, weight)| pixel[channel] * weight)
+                .sum()
+        })
+    }
+
+    fn dcci(&self, point: Point, lattice: Lattice) -> [f32; 4] {
+        let [d1, d2] = lattice.directions();
+        // Known pixels on a 3×3 patch of the pass's own lattice, differenced along `direction`
+        let (e1, e2) = ((d1.0 + d2.0, d1.1 + d2.1), (d1.0 - d2.0, d1.1 - d2.1));
+        let gradient = |direction| -> f32 {
+            let mut sum = 0.0;
+            for i in -1..=1 {
+                for j in -1..=1 {
+                    let centre = offset(offset(point, e1, i), e2, j);
+                    sum += (self.luma(offset(centre, direction, 1))
+                        - self.luma(offset(centre, direction, -1)))
+                    .abs();
+                }
+            }
+            sum
+        };
+        let (g1, g2) = (gradient(d1), gradient(d2));
+
+        // Strong variation along one direction means an edge along the other
+        if (1.0 + g1) / (1.0 + g2) > DCCI_EDGE_RATIO {
+            return self.cubic(point, d2);
+        }
+        if (1.0 + g2) / (1.0 + g1) > DCCI_EDGE_RATIO {
+            return self.cubic(point, d1);
+        }
+        let (w1, w2) = (
+            1.0 / (1.0 + g1.powi(DCCI_WEIGHT_EXPONENT)),
+            1.0 / (1.0 + g2.powi(DCCI_WEIGHT_EXPONENT)),
+        );
+        let (c1, c2) = (self.cubic(point, d1), self.cubic(point, d2));
+        std::array::from_fn(|channel| (w1 * c1[channel] + w2 * c2[channel]) / (w1 + w2))
+    }
+
+    fn nedi(&self, point: Point, lattice: Lattice) -> [f32; 4] {
+        let [d1, d2] = lattice.directions();
+        let neighbours = |centre: Point, times: i64| {
+            [(d1, -times), (d1, times), (d2, -times), (d2, times)]
+                .map(|(d, times)| offset(centre, d, times))
+        };
+        let values = neighbours(point, 1).map(|neighbour| self.get(neighbour));
+        let average: [f32; 4] =
+            std::array::from_fn(|channel| values.iter().map(|v| v[channel]).sum::<f32>() / 4.0);
+        let lumas = values.map(luma);
+        let (min, max) = lumas.iter().fold((f32::MAX, f32::MIN), |(min, max), &l| {
+            (min.min(l), max.max(l))
+        });
+        if max - min < NEDI_FLAT_RANGE {
+            return average;
+        }
+
+        // Least squares weights predicting known pixels of a 9×9 patch from their neighbours twice
+        // as far away
+        let mut covariance = [[0.0f64; 4]; 4];
+        let mut correlation = [0.0f64; 4];
+        for i in -4i64..=4 {
+            for j in -4i64..=4 {
+                if (i + j) % 2 == 0 {
+                    continue;
+                }
+                let sample = offset(offset(point, d1, i), d2, j);
+                let coarse = neighbours(sample, 2).map(|neighbour| self.luma(neighbour) as f64);
+                let target = self.luma(sample) as f64;
+                for (row, &a) in coarse.iter().enumerate() {
+                    correlation[row] += a * target;
+                    for (column, &b) in coarse.iter().enumerate() {
+                        covariance[row][column] += a * b;
+                    }
+                }
+            }
+        }
+        // A little ridge regularization splits weights evenly between identical neighbours along
+        // straight edges
+        let ridge = (0..4).map(|i| covariance[i][i]).sum::<f64>() * NEDI_RIDGE;
+        for (i, row) in covariance.iter_mut().enumerate() {
+            row[i] += ridge;
+        }
+        let Some(weights) = solve(covariance, correlation) else {
+            return average;
+        };
+
+        // Weights summing to one keep flat areas and ramps exact despite the ridge
+        let total: f64 = weights.iter().sum();
+        if total < 0.5 {
+            return average;
+        }
+        let weights = weights.map(|weight| weight / total);
+
+        // Clipping to the neighbours keeps ill-conditioned fits from ringing
+        std::array::from_fn(|channel| {
+            let channels = values.map(|value| value[channel]);
+            let (low, high) = channels
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(low, high), &v| {
+                    (low.min(v), high.max(v))
+                });
+            let value: f64 = channels
+                .iter()
+                .zip(weights)
+                .map(|(&v, w)| v as f64 * w)
+                .sum();
+            (value as f32).clamp(low, high)
+        })
+    }
+
+    /// ICBI's first guess: the average along the direction of lower curvature
+    fn fcbi(&self, point: Point, lattice: Lattice) -> [f32; 4] {
+        let [d1, d2] = lattice.directions();
+        let curvature = |d| {
+            let l = |times| self.luma(offset(point, d, times));
+            (l(-3) + l(1) - 2.0 * l(-1)).abs() + (l(3) + l(-1) - 2.0 * l(1)).abs()
+        };
+        let mean = |d| {
+            let (a, b) = (
+                self.get(offset(point, d, -1)),
+                self.get(offset(point, d, 1)),
+            );
+            std::array::from_fn::<f32, 4, _>(|channel| (a[channel] + b[channel]) / 2.0)
+        };
+        let (c1, c2) = (curvature(d1), curvature(d2));
+        if (c1 - c2).abs() < ICBI_CURVATURE_THRESHOLD {
+            let (m1, m2) = (mean(d1), mean(d2));
+            std::array::from_fn(|channel| (m1[channel] + m2[channel]) / 2.0)
+        } else if c1 < c2 {
+            mean(d1)
+        } else {
+            mean(d2)
+        }
+    }
+
+    /// Damped Newton step on the curvature continuity energy of `point`
+    ///
+    /// The energy sums squared differences between second derivatives at `point` and at its four
+    /// neighbours, along both interpolation directions. It's quadratic in the pixel, so finite
+    /// differences are exact.
+    fn refine(&self, point: Point, lattice: Lattice) -> [f32; 4] {
+        let [d1, d2] = lattice.directions();
+        let centre = self.clamp(point);
+        let neighbours =
+            [(d1, -1), (d1, 1), (d2, -1), (d2, 1)].map(|(d, times)| offset(point, d, times));
+        let current = self.get(point);
+        std::array::from_fn(|channel| {
+            let energy = |value: f64| {
+                let at = |p: Point| match self.clamp(p) == centre {
+                    true => value,
+                    false => self.get(p)[channel] as f64,
+                };
+                let second =
+                    |p: Point, d: Point| at(offset(p, d, -1)) + at(offset(p, d, 1)) - 2.0 * at(p);
+                let mut sum = 0.0;
+                for d in [d1, d2] {
+                    let own = second(point, d);
+                    for &neighbour in &neighbours {
+                        sum += (own - second(neighbour, d)).powi(2);
+                    }
+                }
+                sum
+            };
+            let value = current[channel] as f64;
+            let (below, at, above) = (energy(value - 1.0), energy(value), energy(value + 1.0));
+            let (slope, curvature) = ((above - below) / 2.0, above - 2.0 * at + below);
+            match curvature > 0.0 {
+                true => (value - ICBI_STEP * slope / curvature) as f32,
+                false => current[channel],
+            }
+        })
+    }
+
+    fn into_image(self) -> Rgba32FImage {
+        let pixels = self
+            .pixels
+            .into_iter()
+            .flat_map(|pixel| pixel.map(|channel| channel.clamp(0.0, 1.0)));
+        Rgba32FImage::from_raw(self.width as u32, self.height as u32, pixels.collect()).unwrap()
+    }
+}
+
+/// Solves `matrix * x = vector` by Gaussian elimination, `None` if it's close to singular
+fn solve(mut matrix: [[f64; 4]; 4], mut vector: [f64; 4]) -> Option<[f64; 4]> {
+    let scale = (0..4).map(|i| matrix[i][i]).fold(0.0, f64::max);
+    for column in 0..4 {
+        let pivot = (column..4)
+            .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))?;
+        if matrix[pivot][column].abs() <= scale * 1e-9 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        vector.swap(column, pivot);
+        let pivot_row = matrix[column];
+        for row in column + 1..4 {
+            let factor = matrix[row][column] / pivot_row[column];
+            for (value, pivot) in matrix[row].iter_mut().zip(pivot_row).skip(column) {
+                *value -= factor * pivot;
+            }
+            vector[row] -= factor * vector[column];
+        }
+    }
+    let mut solution = [0.0; 4];
+    for row in (0..4).rev() {
+        let rest: f64 = (row + 1..4).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (vector[row] - rest) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+/// Upscaler cascading an [`EdgeDirected`] method
+///
+/// Doubles as often as needed to cover the requested scale, other sizes are reached from there with
+/// Catmull-Rom. Power-of-two factors give the method's output as is.
+#[derive(Debug, Clone)]
+pub struct EdgeDirectedUpscaler {
+    method: EdgeDirected,
+    image: DynamicImage,
+    upscaled_image: DynamicImage,
+    scale: Scale,
+}
+
+impl EdgeDirectedUpscaler {
+    pub fn new(method: EdgeDirected, scale: impl Into<Scale>) -> Self {
+        let scale = scale.into();
+        let (width, height) = scale.plan((1, 1)).canvas;
+        Self {
+            method,
+            image: RgbImage::new(1, 1).into(),
+            upscaled_image: RgbImage::new(width, height).into(),
+            scale,
+        }
+    }
+
+    pub fn method(&self) -> EdgeDirected {
+        self.method
+    }
+
+    /// Number of doubling steps for the loaded image
+    pub fn doublings(&self) -> u32 {
+        let (x, y) = self.upscale_factors();
+        (x.max(y).ceil() as u32)
+            .max(1)
+            .next_power_of_two()
+            .trailing_zeros()
+    }
+}
+
+impl UpscaleImage for EdgeDirectedUpscaler {
+    type Error = Error;
+
+    fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error> {
+        self.image = image.clone();
+        Ok(())
+    }
+
+    fn upscale(&self) -> Result<DynamicImage, Self::Error> {
+        let plan = self.plan();
+        let mut upscaled = plan.crop_image(&self.image).to_rgba32f();
+        let doublings = self.doublings();
+        for _ in 0..doublings {
+            upscaled = self.method.double(&upscaled);
+        }
+        if doublings > 0 || upscaled.dimensions() != plan.resized {
+            // Steps shift by half a pixel of their own output, 2^n - 1 halves of the last one
+            let shift = ((1 << doublings) - 1) as f32 / 2.0;
+            let resampler = Resampler::shifted(
+                ResampleKernel::CATMULL_ROM,
+                upscaled.dimensions(),
+                plan.resized,
+                (-shift, -shift),
+            );
+            upscaled = resampler.apply(&upscaled);
+        }
+        Ok(plan.compose(convert(upscaled.into(), self.image.color())))
+    }
+
+    fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error> {
+        self.upscaled_image = self.upscale()?;
+        Ok(&self.upscaled_image)
+    }
+
+    fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error> {
+        for _ in 0..times {
+            self.upscale_inplace()?;
+        }
+        Ok(&self.upscaled_image)
+    }
+
+    fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    fn original_dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn kernel_support(&self) -> u32 {
+        // Every doubling reaches as far in its own source pixels, half as far in the original ones
+        let steps = self.doublings();
+        let reach: u32 = (0..steps)
+            .map(|step| self.method.reach().div_ceil(1 << step))
+            .sum();
+        // Re-centring resamples by Catmull-Rom and at most one last-step pixel of shift
+        reach + 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METHODS: [EdgeDirected; 3] = [EdgeDirected::Nedi, EdgeDirected::ICBI, EdgeDirected::Dcci];
+
+    fn image(width: u32, height: u32, f: impl Fn(f32, f32) -> f32) -> Rgba32FImage {
+        Rgba32FImage::from_fn(width, height, |x, y| {
+            let value = f(x as f32, y as f32);
+            Rgba([value, value, value, 1.0])
+        })
+    }
+
+    #[test]
+    fn keeps_sources_and_ramps() {
+        let ramp = image(16, 16, |x, y| (x * 3.0 + y * 5.0) / 200.0);
+        for method in METHODS {
+            let doubled = method.double(&ramp);
+            assert_eq!(doubled.dimensions(), (32, 32));
+            for (x, y, pixel) in ramp.enumerate_pixels() {
+                assert_eq!(doubled.get_pixel(2 * x, 2 * y), pixel, "{method:?}");
+            }
+
+            // Away from the borders every method reproduces a linear ramp
+            for y in 10..22 {
+                for x in 10..22 {
+                    let expected = (x as f32 * 1.5 + y as f32 * 2.5) / 200.0;
+                    let error = (doubled.get_pixel(x, y)[0] - expected).abs();
+                    assert!(error < 1e-3, "{method:?} at ({x}, {y}) is off by {error}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn follows_diagonal_edges() {
+        // Dark below the diagonal, bright above, slightly blurred so NEDI has structure to fit
+        let edge = image(16, 16, |x, y| (0.5 + (x - y - 0.5) * 0.35).clamp(0.0, 1.0));
+        let ideal = |x: u32, y: u32| (0.5 + (x as f32 - y as f32 - 1.0) * 0.175).clamp(0.0, 1.0);
+
+        // Plain averaging of the four neighbours on the same grid is the baseline
+        let mut baseline = Grid::spread(&edge);
+        for lattice in [Lattice::Diagonal, Lattice::Axis] {
+            let holes: Vec<_> = (0..32).flat_map(|y| (0..32).map(move |x| (x, y))).collect();
+            for point in holes.into_iter().filter(|&point| lattice.contains(point)) {
+                let [d1, d2] = lattice.directions();
+                let values = [(d1, -1), (d1, 1), (d2, -1), (d2, 1)]
+                    .map(|(d, t)| baseline.get(offset(point, d, t)));
+                let value = std::array::from_fn(|c| values.iter().map(|v| v[c]).sum::<f32>() / 4.0);
+                baseline.pixels[(point.1 * 32 + point.0) as usize] = value;
+            }
+        }
+        let baseline = baseline.into_image();
+
+        let error = |doubled: &Rgba32FImage| -> f32 {
+            let mut sum = 0.0;
+            for y in 6..26 {
+                for x in 6..26 {
+                    sum += (doubled.get_pixel(x, y)[0] - ideal(x, y)).powi(2);
+                }
+            }
+            sum
+        };
+        let baseline = error(&baseline);
+        for method in METHODS {
+            let error = error(&method.double(&edge));
+            assert!(
+                error < baseline * 0.5,
+                "{method:?}: {error} against {baseline}"
+            );
+        }
+    }
+
+    #[test]
+    fn cascades_and_resamples() {
+        let source = RgbImage::from_fn(9, 7, |x, y| {
+            image::Rgb([(x * 28) as u8, (y * 36) as u8, 90])
+        });
+        let source = DynamicImage::from(source);
+        let mut scaler = EdgeDirectedUpscaler::new(EdgeDirected::Dcci, 4.0);
+        scaler.load(&source).unwrap();
+        assert_eq!(scaler.doublings(), 2);
+        let twice = EdgeDirected::Dcci.double(&EdgeDirected::Dcci.double(&source.to_rgba32f()));
+        let centred = Resampler::shifted(
+            ResampleKernel::CATMULL_ROM,
+            (36, 28),
+            (36, 28),
+            (-1.5, -1.5),
+        )
+        .apply(&twice);
+        assert_eq!(
+            scaler.upscale().unwrap(),
+            convert(centred.into(), source.color())
+        );
+
+        let mut scaler = EdgeDirectedUpscaler::new(EdgeDirected::Nedi, 3.0);
+        scaler.load(&source).unwrap();
+        assert_eq!(scaler.doublings(), 2);
+        assert_eq!(scaler.upscale().unwrap().dimensions(), (27, 21));
+    }
+
+    #[test]
+    fn keeps_features_centred() {
+        use crate::cpu_algo::CPUAlgoUpscaler;
+        use image::imageops::FilterType;
+
+        // Brightness-weighted mean position of a blob in the middle of an odd-sized image
+        let blob: DynamicImage = RgbImage::from_fn(15, 15, |x, y| {
+            let distance = (x as f32 - 7.0).powi(2) + (y as f32 - 7.0).powi(2);
+            let value = (255.0 * (-distance / 6.0).exp()) as u8;
+            image::Rgb([value; 3])
+        })
+        .into();
+        let centroid = |image: &DynamicImage| {
+            let luma = image.to_luma32f();
+            let (mut sum, mut x, mut y) = (0.0, 0.0, 0.0);
+            for (px, py, pixel) in luma.enumerate_pixels() {
+                sum += pixel[0];
+                x += pixel[0] * (px as f32 + 0.5);
+                y += pixel[0] * (py as f32 + 0.5);
+            }
+            (x / sum, y / sum)
+        };
+
+        for factor in [2.0, 4.0, 3.0] {
+            let mut reference = CPUAlgoUpscaler::new(factor, FilterType::CatmullRom);
+            reference.load(&blob).unwrap();
+            let (x, y) = centroid(&reference.upscale().unwrap());
+            for method in METHODS {
+                let mut scaler = EdgeDirectedUpscaler::new(method, factor);
+                scaler.load(&blob).unwrap();
+                let (cx, cy) = centroid(&scaler.upscale().unwrap());
+                assert!(
+                    (cx - x).abs() < 0.05 && (cy - y).abs() < 0.05,
+                    "{method:?} {factor}x centres the blob at ({cx}, {cy}), not ({x}, {y})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_match_whole_image() {
+        use crate::tiled::TiledUpscaler;
+
+        let source: DynamicImage = RgbImage::from_fn(23, 17, |x, y| {
+            let value = if (x as i32 - 2 * y as i32).abs() < 6 {
+                220
+            } else {
+                30
+            };
+            image::Rgb([value, (x * 11) as u8, (y * 15) as u8])
+        })
+        .into();
+        for method in METHODS {
+            let mut whole = EdgeDirectedUpscaler::new(method, 4.0);
+            whole.load(&source).unwrap();
+            let mut tiled =
+                TiledUpscaler::new(EdgeDirectedUpscaler::new(method, 4.0)).with_tile_size(8);
+            assert_eq!(
+                tiled.upscale_image(&source).unwrap(),
+                whole.upscale().unwrap(),
+                "{method:?}"
+            );
+        }
+    }
+}
@@ -3,14 +3,18 @@ pub enum Error {
     #[error("image width and height are not the same")]
     UnsquareImage,
 
-    // #[error("ort: {0}")]
-    // OnnxRuntime(#[from] ort::Error),
+    #[cfg(feature = "onnx")]
+    #[error("ort: {0}")]
+    OnnxRuntime(#[from] ort::Error),
 
-    #[error("incompatible onnx model")]
-    IncompatibleModel,
-    
-    #[error("model io widths and heights are not the same")]
-    UnsquareModelIO,
+    #[error("incompatible onnx model: {0}")]
+    IncompatibleModel(String),
+
+    #[error("model scales width and height by different factors")]
+    AnamorphicModelIO,
+
+    #[error("wgpu: no suitable adapter found")]
+    NoSuitableAdapter,
 
     #[error("wgpu: {0}")]
     FailedDeviceRequest(#[from] wgpu::RequestDeviceError),
@@ -24,6 +28,63 @@ pub enum Error {
     #[error("wgpu: {0}")]
     BufferFailedToMap(#[from] wgpu::BufferAsyncError),
 
+    #[error("config: {0}")]
+    Config(#[from] serde_json::Error),
+
+    #[error("unsupported texture format: {0:?}")]
+    UnsupportedTextureFormat(wgpu::TextureFormat),
+
+    #[error("png: {0}")]
+    PngDecoding(#[from] png::DecodingError),
+
+    #[error("png: {0}")]
+    PngEncoding(#[from] png::EncodingError),
+
+    #[error("interlaced images can't be streamed")]
+    InterlacedImage,
+
+    #[error("tiled upscaling needs a factor scale, got {0:?}")]
+    UnsupportedTiledScale(crate::scale::Scale),
+
+    #[error("threaded upscaling needs a ResampleKernel, image's filters run on one thread")]
+    ThreadsWithoutKernel,
+
+    #[error("thread pool: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+
+    #[error("{0:?} can't upscale by {1}x")]
+    UnsupportedPixelArtFactor(crate::pixel_art::PixelArt, u32),
+
+    #[error("invalid filter bank: {0}")]
+    InvalidFilterBank(String),
+
+    #[error("invalid network weights: {0}")]
+    InvalidWeights(String),
+
+    #[error("invalid shader graph: {0}")]
+    InvalidShaderGraph(String),
+
+    #[error("shader parameter: {0}")]
+    InvalidShaderParam(String),
+
+    /// Shader that naga can't parse or validate, `line` and `column` are 1-based and 0 where
+    /// unknown
+    #[error("shader error at {line}:{column}: {diagnostic}")]
+    ShaderTranslation {
+        line: u32,
+        column: u32,
+        diagnostic: String,
+    },
+
+    #[error("{0:?} has no compute shader")]
+    UnsupportedComputeShader(crate::shader_library::BuiltinShader),
+
+    #[error("mpv hook: {0}")]
+    InvalidMpvHook(String),
+
+    #[error("safetensors: {0}")]
+    Safetensors(#[from] safetensors::SafeTensorError),
+
     #[error("malformed final image")]
     MalformedOutput,
 }
@@ -0,0 +1,598 @@
+//! Compute-shader backend for [`CnnModel`]s
+//!
+//! Feature maps live in storage buffers with channels packed four to a texel, so every tap of a
+//! convolution is one 4x4 matrix product. PReLU is fused into the convolution before it, the pixel
+//! shuffle and residual run as a last pass and the network output is composed on the CPU like
+//! [`CnnModel::run`] does.
+
+use std::{borrow::Cow, sync::mpsc};
+
+use bytemuck::{Pod, Zeroable};
+use image::{DynamicImage, GenericImageView, RgbImage, Rgba32FImage};
+use pollster::FutureExt;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    cnn_model::{CnnModel, Conv},
+    cpu_algo::{ResampleKernel, Resampler},
+    error::Error,
+    gpu_shading_cfg::GpuShadingConfig,
+    scale::Scale,
+    tiling::convert,
+    upscaler::UpscaleImage,
+};
+
+const WORKGROUP: u32 = 8;
+
+/// Upscaler that runs a convolutional network in WGSL compute shaders
+#[derive(Debug)]
+pub struct GPUCnnUpscaler {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    model: CnnModel,
+    conv_pipeline: wgpu::ComputePipeline,
+    shuffle_pipeline: wgpu::ComputePipeline,
+    layers: Vec<LayerBuffers>,
+    features: FeatureBuffers,
+    image: DynamicImage,
+    scale: Scale,
+    upscaled_image: DynamicImage,
+}
+
+/// Weights of a convolution, packed into 4x4 blocks
+#[derive(Debug)]
+struct LayerBuffers {
+    output_groups: u32,
+    input_groups: u32,
+    radius: u32,
+    prelu: bool,
+    weights: wgpu::Buffer,
+    bias: wgpu::Buffer,
+    slopes: wgpu::Buffer,
+}
+
+/// Buffers sized for the loaded image and bind groups of every pass over them
+#[derive(Debug)]
+struct FeatureBuffers {
+    dimensions: (u32, u32),
+    input: wgpu::Buffer,
+    output: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    conv_bind_groups: Vec<wgpu::BindGroup>,
+    shuffle_bind_group: wgpu::BindGroup,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct LayerUniform {
+    width: u32,
+    height: u32,
+    input_groups: u32,
+    output_groups: u32,
+    radius: u32,
+    prelu: u32,
+    padding: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ShuffleUniform {
+    width: u32,
+    height: u32,
+    factor: u32,
+    channels: u32,
+    residual: u32,
+    padding: [u32; 3],
+}
+
+/// Packs `channels` planes of `area` values into `[channels / 4][area]` texels
+fn pack_planes(planes: &[f32], channels: usize, area: usize) -> Vec<[f32; 4]> {
+    let mut packed = vec![[0.0; 4]; channels.div_ceil(4) * area];
+    for (channel, plane) in planes.chunks_exact(area).enumerate().take(channels) {
+        for (texel, &value) in packed[channel / 4 * area..].iter_mut().zip(plane) {
+            texel[channel % 4] = value;
+        }
+    }
+    packed
+}
+
+/// Packs `[outputs][inputs][tap]` weights into column-major 4x4 blocks, columns are input channels
+fn pack_weights(conv: &Conv) -> Vec<[f32; 16]> {
+    let taps = conv.side() * conv.side();
+    let (outputs, inputs) = (conv.outputs.div_ceil(4), conv.inputs.div_ceil(4));
+    let mut packed = vec![[0.0; 16]; outputs * inputs * taps];
+    for output in 0..conv.outputs {
+        for input in 0..conv.inputs {
+            let weights = &conv.weights[(output * conv.inputs + input) * taps..][..taps];
+            let blocks = &mut packed[(output / 4 * inputs + input / 4) * taps..][..taps];
+            for (block, &weight) in blocks.iter_mut().zip(weights) {
+                block[input % 4 * 4 + output % 4] = weight;
+            }
+        }
+    }
+    packed
+}
+
+impl GPUCnnUpscaler {
+    /// Creates an upscaler running `model` on a blank 64x64 image
+    pub fn new(model: CnnModel, scale: impl Into<Scale>) -> Result<Self, Error> {
+        Self::with_config(model, scale, GpuShadingConfig::default())
+    }
+
+    /// Same as [`GPUCnnUpscaler::new`], but picks the adapter by the settings of `config`
+    pub fn with_config(
+        model: CnnModel,
+        scale: impl Into<Scale>,
+        config: GpuShadingConfig,
+    ) -> Result<Self, Error> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: config.backends,
+            ..Default::default()
+        });
+
+        let adapter = instance
+            .request_adapter(&config.adapter_options())
+            .block_on()
+            .ok_or(Error::NoSuitableAdapter)?;
+
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .block_on()?;
+
+        let pipeline = |label: &str, source: &'static str| {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(Cow::from(source)),
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: None,
+                module: &module,
+                entry_point: "main",
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let conv_pipeline = pipeline("GPUCNN_ConvPipeline", include_str!("cnn_conv.wgsl"));
+        let shuffle_pipeline = pipeline("GPUCNN_ShufflePipeline", include_str!("cnn_shuffle.wgsl"));
+
+        let storage = |label: &str, contents: &[u8]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        };
+        let layers: Vec<_> = model
+            .convs
+            .iter()
+            .map(|conv| {
+                let groups = conv.outputs.div_ceil(4);
+                let bias = pack_planes(&conv.bias, conv.outputs, 1);
+                // Unit slopes keep linear layers linear, the shader only reads them with PReLU
+                // enabled
+                let slopes = match &conv.slopes {
+                    Some(slopes) => pack_planes(slopes, conv.outputs, 1),
+                    None => vec![[1.0; 4]; groups],
+                };
+                LayerBuffers {
+                    output_groups: groups as u32,
+                    input_groups: conv.inputs.div_ceil(4) as u32,
+                    radius: conv.radius as u32,
+                    prelu: conv.slopes.is_some(),
+                    weights: storage("GPUCNN_Weights", bytemuck::cast_slice(&pack_weights(conv))),
+                    bias: storage("GPUCNN_Bias", bytemuck::cast_slice(&bias)),
+                    slopes: storage("GPUCNN_Slopes", bytemuck::cast_slice(&slopes)),
+                }
+            })
+            .collect();
+
+        let image: DynamicImage = RgbImage::new(64, 64).into();
+        let mut scaler = Self {
+            features: Self::create_features(
+                &device,
+                &model,
+                &conv_pipeline,
+                &shuffle_pipeline,
+                &layers,
+                (1, 1),
+            ),
+            device,
+            queue,
+            model,
+            conv_pipeline,
+            shuffle_pipeline,
+            layers,
+            image: image.clone(),
+            scale: scale.into(),
+            upscaled_image: RgbImage::new(1, 1).into(),
+        };
+        scaler.load(&image)?;
+        Ok(scaler)
+    }
+
+    pub fn model(&self) -> &CnnModel {
+        &self.model
+    }
+
+    fn create_features(
+        device: &wgpu::Device,
+        model: &CnnModel,
+        conv_pipeline: &wgpu::ComputePipeline,
+        shuffle_pipeline: &wgpu::ComputePipeline,
+        layers: &[LayerBuffers],
+        dimensions: (u32, u32),
+    ) -> FeatureBuffers {
+        let (width, height) = dimensions;
+        let texels = (width * height) as u64;
+        let texel = std::mem::size_of::<[f32; 4]>() as u64;
+        let factor = model.factor() as u64;
+        let widest = layers
+            .iter()
+            .map(|layer| layer.output_groups as u64)
+            .max()
+            .unwrap_or(1);
+
+        let buffer = |label: &str, size: u64, usage: wgpu::BufferUsages| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let storage = wgpu::BufferUsages::STORAGE;
+        let input = buffer(
+            "GPUCNN_Input",
+            texels * texel,
+            storage | wgpu::BufferUsages::COPY_DST,
+        );
+        let ping_pong = [0, 1].map(|_| buffer("GPUCNN_Features", widest * texels * texel, storage));
+        let output_size = texels * factor * factor * texel;
+        let output = buffer(
+            "GPUCNN_Output",
+            output_size,
+            storage | wgpu::BufferUsages::COPY_SRC,
+        );
+        let readback = buffer(
+            "GPUCNN_Readback",
+            output_size,
+            wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        );
+
+        let uniform = |label: &str, contents: &[u8]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        };
+        let bind_group = |pipeline: &wgpu::ComputePipeline, buffers: &[&wgpu::Buffer]| {
+            let entries: Vec<_> = buffers
+                .iter()
+                .enumerate()
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect();
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("GPUCNN_BindGroup"),
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            })
+        };
+
+        // Layers alternate between two feature buffers, the first one reads the input
+        let conv_bind_groups = layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| {
+                let parameters = LayerUniform {
+                    width,
+                    height,
+                    input_groups: layer.input_groups,
+                    output_groups: layer.output_groups,
+                    radius: layer.radius,
+                    prelu: layer.prelu as u32,
+                    padding: [0; 2],
+                };
+                let parameters = uniform("GPUCNN_LayerUniform", bytemuck::bytes_of(&parameters));
+                let source = if index == 0 {
+                    &input
+                } else {
+                    &ping_pong[(index - 1) % 2]
+                };
+                let buffers = [
+                    &parameters,
+                    source,
+                    &ping_pong[index % 2],
+                    &layer.weights,
+                    &layer.bias,
+                    &layer.slopes,
+                ];
+                bind_group(conv_pipeline, &buffers)
+            })
+            .collect();
+
+        let parameters = ShuffleUniform {
+            width,
+            height,
+            factor: model.factor(),
+            channels: model.channels() as u32,
+            residual: model.residual() as u32,
+            padding: [0; 3],
+        };
+        let parameters = uniform("GPUCNN_ShuffleUniform", bytemuck::bytes_of(&parameters));
+        let last = &ping_pong[(layers.len() + 1) % 2];
+        let shuffle_bind_group =
+            bind_group(shuffle_pipeline, &[&parameters, last, &input, &output]);
+
+        FeatureBuffers {
+            dimensions,
+            input,
+            output,
+            readback,
+            conv_bind_groups,
+            shuffle_bind_group,
+        }
+    }
+
+    /// Runs the network on planar input of the loaded dimensions, returns planar output
+    fn forward(&self, planes: &[f32]) -> Result<Vec<f32>, Error> {
+        let (width, height) = self.features.dimensions;
+        let area = (width * height) as usize;
+        let input = pack_planes(planes, self.model.channels(), area);
+        self.queue
+            .write_buffer(&self.features.input, 0, bytemuck::cast_slice(&input));
+
+        let factor = self.model.factor();
+        let mut command_encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("GPUCNN_ComputePass"),
+                    timestamp_writes: None,
+                });
+            compute_pass.set_pipeline(&self.conv_pipeline);
+            for (layer, bind_group) in self.layers.iter().zip(&self.features.conv_bind_groups) {
+                compute_pass.set_bind_group(0, bind_group, &[]);
+                compute_pass.dispatch_workgroups(
+                    width.div_ceil(WORKGROUP),
+                    height.div_ceil(WORKGROUP),
+                    layer.output_groups,
+                );
+            }
+            compute_pass.set_pipeline(&self.shuffle_pipeline);
+            compute_pass.set_bind_group(0, &self.features.shuffle_bind_group, &[]);
+            let (wide, tall) = (width * factor, height * factor);
+            compute_pass.dispatch_workgroups(wide.div_ceil(WORKGROUP), tall.div_ceil(WORKGROUP), 1);
+        }
+        command_encoder.copy_buffer_to_buffer(
+            &self.features.output,
+            0,
+            &self.features.readback,
+            0,
+            self.features.output.size(),
+        );
+        self.queue.submit(Some(command_encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        let buffer_slice = self.features.readback.slice(..);
+        buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+        self.device.poll(wgpu::Maintain::wait()).panic_on_timeout();
+        receiver.recv().unwrap()?;
+
+        let upscaled_area = area * (factor * factor) as usize;
+        let output = {
+            let view = buffer_slice.get_mapped_range();
+            let texels: &[[f32; 4]] = bytemuck::cast_slice(&view);
+            (0..self.model.channels())
+                .flat_map(|c| texels.iter().map(move |t| t[c]))
+                .collect::<Vec<_>>()
+        };
+        self.features.readback.unmap();
+
+        match output.len() == self.model.channels() * upscaled_area {
+            true => Ok(output),
+            false => Err(Error::MalformedOutput),
+        }
+    }
+}
+
+impl UpscaleImage for GPUCnnUpscaler {
+    type Error = Error;
+
+    fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error> {
+        self.image = image.clone();
+        let crop = self.plan().crop;
+        let dimensions = (crop.width, crop.height);
+        if dimensions != self.features.dimensions {
+            self.features = Self::create_features(
+                &self.device,
+                &self.model,
+                &self.conv_pipeline,
+                &self.shuffle_pipeline,
+                &self.layers,
+                dimensions,
+            );
+        }
+        Ok(())
+    }
+
+    fn upscale(&self) -> Result<DynamicImage, Self::Error> {
+        let plan = self.plan();
+        let cropped = plan.crop_image(&self.image).to_rgba32f();
+        let output = self.forward(&self.model.input_planes(&cropped))?;
+        let mut upscaled: Rgba32FImage = self.model.compose(&cropped, &output);
+        if upscaled.dimensions() != plan.resized {
+            let resampler = Resampler::new(
+                ResampleKernel::CATMULL_ROM,
+                upscaled.dimensions(),
+                plan.resized,
+            );
+            upscaled = resampler.apply(&upscaled);
+        }
+        Ok(plan.compose(convert(upscaled.into(), self.image.color())))
+    }
+
+    fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error> {
+        self.upscaled_image = self.upscale()?;
+        Ok(&self.upscaled_image)
+    }
+
+    fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error> {
+        for _ in 0..times {
+            self.upscale_inplace()?;
+        }
+        Ok(&self.upscaled_image)
+    }
+
+    fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    fn original_dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn kernel_support(&self) -> u32 {
+        // Receptive field of the network, plus Catmull-Rom taps of the chroma and of the final
+        // resize
+        let factor = self.model.factor() as f32;
+        let (x, y) = self.upscale_factors();
+        let resampled = x != y || x != factor;
+        let chroma = self.model.input() == crate::cnn_model::NetworkInput::Luma;
+        self.model.receptive_radius() + 2 * (chroma as u32 + resampled as u32)
+    }
+
+    fn max_dimension(&self) -> Option<u32> {
+        // Largest square whose feature maps and upscaled output fit into one storage binding
+        let limits = self.device.limits();
+        let binding = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+        let factor = self.model.factor() as u64;
+        let widest = self
+            .layers
+            .iter()
+            .map(|layer| layer.output_groups as u64)
+            .max()
+            .unwrap_or(1);
+        let side = (binding / (16 * widest.max(factor * factor))) as f64;
+        Some((side.sqrt() as u64 * factor).min(u32::MAX as u64) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnn_model::{random_fsrcnn, random_srvgg, Architecture};
+
+    fn fallback() -> GpuShadingConfig {
+        GpuShadingConfig::default().force_fallback_adapter(true)
+    }
+
+    fn test_image(width: u32, height: u32) -> DynamicImage {
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([
+                (x * 23 % 256) as u8,
+                (y * 37 % 256) as u8,
+                ((x + y) * 11 % 256) as u8,
+                255 - x as u8,
+            ])
+        })
+        .into()
+    }
+
+    /// Compares raw network output against the CPU reference
+    fn assert_matches_reference(model: CnnModel, width: u32, height: u32) {
+        let image = test_image(width, height);
+        let mut scaler =
+            GPUCnnUpscaler::with_config(model.clone(), model.factor() as f32, fallback()).unwrap();
+        scaler.load(&image).unwrap();
+
+        let input = model.input_planes(&image.to_rgba32f());
+        let expected = model.forward(&input, width as usize, height as usize);
+        let actual = scaler.forward(&input).unwrap();
+        assert_eq!(expected.len(), actual.len());
+        for (index, (e, a)) in expected.iter().zip(&actual).enumerate() {
+            assert!(
+                (e - a).abs() <= 1e-4 * e.abs().max(1.0),
+                "value {index}: expected {e}, got {a}"
+            );
+        }
+
+        let expected = DynamicImage::from(model.run(&image.to_rgba32f())).to_rgba8();
+        let actual = scaler.upscale().unwrap().to_rgba8();
+        assert_eq!(
+            actual.dimensions(),
+            (width * model.factor(), height * model.factor())
+        );
+        for (e, a) in expected.pixels().zip(actual.pixels()) {
+            assert!(
+                e.0.iter().zip(a.0).all(|(&e, a)| e.abs_diff(a) <= 1),
+                "expected {:?}, got {:?}",
+                e.0,
+                a.0
+            );
+        }
+    }
+
+    #[test]
+    fn srvgg_matches_reference() {
+        // Five channels per layer leave a partially filled group
+        let model =
+            CnnModel::from_tensors(&random_srvgg(5, 2, 2, 3), Architecture::SrvggCompact).unwrap();
+        assert_matches_reference(model, 19, 13);
+
+        let model =
+            CnnModel::from_tensors(&random_srvgg(8, 1, 3, 4), Architecture::SrvggCompact).unwrap();
+        assert_matches_reference(model, 9, 17);
+    }
+
+    #[test]
+    fn fsrcnn_matches_reference() {
+        let model = CnnModel::from_tensors(
+            &random_fsrcnn(8, 4, 2, 9),
+            Architecture::Fsrcnn { factor: 3 },
+        )
+        .unwrap();
+        assert_matches_reference(model, 14, 11);
+    }
+
+    #[test]
+    fn tiles_match_whole_image() {
+        let model =
+            CnnModel::from_tensors(&random_srvgg(4, 2, 2, 1), Architecture::SrvggCompact).unwrap();
+        let image = test_image(37, 29);
+        let mut scaler = GPUCnnUpscaler::with_config(model, 2.0, fallback()).unwrap();
+        scaler.load(&image).unwrap();
+        let whole = scaler.upscale().unwrap().to_rgba8();
+
+        let mut tiled = crate::tiled::TiledUpscaler::new(scaler).with_tile_size(12);
+        let tiled = tiled.upscale_image(&image).unwrap().to_rgba8();
+        assert_eq!(tiled.dimensions(), whole.dimensions());
+        for (e, a) in whole.pixels().zip(tiled.pixels()) {
+            assert!(
+                e.0.iter().zip(a.0).all(|(&e, a)| e.abs_diff(a) <= 1),
+                "expected {:?}, got {:?}",
+                e.0,
+                a.0
+            );
+        }
+    }
+
+    #[test]
+    fn rescales_to_plan() {
+        let model =
+            CnnModel::from_tensors(&random_srvgg(4, 1, 2, 2), Architecture::SrvggCompact).unwrap();
+        let mut scaler = GPUCnnUpscaler::with_config(model, 3.0, fallback()).unwrap();
+        scaler.load(&RgbImage::new(10, 7).into()).unwrap();
+        let upscaled = scaler.upscale().unwrap();
+        assert_eq!(
+            (upscaled.dimensions(), upscaled.color()),
+            ((30, 21), image::ColorType::Rgb8)
+        );
+    }
+}
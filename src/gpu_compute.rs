@@ -0,0 +1,505 @@
+//! Compute-shader counterpart of the resampling shaders in the [library](crate::shader_library)
+//!
+//! Instead of rendering a full-screen triangle, one compute pass writes the output into a storage
+//! texture, with every workgroup caching the source texels under its 16x16 block of pixels in
+//! workgroup memory. The results match
+//! [`GPUShadingUpscaler`](crate::gpu_shading::GPUShadingUpscaler) rendering the same shader, so
+//! both pipelines can be benchmarked against each other. Storage textures can't be sRGB, the output
+//! texture is `Rgba8Unorm` and the shader encodes sRGB itself when the configured output format
+//! asks for it. BGRA output formats aren't supported.
+//!
+//! Software adapters such as llvmpipe emulate the workgroup barrier at great cost, so compare the
+//! pipelines on hardware.
+
+use std::{borrow::Cow, collections::HashMap, sync::mpsc};
+
+use bytemuck::{Pod, Zeroable};
+use image::{DynamicImage, GenericImageView, RgbImage, RgbaImage};
+use pollster::FutureExt;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    error::Error,
+    gpu_shading_cfg::{is_bgra, GpuShadingConfig},
+    scale::{Scale, ScalePlan},
+    shader_library::BuiltinShader,
+    upscaler::UpscaleImage,
+};
+
+const WORKGROUP: u32 = 16;
+
+/// Upscaler that resamples the image in a WGSL compute shader
+#[derive(Debug)]
+pub struct GPUComputeUpscaler {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: GpuShadingConfig,
+    shader: BuiltinShader,
+    pipeline: wgpu::ComputePipeline,
+    textures: Textures,
+    scale: Scale,
+    plan: ScalePlan,
+    original_dims: (u32, u32),
+    upscaled_image: DynamicImage,
+}
+
+/// Textures sized for the loaded image and the bind group over them
+#[derive(Debug)]
+struct Textures {
+    input: wgpu::Texture,
+    output: wgpu::Texture,
+    padded_bytes_per_row: u32,
+    readback: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ResampleUniform {
+    offset: [i32; 2],
+    resized: [i32; 2],
+    ratio: [f32; 2],
+    padding: [f32; 2],
+    clear_color: [f32; 4],
+}
+
+impl GPUComputeUpscaler {
+    /// Library shaders with a compute implementation
+    pub const SHADERS: [BuiltinShader; 5] = [
+        BuiltinShader::Bilinear,
+        BuiltinShader::BSpline,
+        BuiltinShader::CatmullRom,
+        BuiltinShader::Lanczos2,
+        BuiltinShader::Lanczos3,
+    ];
+
+    /// Creates an upscaler resampling with `shader` on a blank 64x64 image
+    pub fn new(shader: BuiltinShader, scale: impl Into<Scale>) -> Result<Self, Error> {
+        Self::with_config(shader, scale, GpuShadingConfig::default())
+    }
+
+    /// Same as [`GPUComputeUpscaler::new`], but with custom adapter and texture settings
+    ///
+    /// The sampler settings don't apply, taps outside the image repeat the edge like
+    /// [`ClampToEdge`](wgpu::AddressMode::ClampToEdge) or, for Lanczos, are left out. The output
+    /// format has to be `Rgba8Unorm` or `Rgba8UnormSrgb`.
+    pub fn with_config(
+        shader: BuiltinShader,
+        scale: impl Into<Scale>,
+        config: GpuShadingConfig,
+    ) -> Result<Self, Error> {
+        config.validate()?;
+        if is_bgra(config.output_format) {
+            return Err(Error::UnsupportedTextureFormat(config.output_format));
+        }
+        // Kernel selector of the shader and whether taps outside the image are dropped
+        let (kernel, renormalize) = match shader {
+            BuiltinShader::Bilinear => (0, false),
+            BuiltinShader::BSpline => (1, false),
+            BuiltinShader::CatmullRom => (2, false),
+            BuiltinShader::Lanczos2 | BuiltinShader::Lanczos3 => (3, true),
+            BuiltinShader::SharpBilinear | BuiltinShader::Cas => {
+                return Err(Error::UnsupportedComputeShader(shader))
+            }
+        };
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: config.backends,
+            ..Default::default()
+        });
+
+        let adapter = instance
+            .request_adapter(&config.adapter_options())
+            .block_on()
+            .ok_or(Error::NoSuitableAdapter)?;
+
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .block_on()?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("GPUCU_Resample"),
+            source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!("compute_resample.wgsl"))),
+        });
+        let constants = HashMap::from([
+            ("kernel".to_string(), kernel as f64),
+            ("support".to_string(), shader.kernel_support() as f64),
+            ("renormalize".to_string(), renormalize as u8 as f64),
+            (
+                "srgb".to_string(),
+                config.output_format.is_srgb() as u8 as f64,
+            ),
+        ]);
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("GPUCU_ResamplePipeline"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants,
+                ..Default::default()
+            },
+            cache: None,
+        });
+
+        let scale = scale.into();
+        let image: DynamicImage = RgbImage::new(64, 64).into();
+        let plan = scale.plan(image.dimensions());
+        let mut scaler = Self {
+            textures: Self::create_textures(&device, &pipeline, &config, &plan),
+            device,
+            queue,
+            config,
+            shader,
+            pipeline,
+            scale,
+            plan,
+            original_dims: image.dimensions(),
+            upscaled_image: RgbaImage::new(1, 1).into(),
+        };
+        scaler.load(&image)?;
+        Ok(scaler)
+    }
+
+    pub fn shader(&self) -> BuiltinShader {
+        self.shader
+    }
+
+    fn create_textures(
+        device: &wgpu::Device,
+        pipeline: &wgpu::ComputePipeline,
+        config: &GpuShadingConfig,
+        plan: &ScalePlan,
+    ) -> Textures {
+        let texture = |label: &str, (width, height): (u32, u32), format, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let input = texture(
+            "GPUCU_InputTexture",
+            (plan.crop.width, plan.crop.height),
+            config.input_format,
+            wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let output = texture(
+            "GPUCU_OutputTexture",
+            plan.canvas,
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::STORAGE_BINDING,
+        );
+
+        // Buffer rows of texture copies must be aligned, padding is stripped on readback
+        let padded_bytes_per_row =
+            (plan.canvas.0 * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPUCU_Readback"),
+            size: padded_bytes_per_row as u64 * plan.canvas.1 as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let clear = config.clear_color;
+        let parameters = ResampleUniform {
+            offset: [plan.offset.0 as i32, plan.offset.1 as i32],
+            resized: [plan.resized.0 as i32, plan.resized.1 as i32],
+            ratio: [
+                plan.crop.width as f32 / plan.resized.0 as f32,
+                plan.crop.height as f32 / plan.resized.1 as f32,
+            ],
+            padding: [0.0; 2],
+            clear_color: [clear.r, clear.g, clear.b, clear.a].map(|c| c as f32),
+        };
+        let parameters = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GPUCU_ResampleUniform"),
+            contents: bytemuck::bytes_of(&parameters),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let input_view = input.create_view(&wgpu::TextureViewDescriptor::default());
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("GPUCU_BindGroup"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: parameters.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&input_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&output_view),
+                },
+            ],
+        });
+
+        Textures {
+            input,
+            output,
+            padded_bytes_per_row,
+            readback,
+            bind_group,
+        }
+    }
+
+    fn write_input(&self, image: &DynamicImage) {
+        let mut pixels = image.to_rgba8();
+        if is_bgra(self.config.input_format) {
+            pixels.pixels_mut().for_each(|p| p.0.swap(0, 2));
+        }
+
+        self.queue.write_texture(
+            self.textures.input.as_image_copy(),
+            &pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * image.width()),
+                rows_per_image: Some(image.height()),
+            },
+            self.textures.input.size(),
+        );
+    }
+
+    fn dispatch(&self) -> Result<RgbaImage, Error> {
+        let (width, height) = self.plan.canvas;
+        let mut command_encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("GPUCU_ComputePass"),
+                    timestamp_writes: None,
+                });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &self.textures.bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                width.div_ceil(WORKGROUP),
+                height.div_ceil(WORKGROUP),
+                1,
+            );
+        }
+        command_encoder.copy_texture_to_buffer(
+            self.textures.output.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.textures.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.textures.padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            self.textures.output.size(),
+        );
+        self.queue.submit(Some(command_encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        let buffer_slice = self.textures.readback.slice(..);
+        buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+        self.device.poll(wgpu::Maintain::wait()).panic_on_timeout();
+        receiver.recv().unwrap()?;
+
+        let output_raw = {
+            let row_bytes = width as usize * 4;
+            let mut cpu_buffer = Vec::with_capacity(height as usize * row_bytes);
+            let view = buffer_slice.get_mapped_range();
+            for row in view.chunks_exact(self.textures.padded_bytes_per_row as usize) {
+                cpu_buffer.extend_from_slice(&row[..row_bytes]);
+            }
+            cpu_buffer
+        };
+        self.textures.readback.unmap();
+
+        RgbaImage::from_raw(width, height, output_raw).ok_or(Error::MalformedOutput)
+    }
+}
+
+impl UpscaleImage for GPUComputeUpscaler {
+    type Error = Error;
+
+    fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error> {
+        if image.dimensions() != self.original_dims {
+            self.original_dims = image.dimensions();
+            self.plan = self.scale.plan(self.original_dims);
+            self.textures =
+                Self::create_textures(&self.device, &self.pipeline, &self.config, &self.plan);
+        }
+        self.write_input(&self.plan.crop_image(image));
+        Ok(())
+    }
+
+    fn upscale(&self) -> Result<DynamicImage, Self::Error> {
+        Ok(self.dispatch()?.into())
+    }
+
+    fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error> {
+        self.upscaled_image = self.upscale()?;
+        Ok(&self.upscaled_image)
+    }
+
+    fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error> {
+        for _ in 0..times {
+            self.upscale_inplace()?;
+        }
+        Ok(&self.upscaled_image)
+    }
+
+    fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    fn original_dimensions(&self) -> (u32, u32) {
+        self.original_dims
+    }
+
+    fn plan(&self) -> ScalePlan {
+        self.plan
+    }
+
+    fn kernel_support(&self) -> u32 {
+        self.shader.kernel_support()
+    }
+
+    fn max_dimension(&self) -> Option<u32> {
+        Some(self.device.limits().max_texture_dimension_2d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_shading::GPUShadingUpscaler;
+
+    fn fallback() -> GpuShadingConfig {
+        GpuShadingConfig::default().force_fallback_adapter(true)
+    }
+
+    fn test_image(width: u32, height: u32) -> DynamicImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([
+                (x * 23 % 256) as u8,
+                (y * 37 % 256) as u8,
+                ((x + y) * 11 % 256) as u8,
+            ])
+        })
+        .into()
+    }
+
+    fn assert_close(expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) {
+        assert_eq!(expected.dimensions(), actual.dimensions());
+        for (x, y, e) in expected.enumerate_pixels() {
+            let a = actual.get_pixel(x, y);
+            let close =
+                e.0.iter()
+                    .zip(a.0)
+                    .all(|(&e, a)| e.abs_diff(a) <= tolerance);
+            assert!(close, "at {x}, {y}: expected {:?}, got {:?}", e.0, a.0);
+        }
+    }
+
+    #[test]
+    fn matches_render_pipeline() {
+        let image = test_image(45, 29);
+        for config in [
+            fallback(),
+            fallback().output_format(wgpu::TextureFormat::Rgba8Unorm),
+        ] {
+            for shader in GPUComputeUpscaler::SHADERS {
+                let render =
+                    GPUShadingUpscaler::from_image_with_config(shader, &image, 2.5, config.clone())
+                        .unwrap();
+                let mut compute =
+                    GPUComputeUpscaler::with_config(shader, 2.5, config.clone()).unwrap();
+                compute.load(&image).unwrap();
+                let expected = render.upscale().unwrap().to_rgba8();
+                assert_close(&expected, &compute.upscale().unwrap().to_rgba8(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn downscaling_reads_past_the_cache() {
+        let image = test_image(131, 97);
+        let mut compute =
+            GPUComputeUpscaler::with_config(BuiltinShader::Lanczos3, 0.4, fallback()).unwrap();
+        compute.load(&image).unwrap();
+        let actual = compute.upscale().unwrap().to_rgba8();
+        let render =
+            GPUShadingUpscaler::from_image_with_config("lanczos3", &image, 0.4, fallback())
+                .unwrap();
+        assert_close(&render.upscale().unwrap().to_rgba8(), &actual, 2);
+    }
+
+    #[test]
+    fn pads_with_clear_color() {
+        let config = fallback().clear_color(wgpu::Color::RED);
+        let scale = Scale::Pad {
+            width: 40,
+            height: 30,
+        };
+        let image = test_image(13, 11);
+        let mut compute =
+            GPUComputeUpscaler::with_config(BuiltinShader::Bilinear, scale, config.clone())
+                .unwrap();
+        compute.load(&image).unwrap();
+        let render =
+            GPUShadingUpscaler::from_image_with_config("bilinear", &image, scale, config).unwrap();
+        let actual = compute.upscale().unwrap().to_rgba8();
+        assert_eq!(actual.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_close(&render.upscale().unwrap().to_rgba8(), &actual, 2);
+    }
+
+    #[test]
+    fn tiles_match_whole_image() {
+        let image = test_image(70, 53);
+        let mut scaler =
+            GPUComputeUpscaler::with_config(BuiltinShader::CatmullRom, 2.0, fallback()).unwrap();
+        scaler.load(&image).unwrap();
+        let whole = scaler.upscale().unwrap().to_rgba8();
+
+        let mut tiled = crate::tiled::TiledUpscaler::new(scaler).with_tile_size(24);
+        let tiled = tiled.upscale_image(&image).unwrap().to_rgba8();
+        assert_close(&whole, &tiled, 1);
+    }
+
+    #[test]
+    fn unsupported_shader() {
+        let error =
+            GPUComputeUpscaler::with_config(BuiltinShader::Cas, 2.0, fallback()).unwrap_err();
+        assert!(
+            matches!(error, Error::UnsupportedComputeShader(BuiltinShader::Cas)),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn unsupported_output_format() {
+        for format in [
+            wgpu::TextureFormat::Bgra8Unorm,
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+        ] {
+            let config = fallback().output_format(format);
+            let error =
+                GPUComputeUpscaler::with_config(BuiltinShader::Bilinear, 2.0, config).unwrap_err();
+            assert!(
+                matches!(error, Error::UnsupportedTextureFormat(f) if f == format),
+                "{error}"
+            );
+        }
+    }
+}
@@ -1,84 +1,302 @@
-use std::{borrow::Cow, fs::File, io::Read, path::Path, sync::mpsc};
+use std::{
+    borrow::Cow,
+    iter,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
 
-use crate::{error::Error, upscaler::UpscaleSquareImage};
-use image::{DynamicImage, RgbImage, RgbaImage};
-use pollster::FutureExt;
-use wgpu::{
-    PipelineLayoutDescriptor, ShaderModuleDescriptor,
-    TextureDescriptor, TextureUsages,
+use crate::{
+    error::Error,
+    gpu_shading_cfg::{is_bgra, GpuShadingConfig},
+    mpv_hook,
+    scale::{Scale, ScalePlan},
+    shader_graph::{
+        device_capabilities, uniforms_size, Extent, GraphTexture, PassInput, PassShader,
+        ShaderGraph, ShaderParam, ShaderPass,
+    },
+    shader_library::BuiltinShader,
+    upscaler::UpscaleImage,
 };
+use image::{DynamicImage, GenericImageView, RgbImage, RgbaImage};
+use pollster::FutureExt;
+use wgpu::{PipelineLayoutDescriptor, ShaderModuleDescriptor, TextureDescriptor, TextureUsages};
+
+/// Fragment shader program rendered by [`GPUShadingUpscaler`]
+///
+/// Strings convert into built-in programs by [name](ShaderProgram::from_name), any other string or
+/// path is read as a WGSL file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaderProgram {
+    /// WGSL, GLSL or SPIR-V [file](PassShader::load) with a `main` fragment entry point sampling
+    /// `r_color` through `r_sampler`, see [`ShaderPass`], or an mpv user shader
+    File(PathBuf),
+
+    /// AMD FidelityFX Super Resolution 1.0, EASU upscaling followed by RCAS sharpening
+    ///
+    /// `sharpness` is in stops, 0.0 sharpens the most and every stop halves it. EASU expects
+    /// perceptual input, so [`Rgba8Unorm`](wgpu::TextureFormat::Rgba8Unorm) input and output
+    /// formats suit it best.
+    Fsr { sharpness: f32 },
+
+    /// Shader from the [library](crate::shader_library) compiled into the crate
+    Builtin(BuiltinShader),
+
+    /// Passes reading the original image or each other's output
+    Graph(ShaderGraph),
+}
+
+impl ShaderProgram {
+    /// FSR with the sharpness AMD recommends
+    pub const FSR: Self = Self::Fsr { sharpness: 0.2 };
+
+    /// Looks up a built-in program: `"fsr"` or the [name](BuiltinShader::name) of a library shader
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fsr" => Some(Self::FSR),
+            _ => BuiltinShader::from_name(name).map(Self::Builtin),
+        }
+    }
+
+    /// Pass graph rendering the program, files of [mpv hooks](crate::mpv_hook) are imported
+    pub fn graph(&self) -> Result<ShaderGraph, Error> {
+        Ok(match self {
+            Self::File(path) => match PassShader::load(path)? {
+                PassShader::Wgsl(source) | PassShader::Glsl(source)
+                    if mpv_hook::is_hook(&source) =>
+                {
+                    mpv_hook::import(&source)?
+                }
+                shader => ShaderGraph::new().with_pass(ShaderPass::new("main", shader)),
+            },
+            Self::Fsr { sharpness } => ShaderGraph::new()
+                .with_pass(ShaderPass::new(
+                    "easu",
+                    PassShader::Wgsl(include_str!("../shaders/fsr_easu.wgsl").into()),
+                ))
+                .with_pass(
+                    ShaderPass::new(
+                        "rcas",
+                        PassShader::Wgsl(include_str!("../shaders/fsr_rcas.wgsl").into()),
+                    )
+                    .with_param("sharpness", *sharpness),
+                ),
+            Self::Builtin(shader) => ShaderGraph::new().with_pass(shader.pass()),
+            Self::Graph(graph) => graph.clone(),
+        })
+    }
+
+    /// Source pixels a built-in program reads around each output pixel, `None` for files and graphs
+    fn kernel_support(&self) -> Option<u32> {
+        match self {
+            Self::File(_) | Self::Graph(_) => None,
+            // EASU reaches two texels, RCAS one output pixel further
+            Self::Fsr { .. } => Some(3),
+            Self::Builtin(shader) => Some(shader.kernel_support()),
+        }
+    }
+}
+
+impl From<&str> for ShaderProgram {
+    fn from(name: &str) -> Self {
+        Self::from_name(name).unwrap_or_else(|| Self::File(name.into()))
+    }
+}
+
+impl From<String> for ShaderProgram {
+    fn from(name: String) -> Self {
+        name.as_str().into()
+    }
+}
+
+impl From<&Path> for ShaderProgram {
+    fn from(path: &Path) -> Self {
+        Self::File(path.into())
+    }
+}
+
+impl From<PathBuf> for ShaderProgram {
+    fn from(path: PathBuf) -> Self {
+        Self::File(path)
+    }
+}
+
+impl From<BuiltinShader> for ShaderProgram {
+    fn from(shader: BuiltinShader) -> Self {
+        Self::Builtin(shader)
+    }
+}
+
+impl From<ShaderGraph> for ShaderProgram {
+    fn from(graph: ShaderGraph) -> Self {
+        Self::Graph(graph)
+    }
+}
 
+/// Upscaler that renders the image through a graph of fragment shaders
+///
+/// Every pass but the last renders into an intermediate texture sized and formatted as the pass
+/// asks, which later passes sample. The last pass renders into the output.
 #[derive(Debug)]
-struct GPUShadingUpscaler {
+pub struct GPUShadingUpscaler {
     device: wgpu::Device,
     queue: wgpu::Queue,
-    pipeline: wgpu::RenderPipeline,
-    bind_group: wgpu::BindGroup,
+    config: GpuShadingConfig,
+    passes: Vec<GraphPass>,
+    sampler: wgpu::Sampler,
+    bind_groups: Vec<wgpu::BindGroup>,
+    input: InputTex,
+    intermediates: Vec<wgpu::Texture>,
+    textures: Vec<wgpu::Texture>,
     output: OutputTex,
+    kernel_support: u32,
+    scale: Scale,
+    plan: ScalePlan,
+    original_dims: (u32, u32),
+    upscaled_image: DynamicImage,
+}
+
+/// Compiled pass of the shader graph
+#[derive(Debug)]
+struct GraphPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    inputs: Vec<PassInput>,
+    width: Extent,
+    height: Extent,
+    format: wgpu::TextureFormat,
+    params: Vec<ShaderParam>,
+    values: Vec<f32>,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+}
+
+#[derive(Debug)]
+struct InputTex {
+    size: wgpu::Extent3d,
+    texture_handle: wgpu::Texture,
 }
 
 #[derive(Debug)]
 struct OutputTex {
     size: wgpu::Extent3d,
+    padded_bytes_per_row: u32,
     buffer_handle: wgpu::Buffer,
     texture_handle: wgpu::Texture,
 }
 
 impl GPUShadingUpscaler {
-    fn from_image(
-        shader_path: impl AsRef<Path>,
+    /// Creates an upscaler rendering through `shader`, a built-in name or a WGSL file, and loads
+    /// `image`
+    pub fn from_image(
+        shader: impl Into<ShaderProgram>,
         image: &DynamicImage,
-        scale_factor: f32,
+        scale: impl Into<Scale>,
     ) -> Result<Self, Error> {
-        let instance = wgpu::Instance::default();
+        Self::from_image_with_config(shader, image, scale, GpuShadingConfig::default())
+    }
+
+    /// Same as [`GPUShadingUpscaler::from_image`], but with custom adapter, sampler and texture
+    /// settings
+    pub fn from_image_with_config(
+        shader: impl Into<ShaderProgram>,
+        image: &DynamicImage,
+        scale: impl Into<Scale>,
+        config: GpuShadingConfig,
+    ) -> Result<Self, Error> {
+        config.validate()?;
+        let program = shader.into();
+        let graph = program.graph()?;
+        let inputs = graph.resolve()?;
+        let scale = scale.into();
+        let original_dims = image.dimensions();
+        let plan = scale.plan(original_dims);
+        let image = &plan.crop_image(image);
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: config.backends,
+            ..Default::default()
+        });
 
         let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                ..Default::default()
-            })
+            .request_adapter(&config.adapter_options())
             .block_on()
-            .unwrap();
+            .ok_or(Error::NoSuitableAdapter)?;
 
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor::default(), None)
             .block_on()?;
 
+        let capabilities = device_capabilities(&device, &adapter);
+        let modules = graph
+            .passes()
+            .iter()
+            .map(|pass| pass.shader.translate(capabilities))
+            .collect::<Result<Vec<_>, _>>()?;
+
         let vertex_shader = device.create_shader_module(ShaderModuleDescriptor {
             label: Some("GPUSU_ShaderModuleDescriptor_Vertex"),
             source: wgpu::ShaderSource::Wgsl(Cow::from(include_str!("vertex_plane.wgsl"))),
         });
 
-        let fragment_shader = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("GPUSU_ShaderModuleDescriptor_Fragment"),
-            source: wgpu::ShaderSource::Wgsl({
-                let mut shader_code = String::new();
-                File::open(shader_path)?.read_to_string(&mut shader_code)?;
-                Cow::from(shader_code)
-            }),
+        let sampler = device.create_sampler(&config.sampler_descriptor());
+
+        let last = graph.passes().len() - 1;
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("GPUSU_UniformBindGroupLayout"),
         });
 
-        let in_texture_size = wgpu::Extent3d {
-            width: image.width(),
-            height: image.height(),
-            depth_or_array_layers: 1,
-        };
+        let passes = graph
+            .passes()
+            .iter()
+            .zip(inputs)
+            .zip(modules)
+            .enumerate()
+            .map(|(index, ((pass, inputs), (module, params)))| {
+                if let Some(name) = pass
+                    .params
+                    .keys()
+                    .find(|&name| params.iter().all(|param| &param.name != name))
+                {
+                    return Err(Error::InvalidShaderParam(format!(
+                        "pass {:?} doesn't declare {name:?}",
+                        pass.name
+                    )));
+                }
+                let values = params.iter().map(|param| {
+                    pass.params
+                        .get(&param.name)
+                        .copied()
+                        .unwrap_or(param.default)
+                });
+                let values = values.collect();
 
-        let in_texture_handle = device.create_texture(&TextureDescriptor {
-            label: Some("GPUSU_InputTextureHandle"),
-            size: in_texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
+                let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("GPUSU_UniformBuffer"),
+                    size: uniforms_size(params.len()),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &uniform_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    }],
+                    label: Some("GPUSU_UniformBindGroup"),
+                });
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
+                let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+                    binding,
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
@@ -86,138 +304,399 @@ impl GPUShadingUpscaler {
                         sample_type: wgpu::TextureSampleType::Float { filterable: true },
                     },
                     count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
+                };
+                let sampler_entry = wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
-                },
-            ],
-            label: Some("GPUSU_BindGroupLayout"),
+                };
+                let entries: Vec<_> = iter::once(texture_entry(0))
+                    .chain(iter::once(sampler_entry))
+                    .chain((2..inputs.len() as u32 + 1).map(texture_entry))
+                    .collect();
+                let bind_group_layout =
+                    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        entries: &entries,
+                        label: Some("GPUSU_BindGroupLayout"),
+                    });
+
+                let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("GPUSU_PipelineLayout"),
+                    bind_group_layouts: &[&bind_group_layout, &uniform_layout],
+                    push_constant_ranges: &[],
+                });
+
+                let fragment_shader = device.create_shader_module(ShaderModuleDescriptor {
+                    label: Some("GPUSU_ShaderModuleDescriptor_Fragment"),
+                    source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+                });
+                let format = if index == last {
+                    config.output_format
+                } else {
+                    pass.format
+                };
+
+                let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("GPUSU_Pipeline"),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &vertex_shader,
+                        entry_point: "vs_main",
+                        compilation_options: Default::default(),
+                        buffers: &[],
+                    },
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &fragment_shader,
+                        entry_point: "main",
+                        compilation_options: wgpu::PipelineCompilationOptions {
+                            constants: &pass.constants,
+                            ..Default::default()
+                        },
+                        targets: &[Some(format.into())],
+                    }),
+                    multiview: None,
+                    cache: None,
+                });
+
+                Ok(GraphPass {
+                    pipeline,
+                    bind_group_layout,
+                    inputs,
+                    width: pass.width,
+                    height: pass.height,
+                    format,
+                    params,
+                    values,
+                    uniform_buffer,
+                    uniform_bind_group,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let input = Self::create_input(&device, &config, image);
+        let intermediates = Self::create_intermediates(&device, &passes, &input, &plan);
+        let textures = Self::create_textures(&device, &queue, graph.textures());
+        let output = Self::create_output(&device, &config, &plan);
+        let bind_groups = Self::create_bind_groups(
+            &device,
+            &passes,
+            &input,
+            &intermediates,
+            &textures,
+            &sampler,
+        );
+
+        let scaler = Self {
+            device,
+            queue,
+            kernel_support: program.kernel_support().unwrap_or(config.kernel_support),
+            config,
+            passes,
+            sampler,
+            bind_groups,
+            input,
+            intermediates,
+            textures,
+            output,
+            scale,
+            plan,
+            original_dims,
+            upscaled_image: RgbaImage::new(1, 1).into(),
+        };
+        scaler.write_input(image);
+        scaler.write_uniforms();
+
+        Ok(scaler)
+    }
+
+    fn create_input(
+        device: &wgpu::Device,
+        config: &GpuShadingConfig,
+        image: &DynamicImage,
+    ) -> InputTex {
+        let size = wgpu::Extent3d {
+            width: image.width(),
+            height: image.height(),
+            depth_or_array_layers: 1,
+        };
+
+        let texture_handle = device.create_texture(&TextureDescriptor {
+            label: Some("GPUSU_InputTextureHandle"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.input_format,
+            usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
         });
 
-        let out_texture_size = wgpu::Extent3d {
-            width: (image.width() as f32 * scale_factor) as u32,
-            height: (image.height() as f32 * scale_factor) as u32,
+        InputTex {
+            size,
+            texture_handle,
+        }
+    }
+
+    /// Creates render targets of the passes before the last one
+    fn create_intermediates(
+        device: &wgpu::Device,
+        passes: &[GraphPass],
+        input: &InputTex,
+        plan: &ScalePlan,
+    ) -> Vec<wgpu::Texture> {
+        passes[..passes.len() - 1]
+            .iter()
+            .map(|pass| {
+                let size = wgpu::Extent3d {
+                    width: pass.width.resolve(input.size.width, plan.resized.0),
+                    height: pass.height.resolve(input.size.height, plan.resized.1),
+                    depth_or_array_layers: 1,
+                };
+                device.create_texture(&TextureDescriptor {
+                    label: Some("GPUSU_IntermediateTextureHandle"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: pass.format,
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+            })
+            .collect()
+    }
+
+    /// Uploads the fixed textures of the graph
+    fn create_textures(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        textures: &[GraphTexture],
+    ) -> Vec<wgpu::Texture> {
+        textures
+            .iter()
+            .map(|texture| {
+                let size = wgpu::Extent3d {
+                    width: texture.width,
+                    height: texture.height,
+                    depth_or_array_layers: 1,
+                };
+                let texture_handle = device.create_texture(&TextureDescriptor {
+                    label: Some("GPUSU_GraphTextureHandle"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: texture.format,
+                    usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                });
+                queue.write_texture(
+                    texture_handle.as_image_copy(),
+                    &texture.data,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(texture.data.len() as u32 / texture.height),
+                        rows_per_image: Some(texture.height),
+                    },
+                    size,
+                );
+                texture_handle
+            })
+            .collect()
+    }
+
+    fn create_output(
+        device: &wgpu::Device,
+        config: &GpuShadingConfig,
+        plan: &ScalePlan,
+    ) -> OutputTex {
+        let size = wgpu::Extent3d {
+            width: plan.canvas.0,
+            height: plan.canvas.1,
             depth_or_array_layers: 1,
         };
 
-        let out_texture_handle = device.create_texture(&TextureDescriptor {
+        let texture_handle = device.create_texture(&TextureDescriptor {
             label: Some("GPUSU_OutputTextureHandle"),
-            size: out_texture_size,
+            size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format: config.output_format,
             usage: TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+            view_formats: &[],
         });
 
-        let out_staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        // Buffer rows of texture copies must be aligned, padding is stripped on readback
+        let padded_bytes_per_row =
+            (size.width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer_handle = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("GPUSU_OutputBuffer"),
-            size: out_texture_size.width as u64 * out_texture_size.height as u64 * 4,
+            size: padded_bytes_per_row as u64 * size.height as u64,
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
             mapped_at_creation: false,
         });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(
-                        &in_texture_handle.create_view(&wgpu::TextureViewDescriptor::default()),
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&device.create_sampler(
-                        &wgpu::SamplerDescriptor {
-                            address_mode_u: wgpu::AddressMode::ClampToEdge,
-                            address_mode_v: wgpu::AddressMode::ClampToEdge,
-                            address_mode_w: wgpu::AddressMode::ClampToEdge,
-                            mag_filter: wgpu::FilterMode::Linear,
-                            min_filter: wgpu::FilterMode::Nearest,
-                            mipmap_filter: wgpu::FilterMode::Nearest,
-                            ..Default::default()
-                        },
-                    )),
-                },
-            ],
-            label: Some("GPUSU_BindGroup"),
-        });
+        OutputTex {
+            size,
+            padded_bytes_per_row,
+            buffer_handle,
+            texture_handle,
+        }
+    }
 
-        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("GPUSU_PipelineLayout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
+    /// Creates bind groups of every pass, binding its first input, the sampler and then the other
+    /// inputs
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        passes: &[GraphPass],
+        input: &InputTex,
+        intermediates: &[wgpu::Texture],
+        textures: &[wgpu::Texture],
+        sampler: &wgpu::Sampler,
+    ) -> Vec<wgpu::BindGroup> {
+        passes
+            .iter()
+            .map(|pass| {
+                let views: Vec<_> = pass
+                    .inputs
+                    .iter()
+                    .map(|input_ref| match *input_ref {
+                        PassInput::Original => &input.texture_handle,
+                        PassInput::Pass(index) => &intermediates[index],
+                        PassInput::Texture(index) => &textures[index],
+                    })
+                    .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()))
+                    .collect();
+                let entries: Vec<_> = iter::once(wgpu::BindingResource::TextureView(&views[0]))
+                    .chain(iter::once(wgpu::BindingResource::Sampler(sampler)))
+                    .chain(views[1..].iter().map(wgpu::BindingResource::TextureView))
+                    .enumerate()
+                    .map(|(binding, resource)| wgpu::BindGroupEntry {
+                        binding: binding as u32,
+                        resource,
+                    })
+                    .collect();
 
-        let pipeline = device.create_render_pipeline(
-            &wgpu::RenderPipelineDescriptor {
-                label: Some("GPUSU_Pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &vertex_shader,
-                    entry_point: "vs_main",
-                    compilation_options: Default::default(),
-                    buffers: &[],
-                },
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                fragment: Some(wgpu::FragmentState {
-                    module: &fragment_shader,
-                    entry_point: "main",
-                    compilation_options: Default::default(),
-                    targets: &[Some(wgpu::TextureFormat::Rgba8UnormSrgb.into())],
-                }),
-                multiview: None,
-                cache: None,
-            },
-        );
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &pass.bind_group_layout,
+                    entries: &entries,
+                    label: Some("GPUSU_BindGroup"),
+                })
+            })
+            .collect()
+    }
+
+    /// Writes sizes and parameter values of every pass into its uniform buffer
+    fn write_uniforms(&self) {
+        let texture_size = |texture: &wgpu::Texture| (texture.width(), texture.height());
+        let last = self.passes.len() - 1;
+        for (index, pass) in self.passes.iter().enumerate() {
+            let source = match pass.inputs[0] {
+                PassInput::Original => (self.input.size.width, self.input.size.height),
+                PassInput::Pass(input) => texture_size(&self.intermediates[input]),
+                PassInput::Texture(input) => texture_size(&self.textures[input]),
+            };
+            let target = if index == last {
+                self.plan.resized
+            } else {
+                texture_size(&self.intermediates[index])
+            };
+            let size = |(width, height): (u32, u32)| {
+                let (width, height) = (width as f32, height as f32);
+                [width, height, 1.0 / width, 1.0 / height]
+            };
+
+            let mut uniforms = Vec::with_capacity(uniforms_size(pass.values.len()) as usize / 4);
+            uniforms.extend(size(source));
+            uniforms.extend(size(target));
+            uniforms.push(self.plan.resized.0 as f32 / self.input.size.width as f32);
+            uniforms.push(self.plan.resized.1 as f32 / self.input.size.height as f32);
+            uniforms.extend(&pass.values);
+            uniforms.resize(uniforms.capacity(), 0.0);
+            self.queue
+                .write_buffer(&pass.uniform_buffer, 0, bytemuck::cast_slice(&uniforms));
+        }
+    }
+
+    /// Current values of the shader parameters, see
+    /// [`parse_params`](crate::shader_graph::parse_params)
+    pub fn params(&self) -> Vec<(&str, f32)> {
+        let mut params: Vec<(&str, f32)> = Vec::new();
+        for pass in &self.passes {
+            for (param, &value) in pass.params.iter().zip(&pass.values) {
+                if params.iter().all(|&(name, _)| name != param.name) {
+                    params.push((&param.name, value));
+                }
+            }
+        }
+        params
+    }
+
+    /// Sets the parameter `name` of every pass declaring it, without rebuilding pipelines
+    pub fn set_param(&mut self, name: &str, value: f32) -> Result<(), Error> {
+        let mut found = false;
+        for pass in &mut self.passes {
+            if let Some(index) = pass.params.iter().position(|param| param.name == name) {
+                pass.values[index] = value;
+                found = true;
+            }
+        }
+        if !found {
+            return Err(Error::InvalidShaderParam(format!(
+                "no pass declares {name:?}"
+            )));
+        }
+        self.write_uniforms();
+        Ok(())
+    }
+
+    fn write_input(&self, image: &DynamicImage) {
+        let mut pixels = image.to_rgba8();
+        if is_bgra(self.config.input_format) {
+            pixels.pixels_mut().for_each(|p| p.0.swap(0, 2));
+        }
 
-        queue.write_texture(
+        self.queue.write_texture(
             wgpu::ImageCopyTexture {
-                texture: &in_texture_handle,
+                texture: &self.input.texture_handle,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            &image.to_rgba8(),
+            &pixels,
             wgpu::ImageDataLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * image.width()),
                 rows_per_image: Some(image.height()),
             },
-            in_texture_size,
+            self.input.size,
         );
-
-        Ok(Self {
-            device,
-            queue,
-            pipeline,
-            bind_group,
-            output: OutputTex {
-                size: out_texture_size,
-                buffer_handle: out_staging_buffer,
-                texture_handle: out_texture_handle,
-            },
-        })
     }
 
-    fn queue_render(&mut self) {
-        let mut command_encoder =
-            self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-        {
+    fn queue_render(&self) {
+        let last = self.passes.len() - 1;
+        let mut command_encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let targets = self
+            .intermediates
+            .iter()
+            .chain(iter::once(&self.output.texture_handle));
+        let passes = self.passes.iter().zip(&self.bind_groups).zip(targets);
+        for (index, ((pass, bind_group), target)) in passes.enumerate() {
             let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("GPUSU_RenderPass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.output.texture_handle.create_view(&wgpu::TextureViewDescriptor::default()),
+                    view: &target.create_view(&wgpu::TextureViewDescriptor::default()),
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                        load: wgpu::LoadOp::Clear(self.config.clear_color),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -226,8 +705,20 @@ impl GPUShadingUpscaler {
                 timestamp_writes: None,
             });
 
-            render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            // Intermediate textures are covered whole
+            if index == last {
+                render_pass.set_viewport(
+                    self.plan.offset.0 as f32,
+                    self.plan.offset.1 as f32,
+                    self.plan.resized.0 as f32,
+                    self.plan.resized.1 as f32,
+                    0.0,
+                    1.0,
+                );
+            }
+            render_pass.set_pipeline(&pass.pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.set_bind_group(1, &pass.uniform_bind_group, &[]);
             render_pass.draw(0..3, 0..1);
         }
 
@@ -242,7 +733,7 @@ impl GPUShadingUpscaler {
                 buffer: &self.output.buffer_handle,
                 layout: wgpu::ImageDataLayout {
                     offset: 0,
-                    bytes_per_row: Some(self.output.size.width * 4),
+                    bytes_per_row: Some(self.output.padded_bytes_per_row),
                     rows_per_image: Some(self.output.size.height),
                 },
             },
@@ -261,11 +752,18 @@ impl GPUShadingUpscaler {
         receiver.recv().unwrap()?;
 
         let output_raw = {
-            let mut cpu_buffer = Vec::with_capacity(self.output.size.height as usize * self.output.size.width as usize * 4);
+            let row_bytes = self.output.size.width as usize * 4;
+            let mut cpu_buffer = Vec::with_capacity(self.output.size.height as usize * row_bytes);
             let view = buffer_slice.get_mapped_range();
-            cpu_buffer.extend_from_slice(&view[..]);
+            for row in view.chunks_exact(self.output.padded_bytes_per_row as usize) {
+                cpu_buffer.extend_from_slice(&row[..row_bytes]);
+            }
+            if is_bgra(self.config.output_format) {
+                cpu_buffer.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+            }
             cpu_buffer
         };
+        self.output.buffer_handle.unmap();
 
         match RgbaImage::from_raw(self.output.size.width, self.output.size.height, output_raw) {
             Some(image) => Ok(image),
@@ -273,21 +771,714 @@ impl GPUShadingUpscaler {
         }
     }
 
-    fn new(path: impl AsRef<Path>, scale_factor: f32) -> Result<Self, Error> {
-        Self::from_image(path, &RgbImage::new(512, 512).into(), scale_factor)
+    /// Creates an upscaler rendering through `shader` with a blank 512x512 image loaded
+    pub fn new(shader: impl Into<ShaderProgram>, scale: impl Into<Scale>) -> Result<Self, Error> {
+        Self::from_image(shader, &RgbImage::new(512, 512).into(), scale)
+    }
+}
+
+impl UpscaleImage for GPUShadingUpscaler {
+    type Error = Error;
+
+    fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error> {
+        if image.dimensions() == self.original_dims {
+            self.write_input(&self.plan.crop_image(image));
+            return Ok(());
+        }
+
+        self.original_dims = image.dimensions();
+        self.plan = self.scale.plan(self.original_dims);
+        let image = &self.plan.crop_image(image);
+        self.input = Self::create_input(&self.device, &self.config, image);
+        self.intermediates =
+            Self::create_intermediates(&self.device, &self.passes, &self.input, &self.plan);
+        self.output = Self::create_output(&self.device, &self.config, &self.plan);
+        self.bind_groups = Self::create_bind_groups(
+            &self.device,
+            &self.passes,
+            &self.input,
+            &self.intermediates,
+            &self.textures,
+            &self.sampler,
+        );
+        self.write_input(image);
+        self.write_uniforms();
+        Ok(())
+    }
+
+    fn upscale(&self) -> Result<DynamicImage, Self::Error> {
+        self.queue_render();
+        Ok(self.get_rendered_image()?.into())
+    }
+
+    fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error> {
+        self.upscaled_image = self.upscale()?;
+        Ok(&self.upscaled_image)
+    }
+
+    fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error> {
+        for _ in 0..times {
+            self.upscale_inplace()?;
+        }
+        Ok(&self.upscaled_image)
+    }
+
+    fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    fn original_dimensions(&self) -> (u32, u32) {
+        self.original_dims
+    }
+
+    fn plan(&self) -> ScalePlan {
+        self.plan
+    }
+
+    fn kernel_support(&self) -> u32 {
+        self.kernel_support
+    }
+
+    fn max_dimension(&self) -> Option<u32> {
+        Some(self.device.limits().max_texture_dimension_2d)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shader_graph::ORIGINAL;
 
     #[test]
     fn pipeline_test() {
-        let image = image::open("target/input.jpeg").unwrap().crop(0, 128, 1024, 1024);
-        let mut scaler = GPUShadingUpscaler::from_image("shaders/passthrough.wgsl", &image, 2.0).unwrap();
+        let image = image::open("target/input.jpeg")
+            .unwrap()
+            .crop(0, 128, 1024, 1024);
+        let scaler =
+            GPUShadingUpscaler::from_image("shaders/passthrough.wgsl", &image, 2.0).unwrap();
 
         scaler.queue_render();
-        scaler.get_rendered_image().unwrap().save("target/test.png").unwrap();
+        scaler
+            .get_rendered_image()
+            .unwrap()
+            .save("target/test.png")
+            .unwrap();
+    }
+
+    #[test]
+    fn reload_test() {
+        let mut scaler = GPUShadingUpscaler::new("shaders/passthrough.wgsl", 2.0).unwrap();
+
+        let white = RgbImage::from_pixel(512, 512, image::Rgb([255, 255, 255])).into();
+        scaler.load(&white).unwrap();
+        assert_eq!(
+            scaler.upscale().unwrap().to_rgb8().get_pixel(7, 7).0,
+            [255; 3]
+        );
+
+        let black = RgbImage::new(512, 512).into();
+        scaler.load(&black).unwrap();
+        assert_eq!(
+            scaler.upscale().unwrap().to_rgb8().get_pixel(7, 7).0,
+            [0; 3]
+        );
+
+        scaler.load(&RgbImage::new(256, 128).into()).unwrap();
+        assert_eq!(scaler.upscale_repeat(2).unwrap().dimensions(), (512, 256));
+    }
+
+    fn fallback() -> GpuShadingConfig {
+        GpuShadingConfig::default().force_fallback_adapter(true)
+    }
+
+    fn odd_image() -> DynamicImage {
+        RgbImage::from_fn(333, 517, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x * y) % 256) as u8])
+        })
+        .into()
+    }
+
+    #[test]
+    fn unaligned_passthrough_test() {
+        let image = odd_image();
+        let scaler = GPUShadingUpscaler::from_image_with_config(
+            "shaders/passthrough.wgsl",
+            &image,
+            1.0,
+            fallback(),
+        )
+        .unwrap();
+
+        let output = scaler.upscale().unwrap().to_rgb8();
+        assert_eq!(output.dimensions(), (333, 517));
+
+        // Passthrough shader swaps red and blue channels
+        for (expected, actual) in image.to_rgb8().pixels().zip(output.pixels()) {
+            let [r, g, b] = expected.0;
+            for (e, a) in [b, g, r].into_iter().zip(actual.0) {
+                assert!(
+                    e.abs_diff(a) <= 1,
+                    "expected {:?}, got {:?}",
+                    [b, g, r],
+                    actual.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unaligned_sizes_test() {
+        let mut scaler = GPUShadingUpscaler::from_image_with_config(
+            "shaders/passthrough.wgsl",
+            &odd_image(),
+            2.0,
+            fallback(),
+        )
+        .unwrap();
+        assert_eq!(scaler.upscale().unwrap().dimensions(), (666, 1034));
+
+        for (width, height) in [(1, 1), (3, 7), (63, 65), (257, 3)] {
+            scaler.load(&RgbImage::new(width, height).into()).unwrap();
+            assert_eq!(
+                scaler.upscale().unwrap().dimensions(),
+                (width * 2, height * 2)
+            );
+        }
+    }
+
+    #[test]
+    fn tiled_test() {
+        let image = odd_image();
+        let scaler = GPUShadingUpscaler::from_image_with_config(
+            "shaders/passthrough.wgsl",
+            &image,
+            2.0,
+            fallback(),
+        )
+        .unwrap();
+        let whole = scaler.upscale().unwrap().to_rgb8();
+
+        let mut tiled = crate::tiled::TiledUpscaler::new(scaler).with_tile_size(100);
+        let tiled = tiled.upscale_image(&image).unwrap().to_rgb8();
+        assert_eq!(tiled.dimensions(), whole.dimensions());
+        for (expected, actual) in whole.pixels().zip(tiled.pixels()) {
+            for (e, a) in expected.0.into_iter().zip(actual.0) {
+                assert!(
+                    e.abs_diff(a) <= 1,
+                    "expected {:?}, got {:?}",
+                    expected.0,
+                    actual.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn program_names() {
+        assert_eq!(ShaderProgram::from("fsr"), ShaderProgram::FSR);
+        assert_eq!(
+            ShaderProgram::from("shaders/passthrough.wgsl"),
+            ShaderProgram::File("shaders/passthrough.wgsl".into())
+        );
+        assert_eq!(ShaderProgram::from_name("shaders/passthrough.wgsl"), None);
+        assert_eq!(
+            ShaderProgram::from("lanczos3"),
+            ShaderProgram::Builtin(BuiltinShader::Lanczos3)
+        );
+    }
+
+    /// Anti-aliased disc and bars, 4x4 supersampled
+    fn shapes(size: u32) -> DynamicImage {
+        let s = size as f32;
+        RgbImage::from_fn(size, size, |x, y| {
+            let mut sum = [0.0f32; 3];
+            for sample in 0..16 {
+                let px = x as f32 + (sample % 4) as f32 / 4.0 + 0.125;
+                let py = y as f32 + (sample / 4) as f32 / 4.0 + 0.125;
+                let disc = (px - 0.4 * s).hypot(py - 0.45 * s) < 0.3 * s;
+                let bars = (((px + 0.6 * py) / s * 9.0) as u32).is_multiple_of(2);
+                let value = match (disc, bars) {
+                    (true, _) => [230.0, 200.0, 60.0],
+                    (false, true) => [30.0, 60.0, 90.0],
+                    (false, false) => [180.0, 190.0, 210.0],
+                };
+                sum.iter_mut().zip(value).for_each(|(s, v)| *s += v / 16.0);
+            }
+            image::Rgb(sum.map(|v| v.round() as u8))
+        })
+        .into()
+    }
+
+    fn squared_error(a: &DynamicImage, b: &DynamicImage) -> f64 {
+        let (a, b) = (a.to_rgb8(), b.to_rgb8());
+        a.as_raw()
+            .iter()
+            .zip(b.as_raw())
+            .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+            .sum()
+    }
+
+    /// FSR works on gamma-encoded values
+    fn unorm() -> GpuShadingConfig {
+        fallback()
+            .input_format(wgpu::TextureFormat::Rgba8Unorm)
+            .output_format(wgpu::TextureFormat::Rgba8Unorm)
+    }
+
+    #[test]
+    fn fsr_keeps_flat_colour() {
+        let image = RgbImage::from_pixel(37, 23, image::Rgb([40, 120, 200])).into();
+        let scaler =
+            GPUShadingUpscaler::from_image_with_config("fsr", &image, 2.0, unorm()).unwrap();
+        let output = scaler.upscale().unwrap().to_rgb8();
+        assert_eq!(output.dimensions(), (74, 46));
+        for pixel in output.pixels() {
+            assert!(
+                pixel
+                    .0
+                    .iter()
+                    .zip([40, 120, 200])
+                    .all(|(&a, e)| a.abs_diff(e) <= 1),
+                "{:?}",
+                pixel.0
+            );
+        }
+    }
+
+    #[test]
+    fn fsr_beats_bilinear() {
+        let truth = shapes(128);
+        let small = truth.resize_exact(64, 64, image::imageops::FilterType::Triangle);
+        let scaler =
+            GPUShadingUpscaler::from_image_with_config(ShaderProgram::FSR, &small, 2.0, unorm())
+                .unwrap();
+        let fsr = squared_error(&scaler.upscale().unwrap(), &truth);
+        let bilinear = squared_error(
+            &small.resize_exact(128, 128, image::imageops::FilterType::Triangle),
+            &truth,
+        );
+        assert!(fsr < bilinear * 0.9, "fsr {fsr}, bilinear {bilinear}");
+    }
+
+    #[test]
+    fn fsr_sharpness() {
+        let image = shapes(48);
+        let mut scaler =
+            GPUShadingUpscaler::from_image_with_config(ShaderProgram::FSR, &image, 2.0, unorm())
+                .unwrap();
+        assert_eq!(scaler.params(), [("sharpness", 0.2)]);
+        let mut contrast = |sharpness: f32| {
+            scaler.set_param("sharpness", sharpness).unwrap();
+            let output = scaler.upscale().unwrap().to_luma8();
+            let row = output.width() as usize;
+            let raw = output.as_raw();
+            let neighbours = raw.iter().zip(&raw[1..]).chain(raw.iter().zip(&raw[row..]));
+            neighbours.map(|(&a, &b)| a.abs_diff(b) as u32).sum::<u32>()
+        };
+        let (sharp, soft) = (contrast(0.0), contrast(4.0));
+        assert!(sharp > soft, "sharp {sharp}, soft {soft}");
+    }
+
+    #[test]
+    fn fsr_tiles_match_whole_image() {
+        let image = shapes(70);
+        let scaler =
+            GPUShadingUpscaler::from_image_with_config("fsr", &image, 2.0, unorm()).unwrap();
+        assert_eq!(scaler.kernel_support(), 3);
+        let whole = scaler.upscale().unwrap().to_rgb8();
+
+        let mut tiled = crate::tiled::TiledUpscaler::new(scaler).with_tile_size(24);
+        let tiled = tiled.upscale_image(&image).unwrap().to_rgb8();
+        for (expected, actual) in whole.pixels().zip(tiled.pixels()) {
+            for (e, a) in expected.0.into_iter().zip(actual.0) {
+                assert!(
+                    e.abs_diff(a) <= 1,
+                    "expected {:?}, got {:?}",
+                    expected.0,
+                    actual.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn builtins_keep_flat_colour() {
+        let image = RgbImage::from_pixel(37, 23, image::Rgb([40, 120, 200])).into();
+        for shader in BuiltinShader::ALL {
+            let scaler =
+                GPUShadingUpscaler::from_image_with_config(shader, &image, 2.5, unorm()).unwrap();
+            for pixel in scaler.upscale().unwrap().to_rgb8().pixels() {
+                let flat = pixel
+                    .0
+                    .iter()
+                    .zip([40, 120, 200])
+                    .all(|(&a, e)| a.abs_diff(e) <= 1);
+                assert!(flat, "{shader:?}: {:?}", pixel.0);
+            }
+        }
+    }
+
+    #[test]
+    fn builtins_match_cpu() {
+        use crate::cpu_algo::{resample, ResampleKernel};
+
+        let image = shapes(45);
+        for (shader, kernel) in [
+            (BuiltinShader::Bilinear, ResampleKernel::Triangle),
+            (BuiltinShader::BSpline, ResampleKernel::CUBIC_B_SPLINE),
+            (BuiltinShader::CatmullRom, ResampleKernel::CATMULL_ROM),
+            (BuiltinShader::Lanczos2, ResampleKernel::LANCZOS2),
+            (BuiltinShader::Lanczos3, ResampleKernel::LANCZOS3),
+        ] {
+            let scaler =
+                GPUShadingUpscaler::from_image_with_config(shader, &image, 2.5, unorm()).unwrap();
+            let output = scaler.upscale().unwrap().to_rgb8();
+            let expected = resample(&image, output.dimensions(), kernel).to_rgb8();
+
+            // Clamp-to-edge and the CPU's renormalization only differ where the kernel leaves the
+            // image
+            let border = (shader.kernel_support() as f32 * 2.5).ceil() as u32;
+            let (width, height) = output.dimensions();
+            for (x, y, actual) in output.enumerate_pixels() {
+                if x < border || y < border || x >= width - border || y >= height - border {
+                    continue;
+                }
+                let expected = expected.get_pixel(x, y).0;
+                let close = expected
+                    .iter()
+                    .zip(actual.0)
+                    .all(|(e, a)| e.abs_diff(a) <= 2);
+                assert!(
+                    close,
+                    "{shader:?} at {x}, {y}: expected {expected:?}, got {:?}",
+                    actual.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sharp_bilinear_is_nearest_at_integer_scales() {
+        let image = odd_image().crop_imm(0, 0, 40, 30).to_rgb8();
+        let scaler = GPUShadingUpscaler::from_image_with_config(
+            "sharp-bilinear",
+            &image.clone().into(),
+            3.0,
+            unorm(),
+        )
+        .unwrap();
+        for (x, y, actual) in scaler.upscale().unwrap().to_rgb8().enumerate_pixels() {
+            let expected = image.get_pixel(x / 3, y / 3).0;
+            assert!(
+                expected
+                    .iter()
+                    .zip(actual.0)
+                    .all(|(e, a)| e.abs_diff(a) <= 1),
+                "{x}, {y}: {:?}",
+                actual.0
+            );
+        }
+    }
+
+    #[test]
+    fn cas_sharpens_bilinear() {
+        let image = shapes(48);
+        let contrast = |output: DynamicImage| {
+            let output = output.to_luma8();
+            let row = output.width() as usize;
+            let raw = output.as_raw();
+            let neighbours = raw.iter().zip(&raw[1..]).chain(raw.iter().zip(&raw[row..]));
+            neighbours.map(|(&a, &b)| a.abs_diff(b) as u32).sum::<u32>()
+        };
+        let bilinear =
+            GPUShadingUpscaler::from_image_with_config("bilinear", &image, 2.0, unorm()).unwrap();
+        let bilinear = contrast(bilinear.upscale().unwrap());
+
+        let mut cas =
+            GPUShadingUpscaler::from_image_with_config("cas", &image, 2.0, unorm()).unwrap();
+        assert_eq!(cas.params(), [("sharpness", 0.5)]);
+        let soft = contrast(cas.upscale().unwrap());
+        cas.set_param("sharpness", 1.0).unwrap();
+        let sharp = contrast(cas.upscale().unwrap());
+        assert!(
+            sharp > soft && soft > bilinear,
+            "sharp {sharp}, soft {soft}, bilinear {bilinear}"
+        );
+    }
+
+    #[test]
+    fn builtin_tiles_match_whole_image() {
+        let image = shapes(70);
+        for shader in BuiltinShader::ALL {
+            let scaler =
+                GPUShadingUpscaler::from_image_with_config(shader, &image, 2.0, unorm()).unwrap();
+            let whole = scaler.upscale().unwrap().to_rgb8();
+
+            let mut tiled = crate::tiled::TiledUpscaler::new(scaler).with_tile_size(24);
+            let tiled = tiled.upscale_image(&image).unwrap().to_rgb8();
+            for (expected, actual) in whole.pixels().zip(tiled.pixels()) {
+                let close = expected
+                    .0
+                    .iter()
+                    .zip(actual.0)
+                    .all(|(e, a)| e.abs_diff(a) <= 1);
+                assert!(
+                    close,
+                    "{shader:?}: expected {:?}, got {:?}",
+                    expected.0, actual.0
+                );
+            }
+        }
+    }
+
+    /// One axis of a Catmull-Rom resize, renormalized where the window leaves the image like the
+    /// CPU resampler
+    const CATMULL_ROM_AXIS: &str = "
+        @group(0) @binding(0) var r_color: texture_2d<f32>;
+        @group(0) @binding(1) var r_sampler: sampler;
+        override horizontal: bool = true;
+
+        fn weight(x: f32) -> f32 {
+            let a = abs(x);
+            if (a < 1.0) {
+                return (1.5 * a - 2.5) * a * a + 1.0;
+            }
+            if (a < 2.0) {
+                return ((-0.5 * a + 2.5) * a - 4.0) * a + 2.0;
+            }
+            return 0.0;
+        }
+
+        @fragment fn main(@location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+            let size = vec2<i32>(textureDimensions(r_color));
+            let axis = select(vec2<i32>(0, 1), vec2<i32>(1, 0), horizontal);
+            let center = dot(vec2<f32>(axis), coords * vec2<f32>(size));
+            let across = vec2<i32>(coords * vec2<f32>(size)) * axis.yx;
+            let length = dot(axis, size);
+            var sum = vec4<f32>(0.0);
+            var total = 0.0;
+            for (var j = i32(floor(center - 2.0)); j < i32(ceil(center + 2.0)); j++) {
+                if (j >= 0 && j < length) {
+                    let w = weight(f32(j) + 0.5 - center);
+                    sum += w * textureLoad(r_color, across + axis * j, 0);
+                    total += w;
+                }
+            }
+            return sum / total;
+        }
+    ";
+
+    #[test]
+    fn separable_graph_matches_cpu() {
+        let image = shapes(45);
+        let graph = ShaderGraph::new()
+            .with_pass(
+                ShaderPass::new("horizontal", PassShader::Wgsl(CATMULL_ROM_AXIS.into()))
+                    .with_size(Extent::Output, Extent::Source),
+            )
+            .with_pass(
+                ShaderPass::new("vertical", PassShader::Wgsl(CATMULL_ROM_AXIS.into()))
+                    .with_constant("horizontal", 0.0),
+            );
+        let scaler =
+            GPUShadingUpscaler::from_image_with_config(graph, &image, 2.0, unorm()).unwrap();
+        let output = scaler.upscale().unwrap().to_rgb8();
+
+        let expected = crate::cpu_algo::resample(
+            &image,
+            (90, 90),
+            crate::cpu_algo::ResampleKernel::CATMULL_ROM,
+        );
+        for (expected, actual) in expected.to_rgb8().pixels().zip(output.pixels()) {
+            for (e, a) in expected.0.into_iter().zip(actual.0) {
+                assert!(
+                    e.abs_diff(a) <= 1,
+                    "expected {:?}, got {:?}",
+                    expected.0,
+                    actual.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn graph_binds_inputs_in_order() {
+        let fill = |colour: &str| {
+            let source = format!(
+                "@fragment fn main() -> @location(0) vec4<f32> {{ return vec4<f32>({colour}); }}"
+            );
+            PassShader::Wgsl(source.into())
+        };
+        let red = ShaderPass::new("red", fill("1.0, 0.0, 0.0, 1.0"));
+        let green = ShaderPass::new("green", fill("0.0, 0.5, 0.0, 1.0"));
+        let graph = ShaderGraph::new()
+            .with_pass(red.with_size(Extent::Source, Extent::Source))
+            .with_pass(green.with_size(Extent::Scaled(0.5), Extent::Source))
+            .with_pass(
+                ShaderPass::new(
+                    "mix",
+                    PassShader::Wgsl(
+                        "
+                        @group(0) @binding(0) var green: texture_2d<f32>;
+                        @group(0) @binding(1) var r_sampler: sampler;
+                        @group(0) @binding(2) var original: texture_2d<f32>;
+                        @group(0) @binding(3) var red: texture_2d<f32>;
+
+                        @fragment
+                        fn main(@location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+                            let r = textureSample(red, r_sampler, coords).r;
+                            let g = textureSample(green, r_sampler, coords).g;
+                            let b = textureSample(original, r_sampler, coords).b;
+                            return vec4<f32>(r, g, b, 1.0);
+                        }
+                        "
+                        .into(),
+                    ),
+                )
+                .with_inputs(["green", ORIGINAL, "red"]),
+            );
+
+        let image = RgbImage::from_pixel(7, 5, image::Rgb([0, 0, 200])).into();
+        let mut scaler =
+            GPUShadingUpscaler::from_image_with_config(graph, &image, 3.0, unorm()).unwrap();
+        for pixel in scaler.upscale().unwrap().to_rgb8().pixels() {
+            assert!(
+                pixel
+                    .0
+                    .iter()
+                    .zip([255, 128, 200])
+                    .all(|(&a, e)| a.abs_diff(e) <= 1),
+                "{:?}",
+                pixel.0
+            );
+        }
+
+        scaler
+            .load(&RgbImage::from_pixel(3, 2, image::Rgb([0, 0, 20])).into())
+            .unwrap();
+        let output = scaler.upscale().unwrap().to_rgb8();
+        assert_eq!(output.dimensions(), (9, 6));
+        for pixel in output.pixels() {
+            assert!(
+                pixel
+                    .0
+                    .iter()
+                    .zip([255, 128, 20])
+                    .all(|(&a, e)| a.abs_diff(e) <= 1),
+                "{:?}",
+                pixel.0
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_graph_is_rejected() {
+        let pass =
+            ShaderPass::new("a", PathBuf::from("shaders/passthrough.wgsl")).with_inputs(["b"]);
+        let graph = ShaderGraph::new().with_pass(pass);
+        let result =
+            GPUShadingUpscaler::from_image_with_config(graph, &odd_image(), 2.0, fallback());
+        assert!(matches!(result, Err(Error::InvalidShaderGraph(_))));
+    }
+
+    #[test]
+    fn uniforms_reach_shader() {
+        let shader = "
+            // @param gain = 0.5
+
+            @group(0) @binding(0) var r_color: texture_2d<f32>;
+            @group(0) @binding(1) var r_sampler: sampler;
+
+            @fragment fn main(@location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+                let sizes = vec2<f32>(r_uniforms.source_size.x, r_uniforms.target_size.y) / 255.0;
+                return vec4<f32>(sizes, r_uniforms.scale.x / 15.0, r_uniforms.gain);
+            }
+        ";
+        let graph = ShaderGraph::new()
+            .with_pass(ShaderPass::new("uniforms", PassShader::Wgsl(shader.into())));
+        let image = odd_image().crop(0, 0, 50, 40);
+        let mut scaler =
+            GPUShadingUpscaler::from_image_with_config(graph, &image, 3.0, unorm()).unwrap();
+        assert_eq!(
+            scaler.upscale().unwrap().to_rgba8().get_pixel(5, 5).0,
+            [50, 120, 51, 128]
+        );
+
+        scaler.set_param("gain", 1.0).unwrap();
+        scaler.load(&RgbImage::new(20, 10).into()).unwrap();
+        assert_eq!(
+            scaler.upscale().unwrap().to_rgba8().get_pixel(5, 5).0,
+            [20, 30, 51, 255]
+        );
+
+        assert!(matches!(
+            scaler.set_param("missing", 1.0),
+            Err(Error::InvalidShaderParam(_))
+        ));
+        let undeclared = ShaderGraph::new().with_pass(
+            ShaderPass::new("uniforms", PassShader::Wgsl(shader.into())).with_param("missing", 1.0),
+        );
+        let result =
+            GPUShadingUpscaler::from_image_with_config(undeclared, &odd_image(), 2.0, unorm());
+        assert!(matches!(result, Err(Error::InvalidShaderParam(_))));
+    }
+
+    #[test]
+    fn glsl_and_spirv_match_wgsl() {
+        let image = odd_image();
+        let render = |shader: ShaderProgram| {
+            let scaler =
+                GPUShadingUpscaler::from_image_with_config(shader, &image, 2.0, fallback())
+                    .unwrap();
+            scaler.upscale().unwrap().to_rgba8()
+        };
+        let wgsl = render("shaders/passthrough.wgsl".into());
+        assert!(render("shaders/passthrough.frag".into()) == wgsl);
+
+        let module =
+            naga::front::wgsl::parse_str(include_str!("../shaders/passthrough.wgsl")).unwrap();
+        let info = naga::valid::Validator::new(Default::default(), Default::default())
+            .validate(&module)
+            .unwrap();
+        let words = naga::back::spv::write_vec(&module, &info, &Default::default(), None).unwrap();
+        let path = std::env::temp_dir().join("scale-benchmarks-passthrough.spv");
+        std::fs::write(&path, bytemuck::cast_slice(&words)).unwrap();
+        assert!(render(path.into()) == wgsl);
+    }
+
+    #[test]
+    fn translation_errors_are_returned() {
+        let shader = PassShader::Glsl("#version 450\nvoid main() {\n  undefined();\n}\n".into());
+        let graph = ShaderGraph::new().with_pass(ShaderPass::new("broken", shader));
+        let result =
+            GPUShadingUpscaler::from_image_with_config(graph, &odd_image(), 2.0, fallback());
+        assert!(
+            matches!(
+                result,
+                Err(Error::ShaderTranslation {
+                    line: 3,
+                    column: 3,
+                    ..
+                })
+            ),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn validation_follows_device_features() {
+        // Doubles need `SHADER_F64`, which the upscaler never requests
+        let source = "@fragment\nfn main() -> @location(0) vec4<f32> {\n    \
+                      let half = f64(0.5);\n    return vec4<f32>(f32(half));\n}\n";
+        let shader = PassShader::Wgsl(source.into());
+        assert!(shader.translate(naga::valid::Capabilities::FLOAT64).is_ok());
+
+        let graph = ShaderGraph::new().with_pass(ShaderPass::new("doubles", shader));
+        let result =
+            GPUShadingUpscaler::from_image_with_config(graph, &odd_image(), 2.0, fallback());
+        assert!(
+            matches!(result, Err(Error::ShaderTranslation { line: 3, .. })),
+            "{result:?}"
+        );
     }
 }
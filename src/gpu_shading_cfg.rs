@@ -0,0 +1,263 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Configuration of [`GPUShadingUpscaler`](crate::gpu_shading::GPUShadingUpscaler)
+///
+/// Can be loaded from a JSON file to keep benchmark runs reproducible,
+/// missing fields take their default values:
+///
+/// ```json
+/// {
+///     "power_preference": "low-power",
+///     "force_fallback_adapter": true,
+///     "backends": "vulkan,gl",
+///     "mag_filter": "nearest",
+///     "address_mode_u": "mirror-repeat"
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GpuShadingConfig {
+    pub power_preference: wgpu::PowerPreference,
+    pub force_fallback_adapter: bool,
+    #[serde(with = "backends_list")]
+    pub backends: wgpu::Backends,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub input_format: wgpu::TextureFormat,
+    pub output_format: wgpu::TextureFormat,
+    pub clear_color: wgpu::Color,
+
+    /// Radius in source pixels the shader samples around each output pixel
+    pub kernel_support: u32,
+}
+
+impl Default for GpuShadingConfig {
+    fn default() -> Self {
+        Self {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            backends: wgpu::Backends::all(),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            input_format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            output_format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            clear_color: wgpu::Color::BLACK,
+            kernel_support: 2,
+        }
+    }
+}
+
+/// Texture formats that map directly onto 8-bit RGBA images
+const SUPPORTED_FORMATS: [wgpu::TextureFormat; 4] = [
+    wgpu::TextureFormat::Rgba8Unorm,
+    wgpu::TextureFormat::Rgba8UnormSrgb,
+    wgpu::TextureFormat::Bgra8Unorm,
+    wgpu::TextureFormat::Bgra8UnormSrgb,
+];
+
+impl GpuShadingConfig {
+    /// Reads configuration from a JSON file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let config: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Writes configuration to a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+        Ok(())
+    }
+
+    /// Checks that configured texture formats are supported
+    pub fn validate(&self) -> Result<(), Error> {
+        for format in [self.input_format, self.output_format] {
+            if !SUPPORTED_FORMATS.contains(&format) {
+                return Err(Error::UnsupportedTextureFormat(format));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    pub fn force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    /// Sets both magnification and minification filters
+    pub fn filter(self, filter: wgpu::FilterMode) -> Self {
+        self.mag_filter(filter).min_filter(filter)
+    }
+
+    pub fn mag_filter(mut self, mag_filter: wgpu::FilterMode) -> Self {
+        self.mag_filter = mag_filter;
+        self
+    }
+
+    pub fn min_filter(mut self, min_filter: wgpu::FilterMode) -> Self {
+        self.min_filter = min_filter;
+        self
+    }
+
+    /// Sets both horizontal and vertical address modes
+    pub fn address_mode(mut self, address_mode: wgpu::AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self
+    }
+
+    pub fn input_format(mut self, input_format: wgpu::TextureFormat) -> Self {
+        self.input_format = input_format;
+        self
+    }
+
+    pub fn output_format(mut self, output_format: wgpu::TextureFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    /// Sets colour of the area not covered by the rendered image (e.g.
+    /// [`Scale::Pad`](crate::scale::Scale::Pad) borders)
+    pub fn clear_color(mut self, clear_color: wgpu::Color) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    /// Sets how far the shader samples from each output pixel, margin of [tiles](crate::tiled)
+    pub fn kernel_support(mut self, kernel_support: u32) -> Self {
+        self.kernel_support = kernel_support;
+        self
+    }
+
+    pub(crate) fn adapter_options(&self) -> wgpu::RequestAdapterOptions<'static, 'static> {
+        wgpu::RequestAdapterOptions {
+            power_preference: self.power_preference,
+            force_fallback_adapter: self.force_fallback_adapter,
+            compatible_surface: None,
+        }
+    }
+
+    pub(crate) fn sampler_descriptor(&self) -> wgpu::SamplerDescriptor<'static> {
+        wgpu::SamplerDescriptor {
+            label: Some("GPUSU_Sampler"),
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns `true` for formats that store blue in the first byte
+pub(crate) fn is_bgra(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    )
+}
+
+/// (De)serializes backend masks as comma-separated flag names, e.g. `"vulkan,metal"`, unknown names
+/// are an error
+mod backends_list {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        backends: &wgpu::Backends,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let names: Vec<String> = backends
+            .iter_names()
+            .map(|(name, _)| name.to_lowercase())
+            .collect();
+        serializer.serialize_str(&names.join(","))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<wgpu::Backends, D::Error> {
+        let list = String::deserialize(deserializer)?;
+        let mut backends = wgpu::Backends::empty();
+        for name in list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            backends |= wgpu::Backends::from_name(&name.to_uppercase())
+                .ok_or_else(|| D::Error::custom(format!("unknown backend {name:?}")))?;
+        }
+        Ok(backends)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_roundtrip() {
+        let config = GpuShadingConfig::default()
+            .backends(wgpu::Backends::VULKAN | wgpu::Backends::GL)
+            .filter(wgpu::FilterMode::Nearest)
+            .address_mode(wgpu::AddressMode::MirrorRepeat);
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""backends":"vulkan,gl""#));
+        assert_eq!(
+            serde_json::from_str::<GpuShadingConfig>(&json).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn backends_roundtrip() {
+        let config = GpuShadingConfig::default().backends(wgpu::Backends::all());
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains("browser_webgpu"), "{json}");
+        assert_eq!(
+            serde_json::from_str::<GpuShadingConfig>(&json).unwrap(),
+            config
+        );
+
+        let config: GpuShadingConfig =
+            serde_json::from_str(r#"{"backends": "Vulkan, BROWSER_WEBGPU"}"#).unwrap();
+        assert_eq!(
+            config.backends,
+            wgpu::Backends::VULKAN | wgpu::Backends::BROWSER_WEBGPU
+        );
+
+        let error = serde_json::from_str::<GpuShadingConfig>(r#"{"backends": "vulkan,vulcan"}"#);
+        assert!(error.unwrap_err().to_string().contains("\"vulcan\""));
+    }
+
+    #[test]
+    fn partial_json() {
+        let config: GpuShadingConfig = serde_json::from_str(
+            r#"{"force_fallback_adapter": true, "output_format": "rgba8unorm"}"#,
+        )
+        .unwrap();
+        assert!(config.force_fallback_adapter);
+        assert_eq!(config.output_format, wgpu::TextureFormat::Rgba8Unorm);
+        assert_eq!(config.mag_filter, wgpu::FilterMode::Linear);
+    }
+}
@@ -1,7 +1,24 @@
+pub mod cnn_model;
 pub mod cpu_algo;
+pub mod cpu_simd;
+pub mod edge_directed;
 pub mod error;
-pub mod upscaler;
-// pub mod onnx;
+pub mod gpu_cnn;
+pub mod gpu_compute;
 pub mod gpu_shading;
-
-mod gpu_shading_cfg;
\ No newline at end of file
+pub mod gpu_shading_cfg;
+pub mod mpv_hook;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod onnx_io;
+pub mod onnx_metadata;
+#[cfg(all(feature = "onnx", any(test, feature = "bench-helpers")))]
+pub mod onnx_test_model;
+pub mod pixel_art;
+pub mod raisr;
+pub mod scale;
+pub mod shader_graph;
+pub mod shader_library;
+pub mod tiled;
+pub mod tiling;
+pub mod upscaler;
@@ -1,3 +1,77 @@
-fn main() {
-    println!("Hello, world!");
+use std::{error::Error, path::PathBuf, process::ExitCode};
+
+use scale_benchmarks::raisr::FilterBank;
+
+const USAGE: &str = concat!(
+    "usage: scale-benchmarks train-raisr <image folder> <filter bank> ",
+    "[--factor N] [--patch N] [--angles N]"
+);
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("train-raisr") => train_raisr(&args[1..]),
+        _ => Err(USAGE.into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Learns a RAISR filter bank from every image in a folder
+fn train_raisr(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (mut positional, mut factor, mut patch, mut angles) = (Vec::new(), 2, 7, 24);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let option = match arg.as_str() {
+            "--factor" => &mut factor,
+            "--patch" => &mut patch,
+            "--angles" => &mut angles,
+            _ => {
+                positional.push(PathBuf::from(arg));
+                continue;
+            }
+        };
+        *option = args
+            .next()
+            .ok_or(USAGE)?
+            .parse()
+            .map_err(|err| format!("{arg}: {err}"))?;
+    }
+    let [folder, output] = positional.as_slice() else {
+        return Err(USAGE.into());
+    };
+
+    let mut paths = std::fs::read_dir(folder)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    let mut trainer = FilterBank::trainer(factor)?
+        .with_patch(patch)?
+        .with_angles(angles)?;
+    for path in paths.iter().filter(|path| path.is_file()) {
+        match image::open(path) {
+            Ok(image) => {
+                trainer.add_image(&image);
+                println!("trained on {}", path.display());
+            }
+            Err(err) => eprintln!("skipping {}: {err}", path.display()),
+        }
+    }
+    if trainer.images() == 0 {
+        return Err(format!("no images in {}", folder.display()).into());
+    }
+
+    let images = trainer.images();
+    trainer.finish().save(output)?;
+    println!(
+        "saved filters learned from {images} images to {}",
+        output.display()
+    );
+    Ok(())
 }
@@ -0,0 +1,771 @@
+//! Import of mpv/libplacebo user shaders (`//!HOOK` files) into [`ShaderGraph`]s
+//!
+//! Every hook block becomes a GLSL pass translated by naga. The image is RGB, so `MAIN` and its
+//! aliases (`NATIVE`, `PREKERNEL`, `OUTPUT`, ...) start as the loaded image and `LUMA` as its
+//! BT.601 luma, computed on first use. A final pass scales `MAIN` to the output and, if `LUMA` was
+//! hooked, swaps in the new luma.
+//!
+//! Supported directives are `HOOK`, `BIND`, `SAVE`, `DESC`, `WIDTH`, `HEIGHT`, `COMPONENTS`,
+//! `OFFSET` and `WHEN` for passes, `TEXTURE`, `SIZE`, `FORMAT`, `FILTER` and `BORDER` for embedded
+//! textures. `WHEN` and `OFFSET` are ignored as every pass runs, and all textures are read through
+//! the configured sampler. Pass textures always have four channels, those beyond `COMPONENTS` read
+//! as they would from a smaller texture: 0 for colours and 1 for alpha. Compute passes and hooks of
+//! planes other than luma aren't supported.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    error::Error,
+    shader_graph::{Extent, GraphTexture, PassShader, ShaderGraph, ShaderPass, ORIGINAL},
+};
+
+/// Returns `true` for sources with `//!HOOK` directives
+pub fn is_hook(source: &str) -> bool {
+    source
+        .lines()
+        .any(|line| line.trim_start().starts_with("//!HOOK"))
+}
+
+/// Plane a hook point refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Plane {
+    Main,
+    Luma,
+}
+
+fn plane(name: &str) -> Option<Plane> {
+    match name {
+        "MAIN" | "MAINPRESUB" | "NATIVE" | "RGB" | "LINEAR" | "SIGMOID" | "PREKERNEL"
+        | "POSTKERNEL" | "SCALED" | "OUTPUT" => Some(Plane::Main),
+        "LUMA" => Some(Plane::Luma),
+        _ => None,
+    }
+}
+
+/// Side of a texture as `source * loaded side + output * upscaled side + constant`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Side {
+    source: f32,
+    output: f32,
+    constant: f32,
+}
+
+impl Side {
+    const SOURCE: Self = Self::new(1.0, 0.0, 0.0);
+    const OUTPUT: Self = Self::new(0.0, 1.0, 0.0);
+
+    const fn new(source: f32, output: f32, constant: f32) -> Self {
+        Self {
+            source,
+            output,
+            constant,
+        }
+    }
+
+    fn extent(self) -> Option<Extent> {
+        match (self.source, self.output, self.constant) {
+            (source, 0.0, 0.0) if source > 0.0 => Some(Extent::Scaled(source)),
+            (0.0, 1.0, 0.0) => Some(Extent::Output),
+            _ => None,
+        }
+    }
+}
+
+/// Texture known under an mpv name
+#[derive(Debug, Clone)]
+struct Texture {
+    graph_name: String,
+    width: Side,
+    height: Side,
+}
+
+/// Directives and code of one block
+#[derive(Debug, Default)]
+struct Block<'a> {
+    line: usize,
+    directives: Vec<(usize, &'a str, &'a str)>,
+    body: Vec<&'a str>,
+    /// Line of the first body line
+    body_line: usize,
+}
+
+impl<'a> Block<'a> {
+    fn values(&self, directive: &str) -> impl Iterator<Item = (usize, &'a str)> + '_ {
+        let directive = directive.to_string();
+        self.directives
+            .iter()
+            .filter(move |(_, name, _)| *name == directive)
+            .map(|&(line, _, value)| (line, value))
+    }
+
+    fn value(&self, directive: &str) -> Option<(usize, &'a str)> {
+        self.values(directive).last()
+    }
+}
+
+fn invalid(line: usize, message: impl std::fmt::Display) -> Error {
+    Error::InvalidMpvHook(format!("line {line}: {message}"))
+}
+
+/// Splits the source into blocks, each starting with a run of directive lines
+fn blocks(source: &str) -> Vec<Block<'_>> {
+    let mut blocks: Vec<Block> = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        match line.trim().strip_prefix("//!") {
+            Some(directive) => {
+                if blocks.last().is_none_or(|block| !block.body.is_empty()) {
+                    blocks.push(Block {
+                        line: index + 1,
+                        ..Default::default()
+                    });
+                }
+                let (name, value) = directive
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((directive, ""));
+                blocks
+                    .last_mut()
+                    .unwrap()
+                    .directives
+                    .push((index + 1, name, value.trim()));
+            }
+            // Text before the first directive is a preamble, usually a licence
+            None => {
+                if let Some(block) = blocks.last_mut() {
+                    if block.body.is_empty() {
+                        block.body_line = index + 1;
+                    }
+                    block.body.push(line);
+                }
+            }
+        }
+    }
+    blocks
+}
+
+/// Converts an mpv user shader into a shader graph
+pub fn import(source: &str) -> Result<ShaderGraph, Error> {
+    let mut importer = Importer::default();
+    for block in blocks(source) {
+        if block.value("TEXTURE").is_some() {
+            importer.texture(&block)?;
+        } else if block.value("HOOK").is_some() {
+            importer.pass(&block)?;
+        } else {
+            return Err(invalid(
+                block.line,
+                "block neither hooks nor defines a texture",
+            ));
+        }
+    }
+    importer.finish()
+}
+
+#[derive(Debug, Default)]
+struct Importer {
+    graph: ShaderGraph,
+    passes: usize,
+    main: Option<Texture>,
+    luma: Option<Texture>,
+    luma_hooked: bool,
+    saved: HashMap<String, Texture>,
+}
+
+impl Importer {
+    fn texture(&mut self, block: &Block) -> Result<(), Error> {
+        for &(line, name, _) in &block.directives {
+            if !matches!(name, "TEXTURE" | "SIZE" | "FORMAT" | "FILTER" | "BORDER") {
+                return Err(invalid(
+                    line,
+                    format!("unsupported texture directive {name}"),
+                ));
+            }
+        }
+        let (line, name) = block.value("TEXTURE").unwrap();
+        let (size_line, size) = block
+            .value("SIZE")
+            .ok_or_else(|| invalid(line, "texture without SIZE"))?;
+        let size: Vec<u32> = size
+            .split_whitespace()
+            .map(|side| {
+                side.parse()
+                    .map_err(|_| invalid(size_line, format!("bad size {side:?}")))
+            })
+            .collect::<Result<_, _>>()?;
+        let (width, height) = match size[..] {
+            [width] => (width, 1),
+            [width, height] => (width, height),
+            _ => return Err(invalid(size_line, "only 1D and 2D textures are supported")),
+        };
+
+        let (format_line, format) = block
+            .value("FORMAT")
+            .ok_or_else(|| invalid(line, "texture without FORMAT"))?;
+        let format = match format {
+            "r8" => wgpu::TextureFormat::R8Unorm,
+            "rg8" => wgpu::TextureFormat::Rg8Unorm,
+            "rgba8" => wgpu::TextureFormat::Rgba8Unorm,
+            "r16f" | "r16hf" => wgpu::TextureFormat::R16Float,
+            "rg16f" | "rg16hf" => wgpu::TextureFormat::Rg16Float,
+            "rgba16f" | "rgba16hf" => wgpu::TextureFormat::Rgba16Float,
+            _ => {
+                return Err(invalid(
+                    format_line,
+                    format!("unsupported texture format {format}"),
+                ))
+            }
+        };
+
+        let hex: Vec<u8> = block
+            .body
+            .iter()
+            .flat_map(|line| line.bytes())
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect();
+        let data = hex
+            .chunks(2)
+            .map(|pair| {
+                std::str::from_utf8(pair)
+                    .ok()
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or_else(|| invalid(line, format!("texture {name} has malformed data")))
+            })
+            .collect::<Result<_, _>>()?;
+
+        self.saved.insert(
+            name.to_string(),
+            Texture {
+                graph_name: name.to_string(),
+                width: Side::new(0.0, 0.0, width as f32),
+                height: Side::new(0.0, 0.0, height as f32),
+            },
+        );
+        self.graph = std::mem::take(&mut self.graph).with_texture(GraphTexture {
+            name: name.to_string(),
+            width,
+            height,
+            format,
+            data,
+        });
+        Ok(())
+    }
+
+    /// Current texture of a plane, adding the luma pass when it's first needed
+    fn plane(&mut self, plane: Plane) -> Texture {
+        let original = || Texture {
+            graph_name: ORIGINAL.to_string(),
+            width: Side::SOURCE,
+            height: Side::SOURCE,
+        };
+        match plane {
+            Plane::Main => self.main.get_or_insert_with(original).clone(),
+            Plane::Luma => {
+                if self.luma.is_none() {
+                    let shader = PassShader::Wgsl(include_str!("mpv_luma.wgsl").into());
+                    let pass = ShaderPass::new("LUMA", shader)
+                        .with_inputs([ORIGINAL])
+                        .with_size(Extent::Source, Extent::Source);
+                    self.graph = std::mem::take(&mut self.graph).with_pass(pass);
+                    self.luma = Some(Texture {
+                        graph_name: "LUMA".to_string(),
+                        ..original()
+                    });
+                }
+                self.luma.clone().unwrap()
+            }
+        }
+    }
+
+    /// Texture an mpv name refers to from a pass hooking `hooked`
+    fn lookup(&mut self, line: usize, name: &str, hooked: &Texture) -> Result<Texture, Error> {
+        if name == "HOOKED" {
+            return Ok(hooked.clone());
+        }
+        if let Some(texture) = self.saved.get(name) {
+            return Ok(texture.clone());
+        }
+        match plane(name) {
+            Some(plane) => Ok(self.plane(plane)),
+            None => Err(invalid(line, format!("unknown texture {name}"))),
+        }
+    }
+
+    /// Evaluates a size expression in reverse Polish notation
+    fn side(&mut self, line: usize, expression: &str, hooked: &Texture) -> Result<Side, Error> {
+        let mut stack: Vec<Side> = Vec::new();
+        for token in expression.split_whitespace() {
+            let side = if let Ok(number) = token.parse::<f32>() {
+                Side::new(0.0, 0.0, number)
+            } else if let Some((name, side)) = token.rsplit_once('.') {
+                let (width, height) = match name {
+                    "OUTPUT" => (Side::OUTPUT, Side::OUTPUT),
+                    "NATIVE" => (Side::SOURCE, Side::SOURCE),
+                    _ => {
+                        let texture = self.lookup(line, name, hooked)?;
+                        (texture.width, texture.height)
+                    }
+                };
+                match side {
+                    "w" | "width" => width,
+                    "h" | "height" => height,
+                    _ => return Err(invalid(line, format!("unknown size {token}"))),
+                }
+            } else {
+                let (Some(b), Some(a)) = (stack.pop(), stack.pop()) else {
+                    return Err(invalid(line, format!("{token} needs two operands")));
+                };
+                let scale = |side: Side, factor: f32| {
+                    Side::new(
+                        side.source * factor,
+                        side.output * factor,
+                        side.constant * factor,
+                    )
+                };
+                let constant = |side: Side| {
+                    (side.source == 0.0 && side.output == 0.0).then_some(side.constant)
+                };
+                match token {
+                    "+" => Side::new(
+                        a.source + b.source,
+                        a.output + b.output,
+                        a.constant + b.constant,
+                    ),
+                    "-" => Side::new(
+                        a.source - b.source,
+                        a.output - b.output,
+                        a.constant - b.constant,
+                    ),
+                    "*" => match (constant(a), constant(b)) {
+                        (_, Some(factor)) => scale(a, factor),
+                        (Some(factor), _) => scale(b, factor),
+                        _ => return Err(invalid(line, "sizes can only be multiplied by numbers")),
+                    },
+                    "/" => match constant(b) {
+                        Some(divisor) if divisor != 0.0 => scale(a, 1.0 / divisor),
+                        _ => return Err(invalid(line, "sizes can only be divided by numbers")),
+                    },
+                    _ => return Err(invalid(line, format!("unsupported operator {token}"))),
+                }
+            };
+            stack.push(side);
+        }
+        match stack[..] {
+            [side] => Ok(side),
+            _ => Err(invalid(line, format!("malformed size {expression:?}"))),
+        }
+    }
+
+    fn pass(&mut self, block: &Block) -> Result<(), Error> {
+        const DIRECTIVES: [&str; 9] = [
+            "HOOK",
+            "BIND",
+            "SAVE",
+            "DESC",
+            "WIDTH",
+            "HEIGHT",
+            "COMPONENTS",
+            "OFFSET",
+            "WHEN",
+        ];
+        if let Some(&(line, name, _)) = block
+            .directives
+            .iter()
+            .find(|(_, name, _)| !DIRECTIVES.contains(name))
+        {
+            return Err(invalid(line, format!("unsupported pass directive {name}")));
+        }
+        let (hook, hook_plane) = block
+            .values("HOOK")
+            .find_map(|(_, name)| plane(name).map(|plane| (name, plane)))
+            .ok_or_else(|| invalid(block.line, "pass hooks no supported plane"))?;
+        let hooked = self.plane(hook_plane);
+
+        let mut width = hooked.width;
+        let mut height = hooked.height;
+        if let Some((line, expression)) = block.value("WIDTH") {
+            width = self.side(line, expression, &hooked)?;
+        }
+        if let Some((line, expression)) = block.value("HEIGHT") {
+            height = self.side(line, expression, &hooked)?;
+        }
+        let size_error = || {
+            invalid(
+                block.line,
+                "pass size must be a multiple of the source or the output size",
+            )
+        };
+        let extents = (
+            width.extent().ok_or_else(size_error)?,
+            height.extent().ok_or_else(size_error)?,
+        );
+        let components = match block.value("COMPONENTS") {
+            Some((line, count)) => match count.parse() {
+                Ok(count @ 1..=4) => count,
+                _ => return Err(invalid(line, format!("bad component count {count:?}"))),
+            },
+            None => 4,
+        };
+
+        // HOOKED is always the first input, other names get their own bindings
+        let mut inputs = vec![hooked.graph_name.clone()];
+        let mut glsl = String::from("#version 450\n");
+        let bind = |glsl: &mut String, name: &str, binding: usize| {
+            let texture = format!("mpv_texture_{binding}");
+            let _ = write!(
+                glsl,
+                "#define {name}_raw sampler2D({texture}, r_sampler)\n\
+                 #define {name}_pos coords\n\
+                 #define {name}_size vec2(textureSize({name}_raw, 0))\n\
+                 #define {name}_pt (vec2(1.0) / {name}_size)\n\
+                 #define {name}_mul 1.0\n\
+                 #define {name}_off vec2(0.0)\n\
+                 #define {name}_rot mat2(1.0, 0.0, 0.0, 1.0)\n\
+                 #define {name}_tex(pos) ({name}_mul * texture({name}_raw, pos))\n\
+                 #define {name}_texOff(off) {name}_tex({name}_pos + {name}_pt * vec2(off))\n"
+            );
+        };
+        glsl.push_str("layout(set = 0, binding = 0) uniform texture2D mpv_texture_0;\n");
+        glsl.push_str("layout(set = 0, binding = 1) uniform sampler r_sampler;\n");
+        bind(&mut glsl, "HOOKED", 0);
+        for (line, name) in block.values("BIND") {
+            if name == "HOOKED" {
+                continue;
+            }
+            if name == hook {
+                bind(&mut glsl, name, 0);
+                continue;
+            }
+            let texture = self.lookup(line, name, &hooked)?;
+            let binding = inputs.len() + 1;
+            let _ = writeln!(
+                glsl,
+                "layout(set = 0, binding = {binding}) uniform texture2D mpv_texture_{binding};"
+            );
+            bind(&mut glsl, name, binding);
+            inputs.push(texture.graph_name);
+        }
+        glsl.push_str(
+            "layout(location = 0) in vec2 coords;\nlayout(location = 0) out vec4 mpv_color;\n",
+        );
+        glsl.push_str("#define frame 0\n#define random 0.5\n");
+        // Translation errors point into the hook file
+        let _ = writeln!(glsl, "#line {}", block.body_line);
+        for line in &block.body {
+            glsl.push_str(line);
+            glsl.push('\n');
+        }
+        glsl.push_str(match components {
+            1 => "void main() {\n    mpv_color = vec4(hook().x, 0.0, 0.0, 1.0);\n}\n",
+            2 => "void main() {\n    mpv_color = vec4(hook().xy, 0.0, 1.0);\n}\n",
+            3 => "void main() {\n    mpv_color = vec4(hook().xyz, 1.0);\n}\n",
+            _ => "void main() {\n    mpv_color = hook();\n}\n",
+        });
+
+        let name = format!("pass{}", self.passes);
+        self.passes += 1;
+        let pass = ShaderPass::new(name.clone(), PassShader::Glsl(glsl.into()))
+            .with_inputs(inputs)
+            .with_size(extents.0, extents.1);
+        self.graph = std::mem::take(&mut self.graph).with_pass(pass);
+
+        let texture = Texture {
+            graph_name: name,
+            width,
+            height,
+        };
+        match block.value("SAVE").map(|(_, name)| name) {
+            None | Some("HOOKED") => self.replace(hook_plane, texture),
+            Some(save) => match plane(save) {
+                Some(plane) => self.replace(plane, texture),
+                None => {
+                    self.saved.insert(save.to_string(), texture);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn replace(&mut self, plane: Plane, texture: Texture) {
+        match plane {
+            Plane::Main => self.main = Some(texture),
+            Plane::Luma => {
+                self.luma = Some(texture);
+                self.luma_hooked = true;
+            }
+        }
+    }
+
+    /// Adds the pass rendering `MAIN` and the hooked luma into the output
+    fn finish(mut self) -> Result<ShaderGraph, Error> {
+        if self.passes == 0 {
+            return Err(Error::InvalidMpvHook("no passes".to_string()));
+        }
+        let main = self.plane(Plane::Main).graph_name;
+        let luma = if self.luma_hooked {
+            self.plane(Plane::Luma).graph_name
+        } else {
+            main.clone()
+        };
+        let output = ShaderPass::new(
+            "OUTPUT",
+            PassShader::Wgsl(include_str!("mpv_output.wgsl").into()),
+        )
+        .with_inputs([main, luma])
+        .with_constant("with_luma", self.luma_hooked as u8 as f64);
+        let graph = self.graph.with_pass(output);
+        graph.validate()?;
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, RgbImage};
+
+    use super::*;
+    use crate::{
+        gpu_shading::GPUShadingUpscaler, gpu_shading_cfg::GpuShadingConfig, upscaler::UpscaleImage,
+    };
+
+    const EXAMPLE: &str = include_str!("../shaders/mpv_unsharp_luma.glsl");
+
+    fn unorm() -> GpuShadingConfig {
+        GpuShadingConfig::default()
+            .force_fallback_adapter(true)
+            .input_format(wgpu::TextureFormat::Rgba8Unorm)
+            .output_format(wgpu::TextureFormat::Rgba8Unorm)
+            .filter(wgpu::FilterMode::Nearest)
+    }
+
+    #[test]
+    fn example_graph() {
+        assert!(is_hook(EXAMPLE));
+        let graph = import(EXAMPLE).unwrap();
+        let passes = graph.passes();
+        let names: Vec<_> = passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(names, ["LUMA", "pass0", "pass1", "OUTPUT"]);
+
+        assert_eq!(passes[1].inputs.as_deref(), Some(&["LUMA".to_string()][..]));
+        assert_eq!(
+            (passes[1].width, passes[1].height),
+            (Extent::Scaled(1.0), Extent::Scaled(1.0))
+        );
+        assert_eq!(
+            passes[2].inputs.as_deref(),
+            Some(&["LUMA".to_string(), "pass0".to_string()][..])
+        );
+        assert_eq!(
+            (passes[2].width, passes[2].height),
+            (Extent::Scaled(2.0), Extent::Scaled(2.0))
+        );
+        assert_eq!(
+            passes[3].inputs.as_deref(),
+            Some(&[ORIGINAL.to_string(), "pass1".to_string()][..])
+        );
+        assert_eq!(passes[3].constants["with_luma"], 1.0);
+    }
+
+    #[test]
+    fn sizes_and_textures() {
+        let source = "//!TEXTURE LUT\n//!SIZE 2 1\n//!FORMAT rg8\n//!FILTER NEAREST\n00ff 8040\n\n\
+                      //!HOOK MAIN\n//!BIND HOOKED\n//!BIND LUT\n\
+                      //!WIDTH OUTPUT.w\n//!HEIGHT HOOKED.h 3 * 2 /\n\
+                      vec4 hook() {\n\
+                          return HOOKED_tex(HOOKED_pos) + LUT_tex(vec2(0.25, 0.5));\n\
+                      }\n";
+        let graph = import(source).unwrap();
+        assert_eq!(graph.textures()[0].data, [0x00, 0xff, 0x80, 0x40]);
+        assert_eq!(graph.textures()[0].format, wgpu::TextureFormat::Rg8Unorm);
+        let pass = &graph.passes()[0];
+        assert_eq!(
+            pass.inputs.as_deref(),
+            Some(&[ORIGINAL.to_string(), "LUT".to_string()][..])
+        );
+        assert_eq!(
+            (pass.width, pass.height),
+            (Extent::Output, Extent::Scaled(1.5))
+        );
+        assert_eq!(graph.passes()[1].constants["with_luma"], 0.0);
+    }
+
+    #[test]
+    fn rejected_hooks() {
+        let cases = [
+            (
+                "//!HOOK CHROMA\nvec4 hook() { return vec4(0.0); }",
+                "line 1:",
+            ),
+            (
+                "//!HOOK MAIN\n//!COMPUTE 8 8\nvec4 hook() { return vec4(0.0); }",
+                "line 2:",
+            ),
+            (
+                "//!HOOK MAIN\n//!WIDTH HOOKED.w HOOKED.w *\nvec4 hook() { return vec4(0.0); }",
+                "line 2:",
+            ),
+            (
+                "//!HOOK MAIN\n//!WIDTH HOOKED.w 2 +\nvec4 hook() { return vec4(0.0); }",
+                "line 1:",
+            ),
+            (
+                "//!HOOK MAIN\n//!BIND MISSING\nvec4 hook() { return vec4(0.0); }",
+                "line 2:",
+            ),
+            ("//!TEXTURE T\n//!SIZE 2\n//!FORMAT r8\n0g00", "line 1:"),
+            (
+                "//!HOOK MAIN\n//!COMPONENTS 5\nvec4 hook() { return vec4(0.0); }",
+                "line 2:",
+            ),
+        ];
+        for (source, line) in cases {
+            match import(source) {
+                Err(Error::InvalidMpvHook(message)) => {
+                    assert!(message.starts_with(line), "{source}: {message}")
+                }
+                other => panic!("{source}: {other:?}"),
+            }
+        }
+    }
+
+    fn grey(value: u8) -> DynamicImage {
+        RgbImage::from_pixel(9, 7, image::Rgb([value; 3])).into()
+    }
+
+    #[test]
+    fn luma_hook_keeps_chroma() {
+        let source =
+            "//!HOOK LUMA\n//!BIND HOOKED\n//!WIDTH HOOKED.w 2 *\n//!HEIGHT HOOKED.h 2 *\n\
+                      vec4 hook() {\n\
+                          return vec4(1.0 - HOOKED_tex(HOOKED_pos).x, 0.0, 0.0, 1.0);\n\
+                      }\n";
+        let image = RgbImage::from_pixel(9, 7, image::Rgb([120, 100, 80])).into();
+        let scaler = GPUShadingUpscaler::from_image_with_config(
+            import(source).unwrap(),
+            &image,
+            2.0,
+            unorm(),
+        )
+        .unwrap();
+        let output = scaler.upscale().unwrap().to_rgb8();
+        assert_eq!(output.dimensions(), (18, 14));
+
+        // Luma of the input is 103.7, the hook inverts it to 151.3 and moves every channel by
+        // the difference
+        for pixel in output.pixels() {
+            let close = pixel
+                .0
+                .iter()
+                .zip([168, 148, 128])
+                .all(|(&a, e)| a.abs_diff(e) <= 1);
+            assert!(close, "{:?}", pixel.0);
+        }
+    }
+
+    #[test]
+    fn main_hook_with_texture() {
+        let source = "//!TEXTURE LUT\n//!SIZE 2 1\n//!FORMAT r8\n3366\n\
+                      //!HOOK MAIN\n//!BIND HOOKED\n//!BIND LUT\n\
+                      vec4 hook() {\n\
+                          return HOOKED_tex(HOOKED_pos) + LUT_tex(vec2(0.75, 0.5)).x;\n\
+                      }\n";
+        let graph = import(source).unwrap();
+        let scaler =
+            GPUShadingUpscaler::from_image_with_config(graph, &grey(10), 3.0, unorm()).unwrap();
+        for pixel in scaler.upscale().unwrap().to_rgb8().pixels() {
+            assert_eq!(pixel.0, [10 + 0x66; 3]);
+        }
+    }
+
+    #[test]
+    fn example_sharpens_luma() {
+        let image =
+            RgbImage::from_fn(16, 16, |x, _| image::Rgb([if x < 8 { 40 } else { 200 }; 3])).into();
+        let scaler = GPUShadingUpscaler::from_image_with_config(
+            "shaders/mpv_unsharp_luma.glsl",
+            &image,
+            2.0,
+            unorm(),
+        )
+        .unwrap();
+        let output = scaler.upscale().unwrap().to_rgb8();
+        assert_eq!(output.dimensions(), (32, 32));
+        let row: Vec<_> = (0..32).map(|x| output.get_pixel(x, 16).0[0]).collect();
+
+        // Flat areas pass through, the edge over- and undershoots
+        assert_eq!((row[0], row[31]), (40, 200));
+        assert!(row[15] < 40 && row[16] > 200, "{row:?}");
+    }
+
+    #[test]
+    fn components_fill_missing_channels() {
+        let source = "//!HOOK MAIN\n//!BIND HOOKED\n//!COMPONENTS 1\n\
+                      vec4 hook() {\n    return vec4(0.2, 0.4, 0.6, 0.0);\n}\n";
+        let scaler = GPUShadingUpscaler::from_image_with_config(
+            import(source).unwrap(),
+            &grey(0),
+            2.0,
+            unorm(),
+        )
+        .unwrap();
+        for pixel in scaler.upscale().unwrap().to_rgba8().pixels() {
+            assert_eq!(pixel.0, [51, 0, 0, 255]);
+        }
+    }
+
+    /// Anime4K's highlight clamp, which only ever darkens pixels brighter than their surroundings
+    #[test]
+    fn anime4k_clamp_highlights() {
+        let source = include_str!("../testdata/mpv/Anime4K_Clamp_Highlights.glsl");
+        let graph = import(source).unwrap();
+        let inputs: Vec<_> = graph
+            .passes()
+            .iter()
+            .map(|pass| pass.inputs.clone().unwrap_or_default())
+            .collect();
+        assert_eq!(
+            inputs[1..3],
+            [
+                [ORIGINAL.to_string(), "pass0".to_string()],
+                [ORIGINAL.to_string(), "pass1".to_string()]
+            ]
+        );
+
+        // Every pixel lies within its own neighbourhood, so the clamp keeps the image
+        let image: DynamicImage = RgbImage::from_fn(9, 7, |x, y| {
+            image::Rgb([(x * 29) as u8, (y * 37) as u8, ((x + y) * 13) as u8])
+        })
+        .into();
+        let scaler =
+            GPUShadingUpscaler::from_image_with_config(graph, &image, 2.0, unorm()).unwrap();
+        let output = scaler.upscale().unwrap().to_rgb8();
+        for (x, y, pixel) in output.enumerate_pixels() {
+            let expected = image.to_rgb8().get_pixel(x / 2, y / 2).0;
+            let close = pixel
+                .0
+                .iter()
+                .zip(expected)
+                .all(|(&a, e)| a.abs_diff(e) <= 1);
+            assert!(close, "at {x}, {y}: {:?} against {expected:?}", pixel.0);
+        }
+    }
+
+    #[test]
+    fn glsl_errors_are_returned() {
+        let source =
+            "//!HOOK MAIN\n//!BIND HOOKED\n\nvec4 hook() {\n    return undefined_name;\n}\n";
+        let result = GPUShadingUpscaler::from_image_with_config(
+            import(source).unwrap(),
+            &grey(0),
+            2.0,
+            unorm(),
+        );
+        match result {
+            Err(Error::ShaderTranslation {
+                line, diagnostic, ..
+            }) => {
+                assert!(diagnostic.contains("undefined_name"), "{diagnostic}");
+                assert_eq!(line, 5);
+            }
+            other => panic!("{other:?}"),
+        }
+    }
+}
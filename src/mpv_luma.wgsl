@@ -0,0 +1,9 @@
+// Luma plane of an RGB image for mpv shaders hooking LUMA, with BT.601 full range weights
+
+@group(0) @binding(0) var r_color: texture_2d<f32>;
+@group(0) @binding(1) var r_sampler: sampler;
+
+@fragment fn main(@location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(r_color, r_sampler, coords);
+    return vec4<f32>(dot(color.rgb, vec3<f32>(0.299, 0.587, 0.114)), 0.0, 0.0, color.a);
+}
@@ -0,0 +1,18 @@
+// Final pass of imported mpv shaders, scales MAIN to the output and optionally swaps in the hooked luma
+//
+// Replacing luma keeps both chroma differences, which adds the same offset to every channel.
+
+@group(0) @binding(0) var main_plane: texture_2d<f32>;
+@group(0) @binding(1) var r_sampler: sampler;
+@group(0) @binding(2) var luma_plane: texture_2d<f32>;
+
+override with_luma: bool = false;
+
+@fragment fn main(@location(0) coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(main_plane, r_sampler, coords);
+    if (!with_luma) {
+        return color;
+    }
+    let luma = textureSample(luma_plane, r_sampler, coords).x;
+    return vec4<f32>(color.rgb + (luma - dot(color.rgb, vec3<f32>(0.299, 0.587, 0.114))), color.a);
+}
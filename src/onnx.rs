@@ -1,95 +1,384 @@
-use std::{fmt::Debug, path::Path};
+use std::{
+    fmt::Debug,
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
 
-use image::{DynamicImage, GenericImageView, RgbImage};
+use image::{imageops::FilterType, ColorType, DynamicImage, GenericImageView, RgbImage};
 use ndarray::Array4;
-use ort::{Session, ValueType};
+use ort::{
+    session::Session,
+    value::{TensorRef, ValueType},
+};
 
-use crate::{error::Error, upscaler::UpscaleSquareImage};
+use crate::{
+    error::Error,
+    onnx_io::ModelIO,
+    onnx_metadata::ModelMetadata,
+    scale::{Scale, ScalePlan},
+    tiling::{pad_edges, FeatherBlender, TileGrid},
+    upscaler::UpscaleImage,
+};
 
+/// Spatial dimensions of a model tensor, `None` for dynamic axes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ModelDims {
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+impl ModelDims {
+    /// Side used for dynamic axes when the model has to be run on some input
+    const PROBE_SIDE: u32 = 16;
+
+    fn fixed(&self) -> Option<(u32, u32)> {
+        Some((self.width?, self.height?))
+    }
+
+    /// Smallest valid input dimensions no less than [`ModelDims::PROBE_SIDE`]
+    fn probe(&self, multiple: Option<u32>) -> (u32, u32) {
+        let side = Self::PROBE_SIDE.next_multiple_of(multiple.unwrap_or(1).max(1));
+        (self.width.unwrap_or(side), self.height.unwrap_or(side))
+    }
+}
+
+/// Upscaler running ONNX models on CPU
+///
+/// Models may have fixed or dynamic height and width. For dynamic ones
+/// the factor is taken from [`ModelMetadata`] or found by a probe inference,
+/// input is edge-padded to `pad_to_multiple` if metadata requires it.
+///
+/// Images that don't match fixed model dimensions, or exceed the tile size of
+/// a dynamic model, are split into overlapping tiles which are feather-blended
+/// after inference. Models with a dynamic batch axis get tiles in batches.
 #[derive(Debug)]
 pub struct ONNXNeuralUpscaler {
-    session: Session,
-    original_res: u32,
-    target_res: u32,
-    image: Array4<f32>,
+    session: Mutex<Session>,
+    input_name: String,
+    input_dims: ModelDims,
+    dynamic_batch: bool,
+    tile_size: Option<u32>,
+    tile_overlap: u32,
+    batch_size: usize,
+    loaded_dims: (u32, u32),
+    grid: TileGrid,
+    name: String,
+    metadata: ModelMetadata,
+    io: ModelIO,
+    original_dims: (u32, u32),
+    plan: ScalePlan,
+    batches: Vec<Array4<f32>>,
     upscaled_image: DynamicImage,
-    scale_factor: f32,
+    model_factor: f32,
+    scale: Scale,
 }
 
 impl ONNXNeuralUpscaler {
+    /// Overlap of neighbouring tiles in input pixels unless set otherwise
+    pub const DEFAULT_TILE_OVERLAP: u32 = 16;
+
+    /// Tiles per inference for models with a dynamic batch axis unless set otherwise
+    pub const DEFAULT_BATCH_SIZE: usize = 4;
+
+    /// Loads a model, tensor encoding is taken from [`ModelMetadata`]
+    ///
+    /// Models without metadata are expected to take NCHW RGB input in `[0, 1]` range.
     pub fn from_model(filepath: impl AsRef<Path>) -> Result<Self, Error> {
-        fn validated_model_io_dims(iotype: &ValueType) -> Result<(u32, u32), Error> {
-            let dims = match &iotype {
-                ort::ValueType::Tensor { ty: _, dimensions } => dimensions.to_owned(),
-                _ => return Err(Error::IncompatibleModel),
-            };
+        Self::build(filepath.as_ref(), None)
+    }
 
-            if dims[0] != 1 || dims[1] != 3 || dims.len() != 4 || dims[2] < 2 || dims[3] < 2 {
-                return Err(Error::IncompatibleModel);
-            }
+    /// Loads a model with custom tensor layout, channel order and normalization
+    pub fn from_model_with_io(filepath: impl AsRef<Path>, io: ModelIO) -> Result<Self, Error> {
+        Self::build(filepath.as_ref(), Some(io))
+    }
 
-            Ok((dims[2] as u32, dims[3] as u32))
+    fn read_metadata(session: &Session, filepath: &Path) -> Result<ModelMetadata, Error> {
+        let embedded = session.metadata()?;
+        let mut props = Vec::new();
+        for key in embedded.custom_keys()? {
+            if let Some(value) = embedded.custom(&key)? {
+                props.push((key, value));
+            }
         }
 
-        let session = Session::builder()?.commit_from_file(filepath)?;
-        let (x_in, y_in) = validated_model_io_dims(&session.inputs[0].input_type)?;
-        let (x_out, y_out) = validated_model_io_dims(&session.outputs[0].output_type)?;
+        let metadata = ModelMetadata::from_props(props)?;
+        Ok(match ModelMetadata::from_sidecar(filepath)? {
+            Some(sidecar) => metadata.merge(sidecar),
+            None => metadata,
+        })
+    }
+
+    fn build(filepath: &Path, io: Option<ModelIO>) -> Result<Self, Error> {
+        let mut session = Session::builder()?.commit_from_file(filepath)?;
+        let metadata = Self::read_metadata(&session, filepath)?;
+        let io = io.unwrap_or_else(|| metadata.io());
+
+        let validated_model_io_dims =
+            |iotype: &ValueType, what: &str| -> Result<ModelDims, Error> {
+                let dims = match &iotype {
+                    ValueType::Tensor { shape, .. } => shape.to_vec(),
+                    _ => return Err(Error::IncompatibleModel(format!("{what} is not a tensor"))),
+                };
+
+                let Some((width, height, channels)) = io.dimensions(&dims) else {
+                    return Err(Error::IncompatibleModel(format!(
+                        "{what} has shape {dims:?}, expected 4 dimensions"
+                    )));
+                };
+                if !matches!(dims[0], -1 | 1) || channels != 3 {
+                    return Err(Error::IncompatibleModel(format!(
+                        "{what} has shape {dims:?}, expected a single 3-channel {:?} image",
+                        io.layout
+                    )));
+                }
+
+                // Dynamic axes are exported as -1
+                let axis = |side: i64| (side > 0).then_some(side as u32);
+                Ok(ModelDims {
+                    width: axis(width),
+                    height: axis(height),
+                })
+            };
+
+        let input_dims = validated_model_io_dims(&session.inputs[0].input_type, "input")?;
+        let dynamic_batch = matches!(
+            &session.inputs[0].input_type,
+            ValueType::Tensor { shape, .. } if shape[0] == -1
+        );
+        let output_dims = validated_model_io_dims(&session.outputs[0].output_type, "output")?;
 
-        if x_in != y_in || x_out != y_out {
-            return Err(Error::UnsquareModelIO);
+        let model_factor = match (input_dims.fixed(), output_dims.fixed()) {
+            (Some((x_in, y_in)), Some((x_out, y_out))) => {
+                if x_out * y_in != y_out * x_in {
+                    return Err(Error::AnamorphicModelIO);
+                }
+                x_out as f32 / x_in as f32
+            }
+            _ => match metadata.scale {
+                Some(scale) => scale as f32,
+                None => Self::probe_factor(
+                    &mut session,
+                    &io,
+                    input_dims.probe(metadata.pad_to_multiple),
+                )?,
+            },
+        };
+        if metadata
+            .scale
+            .is_some_and(|scale| scale as f32 != model_factor)
+        {
+            return Err(Error::IncompatibleModel(format!(
+                "model upscales by {model_factor}, metadata states {}",
+                metadata.scale.unwrap_or_default()
+            )));
         }
 
-        let upscaled_image = RgbImage::new(x_out, x_out);
-        let scale_factor = x_out as f32 / x_in as f32;
+        let (x_in, y_in) = input_dims.probe(metadata.pad_to_multiple);
+        let scale = Scale::Uniform(model_factor);
+        let plan = scale.plan((x_in, y_in));
+        let upscaled_image = RgbImage::new(plan.canvas.0, plan.canvas.1);
+        let grid = TileGrid::new((x_in, y_in), (x_in, y_in), 0);
+
+        let name = match &metadata.name {
+            Some(name) => name.clone(),
+            None => filepath
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
 
         Ok(Self {
-            session,
-            original_res: x_in,
-            target_res: x_out,
-            image: Array4::zeros([1, 3, x_in as usize, x_in as usize]),
+            input_name: session.inputs[0].name.clone(),
+            name,
+            tile_size: metadata.tile_size,
+            metadata,
+            session: Mutex::new(session),
+            input_dims,
+            dynamic_batch,
+            tile_overlap: Self::DEFAULT_TILE_OVERLAP,
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            original_dims: (x_in, y_in),
+            loaded_dims: (x_in, y_in),
+            grid,
+            plan,
+            batches: vec![Array4::zeros(io.shape((x_in, y_in)))],
+            io,
             upscaled_image: upscaled_image.into(),
-            scale_factor,
+            model_factor,
+            scale,
         })
     }
+
+    /// Runs the model on a blank input and measures its output
+    fn probe_factor(session: &mut Session, io: &ModelIO, dims: (u32, u32)) -> Result<f32, Error> {
+        let input = Array4::<f32>::zeros(io.shape(dims));
+        let input_name = session.inputs[0].name.clone();
+        let outputs =
+            session.run(ort::inputs![input_name => TensorRef::from_array_view(&input)?])?;
+        let output = outputs[0].try_extract_array::<f32>()?;
+
+        match io.dimensions(output.shape()) {
+            Some((width, height, 3)) => {
+                if width as u64 * dims.1 as u64 != height as u64 * dims.0 as u64 {
+                    return Err(Error::AnamorphicModelIO);
+                }
+                Ok(width as f32 / dims.0 as f32)
+            }
+            _ => Err(Error::IncompatibleModel(format!(
+                "output has shape {:?}, expected a 3-channel image",
+                output.shape()
+            ))),
+        }
+    }
+
+    /// Returns display name of the model, file name if metadata has none
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns properties read from the model and its sidecar
+    pub fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+
+    /// Returns the factor the model itself upscales by
+    pub fn model_factor(&self) -> f32 {
+        self.model_factor
+    }
+
+    /// Resamples model output to match `scale` instead of the model's own factor
+    pub fn with_scale(mut self, scale: impl Into<Scale>) -> Self {
+        self.scale = scale.into();
+        self.plan = self.scale.plan(self.original_dims);
+        self
+    }
+
+    /// Splits input larger than `side` along dynamic axes into tiles, overrides metadata
+    ///
+    /// Takes effect on next [`UpscaleImage::load`].
+    pub fn with_tile_size(mut self, side: u32) -> Self {
+        self.tile_size = Some(side.max(1));
+        self
+    }
+
+    /// Sets overlap of neighbouring tiles in input pixels, capped at half a tile
+    ///
+    /// Takes effect on next [`UpscaleImage::load`].
+    pub fn with_tile_overlap(mut self, overlap: u32) -> Self {
+        self.tile_overlap = overlap;
+        self
+    }
+
+    /// Sets how many tiles go into a single inference, ignored without a dynamic batch axis
+    ///
+    /// Takes effect on next [`UpscaleImage::load`].
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns the number of tiles the loaded image is split into
+    pub fn tiles(&self) -> usize {
+        self.grid.len()
+    }
+
+    /// Model input dimensions for an image of given size
+    fn tile_dims(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let multiple = self.metadata.pad_to_multiple.unwrap_or(1).max(1);
+        let side = |fixed: Option<u32>, length: u32| {
+            fixed.unwrap_or_else(|| {
+                self.tile_size
+                    .map_or(length, |tile| tile.min(length))
+                    .next_multiple_of(multiple)
+            })
+        };
+        (
+            side(self.input_dims.width, width),
+            side(self.input_dims.height, height),
+        )
+    }
 }
 
-impl UpscaleSquareImage for ONNXNeuralUpscaler {
+impl UpscaleImage for ONNXNeuralUpscaler {
     type Error = Error;
 
     fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error> {
-        if image.width() != image.height() {
-            return Err(Error::UnsquareImage);
-        }
+        let original_dims = image.dimensions();
+        let plan = self.scale.plan(original_dims);
+        let image = plan.crop_image(image);
+        let (width, height) = image.dimensions();
 
-        let side = image.width();
-        self.image = Array4::zeros([1, 3, side as usize, side as usize]);
-        for (x, y, color) in image.pixels() {
-            let (x, y) = (x as usize, y as usize);
-            self.image[[0, 0, x, y]] = color[2] as f32;
-            self.image[[0, 1, x, y]] = color[1] as f32;
-            self.image[[0, 2, x, y]] = color[0] as f32;
-        }
+        let tile_dims = self.tile_dims((width, height));
+        let grid = TileGrid::new((width, height), tile_dims, self.tile_overlap);
+        let tiles: Vec<RgbImage> = grid
+            .tiles()
+            .map(|tile| {
+                let tile = image.crop_imm(tile.x, tile.y, tile.width, tile.height);
+                pad_edges(&tile, tile_dims).to_rgb8()
+            })
+            .collect();
+
+        let batch_size = if self.dynamic_batch {
+            self.batch_size
+        } else {
+            1
+        };
+        self.batches = tiles
+            .chunks(batch_size)
+            .map(|batch| self.io.images_to_tensor(batch))
+            .collect();
+        self.grid = grid;
+        self.original_dims = original_dims;
+        self.plan = plan;
+        self.loaded_dims = (width, height);
         Ok(())
     }
 
     fn upscale(&self) -> Result<DynamicImage, Self::Error> {
-        let outputs = self
-            .session
-            .run(ort::inputs![&self.session.inputs[0].name => self.image.view()]?)?;
-
-        let pixels: Vec<u8> = outputs[0]
-            .try_extract_tensor::<f32>()?
-            .iter()
-            .map(|&i| i as u8)
-            .collect();
+        let mut session = self.session.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut run = |batch: &Array4<f32>| -> Result<Vec<RgbImage>, Error> {
+            let outputs = session.run(
+                ort::inputs![self.input_name.as_str() => TensorRef::from_array_view(batch)?],
+            )?;
+            self.io
+                .tensor_to_images(outputs[0].try_extract_array::<f32>()?)
+        };
 
-        Ok(RgbImage::from_raw(
-            self.upscaled_resolution(),
-            self.upscaled_resolution(),
-            pixels,
-        )
-        .unwrap()
-        .into())
+        let (width, height) = Scale::Uniform(self.model_factor)
+            .plan(self.loaded_dims)
+            .canvas;
+        let mut upscaled: DynamicImage = if self.grid.len() == 1 {
+            let tile = run(&self.batches[0])?
+                .into_iter()
+                .next()
+                .ok_or(Error::MalformedOutput)?;
+            DynamicImage::from(tile)
+        } else {
+            let mut blender = FeatherBlender::new(
+                self.grid.scaled(self.model_factor, (width, height)),
+                (width, height),
+            );
+            let mut index = 0;
+            for batch in &self.batches {
+                for tile in run(batch)? {
+                    blender.add(index, &tile.into());
+                    index += 1;
+                }
+            }
+            blender.finish(ColorType::Rgb8)
+        };
+
+        // Strips results of padding
+        if upscaled.width() > width || upscaled.height() > height {
+            upscaled = upscaled.crop_imm(0, 0, width, height);
+        }
+
+        if upscaled.dimensions() != self.plan.resized {
+            let (width, height) = self.plan.resized;
+            upscaled = upscaled.resize_exact(width, height, FilterType::CatmullRom);
+        }
+
+        Ok(self.plan.compose(upscaled))
     }
 
     fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error> {
@@ -97,16 +386,20 @@ impl UpscaleSquareImage for ONNXNeuralUpscaler {
         Ok(&self.upscaled_image)
     }
 
-    fn upscale_factor(&self) -> f32 {
-        self.scale_factor
+    fn scale(&self) -> Scale {
+        self.scale
     }
 
-    fn original_resolution(&self) -> u32 {
-        self.original_res
+    fn original_dimensions(&self) -> (u32, u32) {
+        self.original_dims
     }
 
-    fn upscaled_resolution(&self) -> u32 {
-        self.target_res
+    fn plan(&self) -> ScalePlan {
+        self.plan
+    }
+
+    fn kernel_support(&self) -> u32 {
+        self.tile_overlap
     }
 
     fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error> {
@@ -117,3 +410,199 @@ impl UpscaleSquareImage for ONNXNeuralUpscaler {
         Ok(&self.upscaled_image)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        onnx_io::{ChannelOrder, Normalization, TensorLayout},
+        onnx_test_model::TestModel,
+    };
+
+    fn gradient() -> DynamicImage {
+        RgbImage::from_fn(5, 3, |x, y| {
+            image::Rgb([x as u8 * 50, y as u8 * 100, 255 - x as u8 * 10])
+        })
+        .into()
+    }
+
+    fn saved(model: TestModel, name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("scale-benchmarks-{name}.onnx"));
+        model.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn identity_nchw() {
+        let path = saved(TestModel::nearest(1).fixed_size(5, 3), "identity-nchw");
+        let mut scaler = ONNXNeuralUpscaler::from_model(path).unwrap();
+        assert_eq!(scaler.model_factor(), 1.0);
+
+        scaler.load(&gradient()).unwrap();
+        assert_eq!(scaler.upscale().unwrap().to_rgb8(), gradient().to_rgb8());
+    }
+
+    #[test]
+    fn identity_nhwc_bgr() {
+        let path = saved(
+            TestModel::nearest(1)
+                .layout(TensorLayout::Nhwc)
+                .fixed_size(5, 3),
+            "identity-nhwc",
+        );
+        let io = ModelIO {
+            layout: TensorLayout::Nhwc,
+            channels: ChannelOrder::Bgr,
+            input: Normalization::Byte,
+            output: Normalization::Byte,
+        };
+        let mut scaler = ONNXNeuralUpscaler::from_model_with_io(path, io).unwrap();
+
+        scaler.load(&gradient()).unwrap();
+        assert_eq!(scaler.upscale().unwrap().to_rgb8(), gradient().to_rgb8());
+    }
+
+    #[test]
+    fn embedded_metadata() {
+        let model = TestModel::nearest(2)
+            .fixed_size(5, 3)
+            .metadata("name", "Nearest x2")
+            .metadata("scale", "2")
+            .metadata("input", "byte")
+            .metadata("output", "byte");
+        let scaler = ONNXNeuralUpscaler::from_model(saved(model, "metadata")).unwrap();
+
+        assert_eq!(scaler.name(), "Nearest x2");
+        assert_eq!(scaler.metadata().io().input, Normalization::Byte);
+    }
+
+    #[test]
+    fn sidecar_metadata() {
+        let path = saved(
+            TestModel::nearest(2)
+                .fixed_size(5, 3)
+                .metadata("scale", "2"),
+            "sidecar",
+        );
+        std::fs::write(
+            ModelMetadata::sidecar_path(&path),
+            r#"{"name": "Sidecar", "scale": 3}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ONNXNeuralUpscaler::from_model(&path),
+            Err(Error::IncompatibleModel(_))
+        ));
+
+        std::fs::write(ModelMetadata::sidecar_path(&path), r#"{"name": "Sidecar"}"#).unwrap();
+        assert_eq!(
+            ONNXNeuralUpscaler::from_model(&path).unwrap().name(),
+            "Sidecar"
+        );
+    }
+
+    #[test]
+    fn dynamic_axes_probe() {
+        let mut scaler =
+            ONNXNeuralUpscaler::from_model(saved(TestModel::nearest(3), "dynamic-probe")).unwrap();
+        assert_eq!(scaler.model_factor(), 3.0);
+
+        for (width, height) in [(7, 5), (64, 1), (333, 517)] {
+            scaler.load(&RgbImage::new(width, height).into()).unwrap();
+            assert_eq!(
+                scaler.upscale().unwrap().dimensions(),
+                (width * 3, height * 3)
+            );
+        }
+    }
+
+    #[test]
+    fn dynamic_axes_padding() {
+        let model = TestModel::nearest(2)
+            .metadata("scale", "2")
+            .metadata("pad_to_multiple", "8");
+        let mut scaler = ONNXNeuralUpscaler::from_model(saved(model, "dynamic-padding")).unwrap();
+
+        scaler.load(&gradient()).unwrap();
+        let upscaled = scaler.upscale().unwrap().to_rgb8();
+        assert_eq!(upscaled.dimensions(), (10, 6));
+        assert_eq!(
+            upscaled.get_pixel(9, 5),
+            gradient().to_rgb8().get_pixel(4, 2)
+        );
+    }
+
+    #[test]
+    fn fixed_axes_small_input_is_padded() {
+        let mut scaler =
+            ONNXNeuralUpscaler::from_model(saved(TestModel::nearest(2).fixed_size(8, 8), "fixed"))
+                .unwrap();
+
+        scaler.load(&gradient()).unwrap();
+        assert_eq!(scaler.tiles(), 1);
+        let upscaled = scaler.upscale().unwrap().to_rgb8();
+        assert_eq!(upscaled.dimensions(), (10, 6));
+        assert_eq!(
+            upscaled.get_pixel(9, 5),
+            gradient().to_rgb8().get_pixel(4, 2)
+        );
+    }
+
+    #[test]
+    fn fixed_axes_tiling_is_seam_free() {
+        let path = saved(TestModel::nearest(2).fixed_size(16, 16), "tiled");
+        let mut scaler = ONNXNeuralUpscaler::from_model(path)
+            .unwrap()
+            .with_tile_overlap(4);
+
+        let color = image::Rgb([200, 37, 91]);
+        scaler
+            .load(&RgbImage::from_pixel(50, 37, color).into())
+            .unwrap();
+        assert_eq!(scaler.tiles(), 4 * 3);
+
+        let upscaled = scaler.upscale().unwrap().to_rgb8();
+        assert_eq!(upscaled.dimensions(), (100, 74));
+        assert!(upscaled.pixels().all(|pixel| *pixel == color));
+    }
+
+    #[test]
+    fn batched_tiles_match_whole_image() {
+        let image: DynamicImage =
+            RgbImage::from_fn(45, 30, |x, y| image::Rgb([x as u8 * 5, y as u8 * 8, 100])).into();
+
+        let path = saved(TestModel::nearest(2).dynamic_batch(), "batched");
+        let mut whole = ONNXNeuralUpscaler::from_model(&path).unwrap();
+        whole.load(&image).unwrap();
+
+        let mut tiled = ONNXNeuralUpscaler::from_model(&path)
+            .unwrap()
+            .with_tile_size(16)
+            .with_tile_overlap(4)
+            .with_batch_size(5);
+        tiled.load(&image).unwrap();
+        assert_eq!(tiled.tiles(), 4 * 3);
+
+        assert_eq!(
+            tiled.upscale().unwrap().to_rgb8(),
+            whole.upscale().unwrap().to_rgb8()
+        );
+    }
+
+    #[test]
+    fn nearest_x2_keeps_orientation() {
+        let path = saved(TestModel::nearest(2).fixed_size(5, 3), "nearest-x2");
+        let mut scaler = ONNXNeuralUpscaler::from_model(path).unwrap();
+
+        scaler.load(&gradient()).unwrap();
+        let upscaled = scaler.upscale().unwrap().to_rgb8();
+        assert_eq!(upscaled.dimensions(), (10, 6));
+
+        let original = gradient().to_rgb8();
+        for (x, y, pixel) in original.enumerate_pixels() {
+            assert_eq!(upscaled.get_pixel(x * 2, y * 2), pixel);
+            assert_eq!(upscaled.get_pixel(x * 2 + 1, y * 2 + 1), pixel);
+        }
+    }
+}
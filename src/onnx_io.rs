@@ -0,0 +1,255 @@
+use image::RgbImage;
+use ndarray::{Array4, ArrayViewD, Axis};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Order of dimensions in a model's image tensors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TensorLayout {
+    /// `[batch, channels, height, width]`, PyTorch exports
+    #[default]
+    Nchw,
+
+    /// `[batch, height, width, channels]`, TensorFlow exports
+    Nhwc,
+}
+
+/// Order of colour channels in a model's image tensors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelOrder {
+    #[default]
+    Rgb,
+    Bgr,
+}
+
+/// Mapping between 8-bit pixel values and tensor values
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    /// `value / 255`, values in `[0, 1]`
+    #[default]
+    Unit,
+
+    /// Raw values in `[0, 255]`
+    Byte,
+
+    /// `(value / 255 - mean) / std` per channel, in RGB order
+    MeanStd { mean: [f32; 3], std: [f32; 3] },
+}
+
+impl Normalization {
+    fn normalize(&self, value: u8, channel: usize) -> f32 {
+        let value = value as f32;
+        match self {
+            Self::Unit => value / 255.0,
+            Self::Byte => value,
+            Self::MeanStd { mean, std } => (value / 255.0 - mean[channel]) / std[channel],
+        }
+    }
+
+    /// Inverse of `normalize`, rounded to nearest and clamped into `[0, 255]`
+    fn denormalize(&self, value: f32, channel: usize) -> u8 {
+        let value = match self {
+            Self::Unit => value * 255.0,
+            Self::Byte => value,
+            Self::MeanStd { mean, std } => (value * std[channel] + mean[channel]) * 255.0,
+        };
+        value.round().clamp(0.0, 255.0) as u8
+    }
+}
+
+/// Describes how images are encoded into model input and decoded from its output
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelIO {
+    pub layout: TensorLayout,
+    pub channels: ChannelOrder,
+    pub input: Normalization,
+    pub output: Normalization,
+}
+
+impl ModelIO {
+    /// Index of the tensor value for RGB channel `channel`
+    fn channel_index(&self, channel: usize) -> usize {
+        match self.channels {
+            ChannelOrder::Rgb => channel,
+            ChannelOrder::Bgr => 2 - channel,
+        }
+    }
+
+    /// Shape of a single-image tensor with given image dimensions
+    pub fn shape(&self, (width, height): (u32, u32)) -> [usize; 4] {
+        let (width, height) = (width as usize, height as usize);
+        match self.layout {
+            TensorLayout::Nchw => [1, 3, height, width],
+            TensorLayout::Nhwc => [1, height, width, 3],
+        }
+    }
+
+    /// Extracts `(width, height)` from a tensor shape, `None` if it is not a 3-channel image
+    pub fn dimensions<T: Copy>(&self, shape: &[T]) -> Option<(T, T, T)> {
+        match (self.layout, shape) {
+            (TensorLayout::Nchw, &[_, c, h, w]) => Some((w, h, c)),
+            (TensorLayout::Nhwc, &[_, h, w, c]) => Some((w, h, c)),
+            _ => None,
+        }
+    }
+
+    /// Encodes `image` into a model input tensor
+    pub fn image_to_tensor(&self, image: &RgbImage) -> Array4<f32> {
+        self.images_to_tensor(std::slice::from_ref(image))
+    }
+
+    /// Encodes same-sized `images` into a batched model input tensor
+    pub fn images_to_tensor(&self, images: &[RgbImage]) -> Array4<f32> {
+        let dimensions = images.first().map_or((0, 0), RgbImage::dimensions);
+        let mut shape = self.shape(dimensions);
+        shape[0] = images.len();
+
+        let mut tensor = Array4::zeros(shape);
+        for (batch, image) in images.iter().enumerate() {
+            for (x, y, pixel) in image.enumerate_pixels() {
+                let (x, y) = (x as usize, y as usize);
+                for (channel, &value) in pixel.0.iter().enumerate() {
+                    let index = self.channel_index(channel);
+                    let value = self.input.normalize(value, channel);
+                    match self.layout {
+                        TensorLayout::Nchw => tensor[[batch, index, y, x]] = value,
+                        TensorLayout::Nhwc => tensor[[batch, y, x, index]] = value,
+                    }
+                }
+            }
+        }
+        tensor
+    }
+
+    /// Decodes the first image of a model output tensor
+    pub fn tensor_to_image(&self, tensor: ArrayViewD<f32>) -> Result<RgbImage, Error> {
+        let (width, height) = self.output_dimensions(&tensor)?;
+        Ok(self.decode(tensor.index_axis(Axis(0), 0), (width, height)))
+    }
+
+    /// Decodes every image of a batched model output tensor
+    pub fn tensor_to_images(&self, tensor: ArrayViewD<f32>) -> Result<Vec<RgbImage>, Error> {
+        let dimensions = self.output_dimensions(&tensor)?;
+        Ok(tensor
+            .axis_iter(Axis(0))
+            .map(|image| self.decode(image, dimensions))
+            .collect())
+    }
+
+    fn output_dimensions(&self, tensor: &ArrayViewD<f32>) -> Result<(u32, u32), Error> {
+        let (width, height, channels) = self
+            .dimensions(tensor.shape())
+            .ok_or(Error::MalformedOutput)?;
+        if channels != 3 || tensor.shape()[0] < 1 {
+            return Err(Error::MalformedOutput);
+        }
+        Ok((width as u32, height as u32))
+    }
+
+    fn decode(&self, tensor: ArrayViewD<f32>, (width, height): (u32, u32)) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            let (x, y) = (x as usize, y as usize);
+            image::Rgb(std::array::from_fn(|channel| {
+                let index = self.channel_index(channel);
+                let value = match self.layout {
+                    TensorLayout::Nchw => tensor[[index, y, x]],
+                    TensorLayout::Nhwc => tensor[[y, x, index]],
+                };
+                self.output.denormalize(value, channel)
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> RgbImage {
+        RgbImage::from_fn(5, 3, |x, y| {
+            image::Rgb([x as u8 * 50, y as u8 * 100, 255 - x as u8 * 10])
+        })
+    }
+
+    #[test]
+    fn nchw_rgb_unit() {
+        let io = ModelIO::default();
+        let tensor = io.image_to_tensor(&gradient());
+        assert_eq!(tensor.shape(), &[1, 3, 3, 5]);
+        assert_eq!(tensor[[0, 0, 0, 4]], 200.0 / 255.0);
+        assert_eq!(tensor[[0, 1, 2, 0]], 200.0 / 255.0);
+        assert_eq!(
+            io.tensor_to_image(tensor.into_dyn().view()).unwrap(),
+            gradient()
+        );
+    }
+
+    #[test]
+    fn nhwc_bgr_byte() {
+        let io = ModelIO {
+            layout: TensorLayout::Nhwc,
+            channels: ChannelOrder::Bgr,
+            input: Normalization::Byte,
+            output: Normalization::Byte,
+        };
+        let tensor = io.image_to_tensor(&gradient());
+        assert_eq!(tensor.shape(), &[1, 3, 5, 3]);
+        assert_eq!(tensor[[0, 0, 4, 2]], 200.0);
+        assert_eq!(tensor[[0, 0, 4, 0]], 215.0);
+        assert_eq!(
+            io.tensor_to_image(tensor.into_dyn().view()).unwrap(),
+            gradient()
+        );
+    }
+
+    #[test]
+    fn mean_std_roundtrip() {
+        let io = ModelIO {
+            input: Normalization::MeanStd {
+                mean: [0.485, 0.456, 0.406],
+                std: [0.229, 0.224, 0.225],
+            },
+            output: Normalization::MeanStd {
+                mean: [0.485, 0.456, 0.406],
+                std: [0.229, 0.224, 0.225],
+            },
+            ..Default::default()
+        };
+        let tensor = io.image_to_tensor(&gradient());
+        assert_eq!(
+            io.tensor_to_image(tensor.into_dyn().view()).unwrap(),
+            gradient()
+        );
+    }
+
+    #[test]
+    fn output_is_rounded_and_clamped() {
+        let io = ModelIO::default();
+        let tensor =
+            ndarray::Array::from_shape_vec([1, 3, 1, 2], vec![-0.5, 1.5, 0.5, 0.499, 1.0, 0.0])
+                .unwrap();
+        let image = io.tensor_to_image(tensor.into_dyn().view()).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [0, 128, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [255, 127, 0]);
+    }
+
+    #[test]
+    fn batch_roundtrip() {
+        let io = ModelIO {
+            layout: TensorLayout::Nhwc,
+            ..Default::default()
+        };
+        let flipped = image::imageops::flip_horizontal(&gradient());
+        let tensor = io.images_to_tensor(&[gradient(), flipped.clone()]);
+        assert_eq!(tensor.shape(), &[2, 3, 5, 3]);
+        assert_eq!(
+            io.tensor_to_images(tensor.into_dyn().view()).unwrap(),
+            vec![gradient(), flipped]
+        );
+    }
+}
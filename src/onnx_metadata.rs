@@ -0,0 +1,174 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    onnx_io::{ChannelOrder, ModelIO, Normalization, TensorLayout},
+};
+
+/// Keys of ONNX `metadata_props` entries understood by [`ModelMetadata`]
+const KEYS: [&str; 8] = [
+    "name",
+    "scale",
+    "layout",
+    "channels",
+    "input",
+    "output",
+    "tile_size",
+    "pad_to_multiple",
+];
+
+/// Model properties that can't be derived from its graph
+///
+/// Read from ONNX `metadata_props` and from a JSON sidecar next to the model
+/// file (`model.onnx` -> `model.json`), the latter takes precedence.
+/// Property values in `metadata_props` are JSON, bare strings are accepted too:
+///
+/// ```json
+/// {
+///     "name": "RealESRGAN Compact",
+///     "scale": 4,
+///     "channels": "rgb",
+///     "input": { "mean_std": { "mean": [0.5, 0.5, 0.5], "std": [0.5, 0.5, 0.5] } },
+///     "tile_size": 64,
+///     "pad_to_multiple": 8
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelMetadata {
+    /// Display name
+    pub name: Option<String>,
+
+    /// Upscaling factor
+    pub scale: Option<u32>,
+
+    pub layout: Option<TensorLayout>,
+    pub channels: Option<ChannelOrder>,
+
+    /// Normalization of input tensor
+    pub input: Option<Normalization>,
+
+    /// Normalization of output tensor
+    pub output: Option<Normalization>,
+
+    /// Preferred input tile side for models with dynamic dimensions
+    pub tile_size: Option<u32>,
+
+    /// Input height and width must be multiples of this
+    pub pad_to_multiple: Option<u32>,
+}
+
+impl ModelMetadata {
+    /// Parses known entries of ONNX `metadata_props`, ignores the rest
+    pub fn from_props<K, V>(props: impl IntoIterator<Item = (K, V)>) -> Result<Self, Error>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut map = serde_json::Map::new();
+        for (key, value) in props {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            if KEYS.contains(&key) {
+                let value = serde_json::from_str(value).unwrap_or_else(|_| value.into());
+                map.insert(key.to_owned(), value);
+            }
+        }
+        Ok(serde_json::from_value(map.into())?)
+    }
+
+    /// Path of the JSON sidecar for a model file
+    pub fn sidecar_path(model_path: impl AsRef<Path>) -> PathBuf {
+        model_path.as_ref().with_extension("json")
+    }
+
+    /// Reads JSON sidecar of a model file, `None` if there is none
+    pub fn from_sidecar(model_path: impl AsRef<Path>) -> Result<Option<Self>, Error> {
+        let path = Self::sidecar_path(model_path);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_reader(BufReader::new(File::open(
+            path,
+        )?))?))
+    }
+
+    /// Combines two sets of properties, values from `other` win
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            name: other.name.or(self.name),
+            scale: other.scale.or(self.scale),
+            layout: other.layout.or(self.layout),
+            channels: other.channels.or(self.channels),
+            input: other.input.or(self.input),
+            output: other.output.or(self.output),
+            tile_size: other.tile_size.or(self.tile_size),
+            pad_to_multiple: other.pad_to_multiple.or(self.pad_to_multiple),
+        }
+    }
+
+    /// Tensor encoding described by the metadata, defaults fill the gaps
+    pub fn io(&self) -> ModelIO {
+        let default = ModelIO::default();
+        ModelIO {
+            layout: self.layout.unwrap_or(default.layout),
+            channels: self.channels.unwrap_or(default.channels),
+            input: self.input.unwrap_or(default.input),
+            output: self.output.unwrap_or(default.output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn props_accept_bare_strings_and_json() {
+        let metadata = ModelMetadata::from_props([
+            ("name", "Compact"),
+            ("scale", "4"),
+            ("channels", "bgr"),
+            (
+                "output",
+                r#"{"mean_std": {"mean": [0.5, 0.5, 0.5], "std": [0.5, 0.5, 0.5]}}"#,
+            ),
+            ("producer", "spandrel"),
+        ])
+        .unwrap();
+
+        assert_eq!(metadata.name.as_deref(), Some("Compact"));
+        assert_eq!(metadata.scale, Some(4));
+        assert_eq!(metadata.io().channels, ChannelOrder::Bgr);
+        assert_eq!(
+            metadata.io().output,
+            Normalization::MeanStd {
+                mean: [0.5; 3],
+                std: [0.5; 3]
+            }
+        );
+        assert_eq!(metadata.io().input, Normalization::Unit);
+    }
+
+    #[test]
+    fn sidecar_wins() {
+        let embedded = ModelMetadata::from_props([("scale", "2"), ("tile_size", "64")]).unwrap();
+        let sidecar: ModelMetadata =
+            serde_json::from_str(r#"{"scale": 4, "layout": "nhwc"}"#).unwrap();
+
+        let metadata = embedded.merge(sidecar);
+        assert_eq!(metadata.scale, Some(4));
+        assert_eq!(metadata.tile_size, Some(64));
+        assert_eq!(metadata.io().layout, TensorLayout::Nhwc);
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        assert!(ModelMetadata::from_props([("scale", "four")]).is_err());
+    }
+}
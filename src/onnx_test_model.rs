@@ -0,0 +1,212 @@
+//! Tiny hand-encoded ONNX models for tests and benchmarks, no Python tooling required
+
+use std::{fs, path::Path};
+
+use crate::{error::Error, onnx_io::TensorLayout};
+
+/// Minimal protobuf wire format writer
+#[derive(Default)]
+struct Proto(Vec<u8>);
+
+impl Proto {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn int(mut self, field: u32, value: i64) -> Self {
+        self.varint((field as u64) << 3);
+        self.varint(value as u64);
+        self
+    }
+
+    fn bytes(mut self, field: u32, value: &[u8]) -> Self {
+        self.varint((field as u64) << 3 | 2);
+        self.varint(value.len() as u64);
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    fn string(self, field: u32, value: &str) -> Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    fn message(self, field: u32, value: Proto) -> Self {
+        self.bytes(field, &value.0)
+    }
+}
+
+/// Tensor dimension, either fixed or named dynamic axis
+#[derive(Debug, Clone)]
+enum Dim {
+    Fixed(i64),
+    Dynamic(&'static str),
+}
+
+const FLOAT: i64 = 1;
+const ATTR_STRING: i64 = 3;
+
+fn value_info(name: &str, shape: &[Dim]) -> Proto {
+    let shape = shape.iter().fold(Proto::default(), |proto, dim| {
+        proto.message(
+            1,
+            match dim {
+                Dim::Fixed(value) => Proto::default().int(1, *value),
+                Dim::Dynamic(param) => Proto::default().string(2, param),
+            },
+        )
+    });
+    let tensor_type = Proto::default().int(1, FLOAT).message(2, shape);
+    Proto::default()
+        .string(1, name)
+        .message(2, Proto::default().message(1, tensor_type))
+}
+
+fn float_initializer(name: &str, values: &[f32]) -> Proto {
+    let raw: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    Proto::default()
+        .int(1, values.len() as i64)
+        .int(2, FLOAT)
+        .string(8, name)
+        .bytes(9, &raw)
+}
+
+/// Builder of a nearest-neighbour "neural" upscaler
+///
+/// Takes `[1, 3, H, W]` float input and produces `[1, 3, H * scale, W * scale]`
+/// (or NHWC equivalents), factor of 1 makes an identity model. Batch axis
+/// may be made dynamic.
+#[derive(Debug, Clone)]
+pub struct TestModel {
+    scale: u32,
+    size: Option<(u32, u32)>,
+    dynamic_batch: bool,
+    layout: TensorLayout,
+    metadata: Vec<(String, String)>,
+}
+
+impl TestModel {
+    /// Model with dynamic input height and width
+    pub fn nearest(scale: u32) -> Self {
+        Self {
+            scale: scale.max(1),
+            size: None,
+            dynamic_batch: false,
+            layout: TensorLayout::Nchw,
+            metadata: Vec::new(),
+        }
+    }
+
+    /// Sets layout of input and output tensors
+    pub fn layout(mut self, layout: TensorLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Fixes input dimensions, as models exported with static axes do
+    pub fn fixed_size(mut self, width: u32, height: u32) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    /// Makes batch axis dynamic, so the model takes several images at once
+    pub fn dynamic_batch(mut self) -> Self {
+        self.dynamic_batch = true;
+        self
+    }
+
+    /// Adds an entry to model's `metadata_props`
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.push((key.into(), value.into()));
+        self
+    }
+
+    fn shapes(&self) -> ([Dim; 4], [Dim; 4]) {
+        let (input_hw, output_hw) = match self.size {
+            Some((width, height)) => (
+                [Dim::Fixed(height as i64), Dim::Fixed(width as i64)],
+                [
+                    Dim::Fixed((height * self.scale) as i64),
+                    Dim::Fixed((width * self.scale) as i64),
+                ],
+            ),
+            None => (
+                [Dim::Dynamic("height"), Dim::Dynamic("width")],
+                [Dim::Dynamic("out_height"), Dim::Dynamic("out_width")],
+            ),
+        };
+        let batch = match self.dynamic_batch {
+            true => Dim::Dynamic("batch"),
+            false => Dim::Fixed(1),
+        };
+        let shape = |[h, w]: [Dim; 2]| match self.layout {
+            TensorLayout::Nchw => [batch.clone(), Dim::Fixed(3), h, w],
+            TensorLayout::Nhwc => [batch.clone(), h, w, Dim::Fixed(3)],
+        };
+        (shape(input_hw), shape(output_hw))
+    }
+
+    /// Serializes the model into ONNX protobuf bytes
+    pub fn encode(&self) -> Vec<u8> {
+        let (input_shape, output_shape) = self.shapes();
+
+        let mut graph = Proto::default().string(2, "test_model");
+        if self.scale == 1 {
+            graph = graph.message(
+                1,
+                Proto::default()
+                    .string(1, "input")
+                    .string(2, "output")
+                    .string(3, "identity")
+                    .string(4, "Identity"),
+            );
+        } else {
+            let scale = self.scale as f32;
+            let scales = match self.layout {
+                TensorLayout::Nchw => [1.0, 1.0, scale, scale],
+                TensorLayout::Nhwc => [1.0, scale, scale, 1.0],
+            };
+            graph = graph
+                .message(
+                    1,
+                    Proto::default()
+                        .string(1, "input")
+                        .string(1, "")
+                        .string(1, "scales")
+                        .string(2, "output")
+                        .string(3, "resize")
+                        .string(4, "Resize")
+                        .message(
+                            5,
+                            Proto::default()
+                                .string(1, "mode")
+                                .bytes(4, b"nearest")
+                                .int(20, ATTR_STRING),
+                        ),
+                )
+                .message(5, float_initializer("scales", &scales));
+        }
+        graph = graph
+            .message(11, value_info("input", &input_shape))
+            .message(12, value_info("output", &output_shape));
+
+        let mut model = Proto::default()
+            .int(1, 8)
+            .string(2, "scale-benchmarks")
+            .message(7, graph)
+            .message(8, Proto::default().string(1, "").int(2, 13));
+        for (key, value) in &self.metadata {
+            model = model.message(14, Proto::default().string(1, key).string(2, value));
+        }
+        model.0
+    }
+
+    /// Writes the model to `path`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        fs::write(path, self.encode())?;
+        Ok(())
+    }
+}
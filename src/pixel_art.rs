@@ -0,0 +1,377 @@
+//! Upscalers for pixel art that keep hard edges instead of blurring them
+//!
+//! [`PixelArt::Epx`] and [`PixelArt::Eagle`] follow their published rules exactly. hqx, xBR and
+//! xBRZ are not implemented yet.
+
+use image::{imageops::FilterType, DynamicImage, GenericImageView, RgbImage, Rgba, RgbaImage};
+
+use crate::{error::Error, scale::Scale, tiling::convert, upscaler::UpscaleImage};
+
+/// Pixel-art scaling algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelArt {
+    /// Scale2x and Scale3x from AdvanceMAME, Scale4x runs Scale2x twice
+    Epx,
+
+    /// Eagle, 4x runs it twice
+    Eagle,
+}
+
+impl PixelArt {
+    /// Factors the algorithm produces natively
+    pub fn factors(&self) -> &'static [u32] {
+        match self {
+            Self::Eagle => &[2, 4],
+            _ => &[2, 3, 4],
+        }
+    }
+
+    /// Upscales `image` by a native `factor`
+    pub fn apply(&self, image: &RgbaImage, factor: u32) -> Result<RgbaImage, Error> {
+        if !self.factors().contains(&factor) {
+            return Err(Error::UnsupportedPixelArtFactor(*self, factor));
+        }
+        Ok(match (self, factor) {
+            (Self::Epx, 2) => scale2x(image),
+            (Self::Epx, 3) => scale3x(image),
+            (Self::Epx, _) => scale2x(&scale2x(image)),
+            (Self::Eagle, 2) => eagle(image),
+            (Self::Eagle, _) => eagle(&eagle(image)),
+        })
+    }
+
+    /// Source pixels around a pixel that affect its output block at `factor`
+    fn reach(&self, factor: u32) -> u32 {
+        match (self, factor) {
+            (Self::Epx | Self::Eagle, 4) => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Edge-clamped reads around a source pixel
+struct Neighbours<'a> {
+    image: &'a RgbaImage,
+    x: i64,
+    y: i64,
+}
+
+impl Neighbours<'_> {
+    fn at(&self, dx: i64, dy: i64) -> Rgba<u8> {
+        let x = (self.x + dx).clamp(0, self.image.width() as i64 - 1);
+        let y = (self.y + dy).clamp(0, self.image.height() as i64 - 1);
+        *self.image.get_pixel(x as u32, y as u32)
+    }
+}
+
+/// Calls `block(neighbours, output)` for every source pixel, `output` holds its `factor`×`factor`
+/// block
+fn per_block(
+    image: &RgbaImage,
+    factor: u32,
+    mut block: impl FnMut(&Neighbours, &mut [Rgba<u8>]),
+) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let mut upscaled = RgbaImage::new(width * factor, height * factor);
+    let mut output = vec![Rgba([0; 4]); (factor * factor) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let neighbours = Neighbours {
+                image,
+                x: x as i64,
+                y: y as i64,
+            };
+            block(&neighbours, &mut output);
+            for (index, pixel) in output.iter().enumerate() {
+                let (dx, dy) = (index as u32 % factor, index as u32 / factor);
+                upscaled.put_pixel(x * factor + dx, y * factor + dy, *pixel);
+            }
+        }
+    }
+    upscaled
+}
+
+fn scale2x(image: &RgbaImage) -> RgbaImage {
+    per_block(image, 2, |n, out| {
+        let (b, d, e, f, h) = (n.at(0, -1), n.at(-1, 0), n.at(0, 0), n.at(1, 0), n.at(0, 1));
+        out.fill(e);
+        if b != h && d != f {
+            if d == b {
+                out[0] = d;
+            }
+            if b == f {
+                out[1] = f;
+            }
+            if d == h {
+                out[2] = d;
+            }
+            if h == f {
+                out[3] = f;
+            }
+        }
+    })
+}
+
+fn scale3x(image: &RgbaImage) -> RgbaImage {
+    per_block(image, 3, |n, out| {
+        let (a, b, c) = (n.at(-1, -1), n.at(0, -1), n.at(1, -1));
+        let (d, e, f) = (n.at(-1, 0), n.at(0, 0), n.at(1, 0));
+        let (g, h, i) = (n.at(-1, 1), n.at(0, 1), n.at(1, 1));
+        out.fill(e);
+        if b == h || d == f {
+            return;
+        }
+        let pick = |condition: bool, color: Rgba<u8>| if condition { color } else { e };
+        out[0] = pick(d == b, d);
+        out[1] = pick((d == b && e != c) || (b == f && e != a), b);
+        out[2] = pick(b == f, f);
+        out[3] = pick((d == b && e != g) || (d == h && e != a), d);
+        out[5] = pick((b == f && e != i) || (h == f && e != c), f);
+        out[6] = pick(d == h, d);
+        out[7] = pick((d == h && e != i) || (h == f && e != g), h);
+        out[8] = pick(h == f, f);
+    })
+}
+
+fn eagle(image: &RgbaImage) -> RgbaImage {
+    per_block(image, 2, |n, out| {
+        // A corner takes the colour of its three outer neighbours when they agree
+        for (index, (dx, dy)) in [(-1, -1), (1, -1), (-1, 1), (1, 1)].into_iter().enumerate() {
+            let (vertical, horizontal, diagonal) = (n.at(0, dy), n.at(dx, 0), n.at(dx, dy));
+            out[index] = if vertical == horizontal && horizontal == diagonal {
+                diagonal
+            } else {
+                n.at(0, 0)
+            };
+        }
+    })
+}
+
+/// Upscaler running a [`PixelArt`] algorithm
+///
+/// The algorithm runs at its smallest native factor covering the requested scale, other sizes are
+/// reached from there with nearest neighbour. Native factors give the algorithm's output as is.
+#[derive(Debug, Clone)]
+pub struct PixelArtUpscaler {
+    algorithm: PixelArt,
+    image: DynamicImage,
+    upscaled_image: DynamicImage,
+    scale: Scale,
+}
+
+impl PixelArtUpscaler {
+    pub fn new(algorithm: PixelArt, scale: impl Into<Scale>) -> Self {
+        let scale = scale.into();
+        let image = RgbImage::new(1, 1).into();
+        let (width, height) = scale.plan((1, 1)).canvas;
+        Self {
+            algorithm,
+            image,
+            upscaled_image: RgbImage::new(width, height).into(),
+            scale,
+        }
+    }
+
+    pub fn algorithm(&self) -> PixelArt {
+        self.algorithm
+    }
+
+    /// Factor the algorithm runs at for the loaded image
+    pub fn native_factor(&self) -> u32 {
+        let (x, y) = self.upscale_factors();
+        let factors = self.algorithm.factors();
+        let needed = x.max(y).ceil() as u32;
+        factors
+            .iter()
+            .copied()
+            .find(|&factor| factor >= needed)
+            .unwrap_or(factors[factors.len() - 1])
+    }
+}
+
+impl UpscaleImage for PixelArtUpscaler {
+    type Error = Error;
+
+    fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error> {
+        self.image = image.clone();
+        Ok(())
+    }
+
+    fn upscale(&self) -> Result<DynamicImage, Self::Error> {
+        let plan = self.plan();
+        let cropped = plan.crop_image(&self.image);
+        let upscaled: DynamicImage = self
+            .algorithm
+            .apply(&cropped.to_rgba8(), self.native_factor())?
+            .into();
+        let (width, height) = plan.resized;
+        let resized = if upscaled.dimensions() == (width, height) {
+            upscaled
+        } else {
+            upscaled.resize_exact(width, height, FilterType::Nearest)
+        };
+        Ok(plan.compose(convert(resized, self.image.color())))
+    }
+
+    fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error> {
+        self.upscaled_image = self.upscale()?;
+        Ok(&self.upscaled_image)
+    }
+
+    fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error> {
+        for _ in 0..times {
+            self.upscale_inplace()?;
+        }
+        Ok(&self.upscaled_image)
+    }
+
+    fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    fn original_dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn kernel_support(&self) -> u32 {
+        self.algorithm.reach(self.native_factor())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const ALGORITHMS: [PixelArt; 2] = [PixelArt::Epx, PixelArt::Eagle];
+
+    /// Indexed-colour image, `.` is transparent
+    fn sprite(rows: &[&str]) -> RgbaImage {
+        let color = |symbol| match symbol {
+            b'#' => Rgba([20, 12, 28, 255]),
+            b'r' => Rgba([208, 70, 72, 255]),
+            b'y' => Rgba([218, 212, 94, 255]),
+            b'g' => Rgba([109, 170, 44, 255]),
+            b'b' => Rgba([89, 125, 206, 255]),
+            b'w' => Rgba([222, 238, 214, 255]),
+            _ => Rgba([0, 0, 0, 0]),
+        };
+        RgbaImage::from_fn(rows[0].len() as u32, rows.len() as u32, |x, y| {
+            color(rows[y as usize].as_bytes()[x as usize])
+        })
+    }
+
+    /// Mushroom with diagonals, shallow and steep slopes, single pixel details and a gradient-free
+    /// background
+    fn mushroom() -> RgbaImage {
+        sprite(&[
+            "................",
+            ".....######.....",
+            "...##rrwwrr##...",
+            "..#rrrwwwwrrr#..",
+            ".#wrrrrwwrrrrw#.",
+            ".#wwrrrrrrrrww#.",
+            "#rwwrr####rrwwr#",
+            "#rrrr#bbbb#rrrr#",
+            "#rrrr#bbbb#rrrr#",
+            ".#rr##wbbw##rr#.",
+            "..##.#wwww#.##..",
+            ".....#wwwy#.....",
+            ".....#wwyy#.....",
+            "......####......",
+            "..gg........gg..",
+            "gggggggggggggggg",
+        ])
+    }
+
+    #[test]
+    fn scale2x_rules() {
+        let image = sprite(&["...", ".#.", "..."]);
+        let block = |image: &RgbaImage, x: u32, y: u32| {
+            [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| image[(x + dx, y + dy)][3])
+        };
+
+        // An isolated pixel has no matching neighbours and stays a 2×2 block
+        let upscaled = PixelArt::Epx.apply(&image, 2).unwrap();
+        assert_eq!(block(&upscaled, 2, 2), [255; 4]);
+        assert_eq!(block(&upscaled, 2, 0), [0; 4]);
+
+        // The gap above a diagonal step fills in towards the step
+        let image = sprite(&["#..", "##.", "..."]);
+        let upscaled = PixelArt::Epx.apply(&image, 2).unwrap();
+        assert_eq!(block(&upscaled, 2, 0), [0, 0, 255, 0]);
+        assert_eq!(block(&upscaled, 2, 2), [255; 4]);
+    }
+
+    #[test]
+    fn eagle_rules() {
+        let image = sprite(&["##.", "#r.", "..."]);
+        let upscaled = PixelArt::Eagle.apply(&image, 2).unwrap();
+        // Corners of the centre with three agreeing outer neighbours take their colour
+        let block = [(2, 2), (3, 2), (2, 3), (3, 3)].map(|(x, y)| upscaled[(x, y)][0]);
+        assert_eq!(block, [20, 208, 208, 0]);
+    }
+
+    #[test]
+    fn flat_and_exact() {
+        let flat = RgbaImage::from_pixel(5, 4, Rgba([40, 90, 160, 255]));
+        let image = mushroom();
+        for algorithm in ALGORITHMS {
+            for &factor in algorithm.factors() {
+                let upscaled = algorithm.apply(&flat, factor).unwrap();
+                assert_eq!(
+                    upscaled,
+                    RgbaImage::from_pixel(5 * factor, 4 * factor, flat[(0, 0)])
+                );
+
+                let mut scaler = PixelArtUpscaler::new(algorithm, factor as f32);
+                scaler.load(&image.clone().into()).unwrap();
+                assert_eq!(scaler.native_factor(), factor);
+                assert_eq!(
+                    scaler.upscale().unwrap().to_rgba8(),
+                    algorithm.apply(&image, factor).unwrap()
+                );
+            }
+        }
+        assert!(PixelArt::Eagle.apply(&flat, 3).is_err());
+    }
+
+    /// Compares against `testdata/pixel_art`, made by the independent `reference.py` there
+    #[test]
+    fn golden_images() {
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/pixel_art");
+        let image = mushroom();
+        for algorithm in ALGORITHMS {
+            for &factor in algorithm.factors() {
+                let upscaled = algorithm.apply(&image, factor).unwrap();
+                let path = directory.join(format!("{algorithm:?}_{factor}x.png").to_lowercase());
+                let golden =
+                    image::open(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+                assert!(
+                    golden.to_rgba8() == upscaled,
+                    "{algorithm:?} {factor}x differs from {}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_match_whole_image() {
+        use crate::tiled::TiledUpscaler;
+
+        let image: DynamicImage = mushroom().into();
+        for algorithm in ALGORITHMS {
+            let mut whole = PixelArtUpscaler::new(algorithm, 4.0);
+            whole.load(&image).unwrap();
+            let mut tiled =
+                TiledUpscaler::new(PixelArtUpscaler::new(algorithm, 4.0)).with_tile_size(5);
+            assert_eq!(
+                tiled.upscale_image(&image).unwrap(),
+                whole.upscale().unwrap(),
+                "{algorithm:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,768 @@
+//! RAISR-style upscaling: a cheap upscale sharpened by filters learned per local structure
+//!
+//! After Romano, Isidoro and Milanfar, "RAISR: Rapid and Accurate Image Super Resolution" (2016).
+//! The luma of a bilinear upscale is filtered with one of the bank's filters, picked by hashing the
+//! gradient angle, strength and coherence around every pixel together with its position modulo the
+//! factor. The luma change is added to every colour channel.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use image::{DynamicImage, GenericImageView, RgbImage, Rgba32FImage};
+
+use crate::{
+    cpu_algo::{ResampleKernel, Resampler},
+    error::Error,
+    scale::Scale,
+    tiling::convert,
+    upscaler::UpscaleImage,
+};
+
+const MAGIC: &[u8; 8] = b"RAISRFB1";
+
+/// Ridge added to the normal equations, relative to their mean diagonal
+const RIDGE: f64 = 1e-3;
+
+/// How a filter bank splits pixels into buckets
+#[derive(Debug, Clone, PartialEq)]
+struct Hashing {
+    factor: u32,
+    patch: u32,
+    angles: u32,
+    strength_thresholds: Vec<f32>,
+    coherence_thresholds: Vec<f32>,
+}
+
+impl Hashing {
+    /// Largest upscaling factor a bank may have
+    const MAX_FACTOR: u32 = 16;
+    /// Largest filter side a bank may have
+    const MAX_PATCH: u32 = 15;
+    /// Most angle buckets a bank may have
+    const MAX_ANGLES: u32 = 180;
+
+    fn buckets(&self) -> usize {
+        let (strengths, coherences) = (
+            self.strength_thresholds.len() + 1,
+            self.coherence_thresholds.len() + 1,
+        );
+        (self.angles as usize) * strengths * coherences * (self.factor * self.factor) as usize
+    }
+
+    fn taps(&self) -> usize {
+        (self.patch * self.patch) as usize
+    }
+
+    fn radius(&self) -> i64 {
+        self.patch as i64 / 2
+    }
+
+    /// Gaussian weights of the structure tensor window, summing to one
+    fn window(&self) -> Vec<f32> {
+        let (radius, sigma) = (self.radius(), self.patch as f32 / 4.0);
+        let weights: Vec<f32> = (-radius..=radius)
+            .flat_map(|y| {
+                (-radius..=radius)
+                    .map(move |x| (-((x * x + y * y) as f32) / (2.0 * sigma * sigma)).exp())
+            })
+            .collect();
+        let sum: f32 = weights.iter().sum();
+        weights.into_iter().map(|weight| weight / sum).collect()
+    }
+
+    /// Bucket of the pixel at `(x, y)` of an upscaled luma plane
+    fn bucket(&self, gradients: &Gradients, window: &[f32], x: i64, y: i64) -> usize {
+        let radius = self.radius();
+        let (mut xx, mut xy, mut yy) = (0.0, 0.0, 0.0);
+        for (index, weight) in window.iter().enumerate() {
+            let (dx, dy) = (
+                index as i64 % self.patch as i64 - radius,
+                index as i64 / self.patch as i64 - radius,
+            );
+            let (gx, gy) = gradients.at(x + dx, y + dy);
+            xx += weight * gx * gx;
+            xy += weight * gx * gy;
+            yy += weight * gy * gy;
+        }
+
+        // Eigen decomposition of the structure tensor
+        let spread = (((xx - yy) / 2.0f32).powi(2) + xy * xy).sqrt();
+        let (major, minor) = (
+            (xx + yy) / 2.0 + spread,
+            ((xx + yy) / 2.0 - spread).max(0.0),
+        );
+        let mut theta = 0.5 * (2.0 * xy).atan2(xx - yy);
+        if theta < 0.0 {
+            theta += std::f32::consts::PI;
+        }
+        let (major, minor) = (major.sqrt(), minor.sqrt());
+        let coherence = if major + minor > 0.0 {
+            (major - minor) / (major + minor)
+        } else {
+            0.0
+        };
+
+        let angle = ((theta / std::f32::consts::PI * self.angles as f32) as usize)
+            .min(self.angles as usize - 1);
+        let strength = self
+            .strength_thresholds
+            .iter()
+            .filter(|&&threshold| major >= threshold)
+            .count();
+        let coherence = self
+            .coherence_thresholds
+            .iter()
+            .filter(|&&threshold| coherence >= threshold)
+            .count();
+        let pixel_type = (y as u32 % self.factor * self.factor + x as u32 % self.factor) as usize;
+
+        let (strengths, coherences) = (
+            self.strength_thresholds.len() + 1,
+            self.coherence_thresholds.len() + 1,
+        );
+        let structure = (angle * strengths + strength) * coherences + coherence;
+        structure * (self.factor * self.factor) as usize + pixel_type
+    }
+
+    /// Calls `pixel(x, y, bucket, patch)` for every pixel of `luma` at least `margin` pixels from
+    /// its border
+    fn for_each_pixel(
+        &self,
+        luma: &Plane,
+        margin: i64,
+        mut pixel: impl FnMut(i64, i64, usize, &[f32]),
+    ) {
+        let gradients = Gradients::new(luma);
+        let window = self.window();
+        let radius = self.radius();
+        let mut patch = vec![0.0; self.taps()];
+        for y in margin..luma.height - margin {
+            for x in margin..luma.width - margin {
+                for (index, value) in patch.iter_mut().enumerate() {
+                    let (dx, dy) = (
+                        index as i64 % self.patch as i64,
+                        index as i64 / self.patch as i64,
+                    );
+                    *value = luma.at(x + dx - radius, y + dy - radius);
+                }
+                pixel(x, y, self.bucket(&gradients, &window, x, y), &patch);
+            }
+        }
+    }
+}
+
+/// Luma of an image, with edge-clamped reads
+struct Plane {
+    width: i64,
+    height: i64,
+    values: Vec<f32>,
+}
+
+impl Plane {
+    fn luma(image: &Rgba32FImage) -> Self {
+        let values = image
+            .pixels()
+            .map(|pixel| 0.299 * pixel[0] + 0.587 * pixel[1] + 0.114 * pixel[2])
+            .collect();
+        Self {
+            width: image.width() as i64,
+            height: image.height() as i64,
+            values,
+        }
+    }
+
+    fn at(&self, x: i64, y: i64) -> f32 {
+        let (x, y) = (x.clamp(0, self.width - 1), y.clamp(0, self.height - 1));
+        self.values[(y * self.width + x) as usize]
+    }
+}
+
+/// Central differences of a [`Plane`]
+struct Gradients<'a>(&'a Plane);
+
+impl<'a> Gradients<'a> {
+    fn new(plane: &'a Plane) -> Self {
+        Self(plane)
+    }
+
+    fn at(&self, x: i64, y: i64) -> (f32, f32) {
+        let plane = self.0;
+        (
+            (plane.at(x + 1, y) - plane.at(x - 1, y)) / 2.0,
+            (plane.at(x, y + 1) - plane.at(x, y - 1)) / 2.0,
+        )
+    }
+}
+
+/// Bilinear upscale RAISR filters refine
+fn cheap_upscale(image: &Rgba32FImage, factor: u32) -> Rgba32FImage {
+    let (width, height) = image.dimensions();
+    Resampler::new(
+        ResampleKernel::Triangle,
+        (width, height),
+        (width * factor, height * factor),
+    )
+    .apply(image)
+}
+
+/// Filters for every hash bucket, learned by [`FilterTrainer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterBank {
+    hashing: Hashing,
+    filters: Vec<f32>,
+}
+
+impl FilterBank {
+    /// Bank whose filters keep the bilinear upscale as is
+    pub fn identity(factor: u32) -> Result<Self, Error> {
+        Ok(FilterTrainer::new(factor)?.finish())
+    }
+
+    /// Starts learning a bank for `factor` times upscaling, from 1 to 16
+    pub fn trainer(factor: u32) -> Result<FilterTrainer, Error> {
+        FilterTrainer::new(factor)
+    }
+
+    pub fn factor(&self) -> u32 {
+        self.hashing.factor
+    }
+
+    /// Side of the square filters
+    pub fn patch(&self) -> u32 {
+        self.hashing.patch
+    }
+
+    /// Upscales `image` by [`FilterBank::factor`]
+    pub fn apply(&self, image: &Rgba32FImage) -> Rgba32FImage {
+        let mut upscaled = cheap_upscale(image, self.factor());
+        let luma = Plane::luma(&upscaled);
+        let taps = self.hashing.taps();
+        self.hashing
+            .for_each_pixel(&luma, 0, |x, y, bucket, patch| {
+                let filter = &self.filters[bucket * taps..(bucket + 1) * taps];
+                let filtered: f32 = patch
+                    .iter()
+                    .zip(filter)
+                    .map(|(value, weight)| value * weight)
+                    .sum();
+                let change = filtered - luma.at(x, y);
+                let pixel = upscaled.get_pixel_mut(x as u32, y as u32);
+                for channel in &mut pixel.0[..3] {
+                    *channel = (*channel + change).clamp(0.0, 1.0);
+                }
+            });
+        upscaled
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        Ok(writer.flush()?)
+    }
+
+    /// Reads the format [`FilterBank::write_to`] writes
+    pub fn read_from(mut reader: impl Read) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::InvalidFilterBank(reason.to_string());
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("unknown file signature"));
+        }
+
+        let mut read_u32 = || -> Result<u32, Error> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+        let (factor, patch, angles) = (read_u32()?, read_u32()?, read_u32()?);
+        let (strengths, coherences) = (read_u32()?, read_u32()?);
+        if !(1..=Hashing::MAX_FACTOR).contains(&factor)
+            || !(3..=Hashing::MAX_PATCH).contains(&patch)
+            || patch % 2 == 0
+            || !(1..=Hashing::MAX_ANGLES).contains(&angles)
+            || strengths > 64
+            || coherences > 64
+        {
+            return Err(invalid("hashing parameters out of range"));
+        }
+
+        // Grows with the data actually read, so a truncated file can't claim a huge allocation
+        let mut read_f32s = |count: usize| -> Result<Vec<f32>, Error> {
+            let length = count
+                .checked_mul(4)
+                .ok_or_else(|| invalid("too many filters"))?;
+            let mut bytes = Vec::new();
+            (&mut reader).take(length as u64).read_to_end(&mut bytes)?;
+            if bytes.len() != length {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect())
+        };
+        let hashing = Hashing {
+            factor,
+            patch,
+            angles,
+            strength_thresholds: read_f32s(strengths as usize)?,
+            coherence_thresholds: read_f32s(coherences as usize)?,
+        };
+        let count = hashing.buckets().checked_mul(hashing.taps());
+        let filters = read_f32s(count.ok_or_else(|| invalid("too many filters"))?)?;
+        Ok(Self { hashing, filters })
+    }
+
+    /// Writes a little-endian binary: signature, hashing parameters, thresholds, then every filter
+    pub fn write_to(&self, mut writer: impl Write) -> Result<(), Error> {
+        let hashing = &self.hashing;
+        writer.write_all(MAGIC)?;
+        let header = [
+            hashing.factor,
+            hashing.patch,
+            hashing.angles,
+            hashing.strength_thresholds.len() as u32,
+            hashing.coherence_thresholds.len() as u32,
+        ];
+        for value in header {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        let floats = hashing
+            .strength_thresholds
+            .iter()
+            .chain(&hashing.coherence_thresholds)
+            .chain(&self.filters);
+        for value in floats {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Least squares fit of [`FilterBank`] filters to high-resolution images
+///
+/// Every image is downscaled with Catmull-Rom, upscaled bilinearly and filtered towards the
+/// original.
+pub struct FilterTrainer {
+    hashing: Hashing,
+    /// Upper triangles of the normal matrices, per bucket, allocated on first use
+    normal: Vec<Vec<f64>>,
+    targets: Vec<Vec<f64>>,
+    samples: Vec<u64>,
+    images: usize,
+}
+
+impl FilterTrainer {
+    fn new(factor: u32) -> Result<Self, Error> {
+        if !(1..=Hashing::MAX_FACTOR).contains(&factor) {
+            return Err(Error::InvalidFilterBank(format!(
+                "factor {factor} outside 1 to {}",
+                Hashing::MAX_FACTOR
+            )));
+        }
+        Ok(Self {
+            hashing: Hashing {
+                factor,
+                patch: 7,
+                angles: 24,
+                strength_thresholds: vec![0.01, 0.04],
+                coherence_thresholds: vec![0.25, 0.5],
+            },
+            normal: Vec::new(),
+            targets: Vec::new(),
+            samples: Vec::new(),
+            images: 0,
+        })
+    }
+
+    /// Side of the square filters and the gradient window, odd from 3 to 15
+    pub fn with_patch(mut self, patch: u32) -> Result<Self, Error> {
+        if !(3..=Hashing::MAX_PATCH).contains(&patch) || patch.is_multiple_of(2) {
+            return Err(Error::InvalidFilterBank(format!(
+                "patch {patch} isn't odd from 3 to {}",
+                Hashing::MAX_PATCH
+            )));
+        }
+        self.hashing.patch = patch;
+        Ok(self)
+    }
+
+    /// Number of gradient angle buckets over 180°, from 1 to 180
+    pub fn with_angles(mut self, angles: u32) -> Result<Self, Error> {
+        if !(1..=Hashing::MAX_ANGLES).contains(&angles) {
+            return Err(Error::InvalidFilterBank(format!(
+                "{angles} angles outside 1 to {}",
+                Hashing::MAX_ANGLES
+            )));
+        }
+        self.hashing.angles = angles;
+        Ok(self)
+    }
+
+    /// Gradient magnitudes, in luma per pixel, separating strength buckets
+    pub fn with_strength_thresholds(mut self, thresholds: Vec<f32>) -> Self {
+        self.hashing.strength_thresholds = thresholds;
+        self
+    }
+
+    /// Coherences, from 0 for isotropic to 1 for a single direction, separating coherence buckets
+    pub fn with_coherence_thresholds(mut self, thresholds: Vec<f32>) -> Self {
+        self.hashing.coherence_thresholds = thresholds;
+        self
+    }
+
+    /// Number of images added so far
+    pub fn images(&self) -> usize {
+        self.images
+    }
+
+    /// Accumulates every pixel of `image`, which is the high-resolution ground truth
+    pub fn add_image(&mut self, image: &DynamicImage) {
+        let factor = self.hashing.factor;
+        let (width, height) = (
+            image.width() / factor * factor,
+            image.height() / factor * factor,
+        );
+        if width == 0 || height == 0 {
+            return;
+        }
+        if self.normal.is_empty() {
+            self.normal = vec![Vec::new(); self.hashing.buckets()];
+            self.targets = vec![Vec::new(); self.hashing.buckets()];
+            self.samples = vec![0; self.hashing.buckets()];
+        }
+
+        let truth = image.crop_imm(0, 0, width, height).to_rgba32f();
+        let low = Resampler::new(
+            ResampleKernel::CATMULL_ROM,
+            (width, height),
+            (width / factor, height / factor),
+        )
+        .apply(&truth);
+        let upscaled = Plane::luma(&cheap_upscale(&low, factor));
+        let truth = Plane::luma(&truth);
+
+        let taps = self.hashing.taps();
+        let margin = self.hashing.radius() + 1;
+        let (normal, targets, samples) = (&mut self.normal, &mut self.targets, &mut self.samples);
+        self.hashing
+            .for_each_pixel(&upscaled, margin, |x, y, bucket, patch| {
+                let (normal, target) = (&mut normal[bucket], &mut targets[bucket]);
+                if normal.is_empty() {
+                    *normal = vec![0.0; taps * (taps + 1) / 2];
+                    *target = vec![0.0; taps];
+                }
+                let truth = truth.at(x, y) as f64;
+                let mut entry = 0;
+                for (row, &a) in patch.iter().enumerate() {
+                    let a = a as f64;
+                    target[row] += a * truth;
+                    for &b in &patch[row..] {
+                        normal[entry] += a * b as f64;
+                        entry += 1;
+                    }
+                }
+                samples[bucket] += 1;
+            });
+        self.images += 1;
+    }
+
+    /// Solves every bucket, buckets without enough samples keep the bilinear upscale
+    pub fn finish(self) -> FilterBank {
+        let taps = self.hashing.taps();
+        let mut identity = vec![0.0; taps];
+        identity[taps / 2] = 1.0;
+
+        let mut filters = Vec::with_capacity(self.hashing.buckets() * taps);
+        for bucket in 0..self.hashing.buckets() {
+            let samples = self.samples.get(bucket).copied().unwrap_or(0);
+            let solved = match samples >= taps as u64 {
+                true => solve_normal(&self.normal[bucket], &self.targets[bucket], taps),
+                false => None,
+            };
+            filters.extend(solved.unwrap_or_else(|| identity.clone()));
+        }
+        FilterBank {
+            hashing: self.hashing,
+            filters,
+        }
+    }
+}
+
+/// Solves ridge-regularized normal equations by Cholesky decomposition
+fn solve_normal(upper: &[f64], target: &[f64], taps: usize) -> Option<Vec<f32>> {
+    // Expand the upper triangle into a full matrix
+    let mut matrix = vec![0.0; taps * taps];
+    let mut entry = 0;
+    for row in 0..taps {
+        for column in row..taps {
+            matrix[row * taps + column] = upper[entry];
+            matrix[column * taps + row] = upper[entry];
+            entry += 1;
+        }
+    }
+    let ridge = (0..taps).map(|i| matrix[i * taps + i]).sum::<f64>() / taps as f64 * RIDGE;
+    if ridge <= 0.0 {
+        return None;
+    }
+    for i in 0..taps {
+        matrix[i * taps + i] += ridge;
+    }
+
+    // Lower triangular factor in place
+    for column in 0..taps {
+        let dot: f64 = (0..column).map(|k| matrix[column * taps + k].powi(2)).sum();
+        let diagonal = matrix[column * taps + column] - dot;
+        if diagonal <= 0.0 {
+            return None;
+        }
+        let diagonal = diagonal.sqrt();
+        matrix[column * taps + column] = diagonal;
+        for row in column + 1..taps {
+            let dot: f64 = (0..column)
+                .map(|k| matrix[row * taps + k] * matrix[column * taps + k])
+                .sum();
+            matrix[row * taps + column] = (matrix[row * taps + column] - dot) / diagonal;
+        }
+    }
+
+    // Forward then backward substitution
+    let mut solution = target.to_vec();
+    for row in 0..taps {
+        let dot: f64 = (0..row).map(|k| matrix[row * taps + k] * solution[k]).sum();
+        solution[row] = (solution[row] - dot) / matrix[row * taps + row];
+    }
+    for row in (0..taps).rev() {
+        let dot: f64 = (row + 1..taps)
+            .map(|k| matrix[k * taps + row] * solution[k])
+            .sum();
+        solution[row] = (solution[row] - dot) / matrix[row * taps + row];
+    }
+    Some(solution.into_iter().map(|value| value as f32).collect())
+}
+
+/// Upscaler applying a [`FilterBank`]
+///
+/// Runs at the bank's factor, other sizes are reached from there with Catmull-Rom.
+pub struct RaisrUpscaler {
+    bank: FilterBank,
+    image: DynamicImage,
+    upscaled_image: DynamicImage,
+    scale: Scale,
+}
+
+impl RaisrUpscaler {
+    pub fn new(bank: FilterBank, scale: impl Into<Scale>) -> Self {
+        let scale = scale.into();
+        let (width, height) = scale.plan((1, 1)).canvas;
+        Self {
+            bank,
+            image: RgbImage::new(1, 1).into(),
+            upscaled_image: RgbImage::new(width, height).into(),
+            scale,
+        }
+    }
+
+    /// Loads a bank saved by [`FilterBank::save`]
+    pub fn from_file(path: impl AsRef<Path>, scale: impl Into<Scale>) -> Result<Self, Error> {
+        Ok(Self::new(FilterBank::load(path)?, scale))
+    }
+
+    pub fn bank(&self) -> &FilterBank {
+        &self.bank
+    }
+}
+
+impl UpscaleImage for RaisrUpscaler {
+    type Error = Error;
+
+    fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error> {
+        self.image = image.clone();
+        Ok(())
+    }
+
+    fn upscale(&self) -> Result<DynamicImage, Self::Error> {
+        let plan = self.plan();
+        let mut upscaled = self.bank.apply(&plan.crop_image(&self.image).to_rgba32f());
+        if upscaled.dimensions() != plan.resized {
+            let resampler = Resampler::new(
+                ResampleKernel::CATMULL_ROM,
+                upscaled.dimensions(),
+                plan.resized,
+            );
+            upscaled = resampler.apply(&upscaled);
+        }
+        Ok(plan.compose(convert(upscaled.into(), self.image.color())))
+    }
+
+    fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error> {
+        self.upscaled_image = self.upscale()?;
+        Ok(&self.upscaled_image)
+    }
+
+    fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error> {
+        for _ in 0..times {
+            self.upscale_inplace()?;
+        }
+        Ok(&self.upscaled_image)
+    }
+
+    fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    fn original_dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn kernel_support(&self) -> u32 {
+        // Filter and gradient window overlap in upscaled pixels, plus bilinear and Catmull-Rom taps
+        let reach = (self.bank.hashing.radius() as u32 * 2 + 1).div_ceil(self.bank.factor());
+        let (x, y) = self.upscale_factors();
+        let resampled = x != y || x != self.bank.factor() as f32;
+        reach + 1 + resampled as u32 * 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Anti-aliased discs and bars at varying angles, rendered at `size`
+    fn shapes(size: u32, seed: u32) -> DynamicImage {
+        let s = size as f32;
+        RgbImage::from_fn(size, size, |x, y| {
+            let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+            let angle = seed as f32 * 0.7;
+            let bar = ((x - s / 2.0) * angle.cos() + (y - s / 2.0) * angle.sin()).abs() - s / 10.0;
+            let disc =
+                ((x - s * 0.3).powi(2) + (y - s * 0.7).powi(2)).sqrt() - s / (5.0 + seed as f32);
+            let coverage = |distance: f32| (0.5 - distance).clamp(0.0, 1.0);
+            let value = 30.0 + 120.0 * coverage(bar) + 90.0 * coverage(disc);
+            image::Rgb([value as u8, value as u8, (value * 0.9) as u8])
+        })
+        .into()
+    }
+
+    fn squared_error(a: &Rgba32FImage, b: &Rgba32FImage) -> f32 {
+        a.pixels()
+            .zip(b.pixels())
+            .map(|(a, b)| (0..3).map(|c| (a[c] - b[c]).powi(2)).sum::<f32>())
+            .sum()
+    }
+
+    #[test]
+    fn identity_bank_is_bilinear() {
+        let image = shapes(24, 1).to_rgba32f();
+        let bank = FilterBank::identity(2).unwrap();
+        assert_eq!(bank.apply(&image), cheap_upscale(&image, 2));
+    }
+
+    #[test]
+    fn file_roundtrip() {
+        let trainer = FilterBank::trainer(2).unwrap().with_patch(5).unwrap();
+        let mut trainer = trainer.with_angles(4).unwrap();
+        trainer.add_image(&shapes(48, 2));
+        let bank = trainer.finish();
+
+        let mut bytes = Vec::new();
+        bank.write_to(&mut bytes).unwrap();
+        assert_eq!(FilterBank::read_from(bytes.as_slice()).unwrap(), bank);
+
+        bytes[0] = b'X';
+        assert!(matches!(
+            FilterBank::read_from(bytes.as_slice()),
+            Err(Error::InvalidFilterBank(_))
+        ));
+        assert!(FilterBank::read_from(&bytes[..40]).is_err());
+    }
+
+    #[test]
+    fn out_of_range_parameters_are_rejected() {
+        for factor in [0, 17] {
+            assert!(matches!(
+                FilterBank::trainer(factor),
+                Err(Error::InvalidFilterBank(_))
+            ));
+        }
+        let trainer = || FilterBank::trainer(2).unwrap();
+        for patch in [1, 4, 17] {
+            assert!(trainer().with_patch(patch).is_err(), "patch {patch}");
+        }
+        for angles in [0, 181] {
+            assert!(trainer().with_angles(angles).is_err(), "{angles} angles");
+        }
+        assert!(trainer().with_patch(15).unwrap().with_angles(180).is_ok());
+    }
+
+    #[test]
+    fn crafted_headers_are_rejected() {
+        let header = |factor: u32, patch: u32, angles: u32| {
+            let mut bytes = MAGIC.to_vec();
+            for value in [factor, patch, angles, 0, 0] {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes
+        };
+        for (factor, patch, angles) in [(2, u32::MAX, 4), (2, 17, 4), (2, 1, 4), (2, 5, u32::MAX)] {
+            assert!(
+                matches!(
+                    FilterBank::read_from(header(factor, patch, angles).as_slice()),
+                    Err(Error::InvalidFilterBank(_))
+                ),
+                "patch {patch}, angles {angles}"
+            );
+        }
+
+        // The largest bank allowed claims gigabytes but ends after its header
+        let largest = header(16, Hashing::MAX_PATCH, Hashing::MAX_ANGLES);
+        assert!(matches!(
+            FilterBank::read_from(largest.as_slice()),
+            Err(Error::IO(_))
+        ));
+    }
+
+    #[test]
+    fn training_beats_bilinear() {
+        let train = |trainer: FilterTrainer| {
+            let mut trainer = trainer.with_patch(5).unwrap();
+            for seed in 0..6 {
+                trainer.add_image(&shapes(64, seed));
+            }
+            assert_eq!(trainer.images(), 6);
+            trainer.finish()
+        };
+        let bank = train(FilterBank::trainer(2).unwrap().with_angles(8).unwrap());
+        let single = FilterBank::trainer(2).unwrap().with_angles(1).unwrap();
+        let single = train(
+            single
+                .with_strength_thresholds(vec![])
+                .with_coherence_thresholds(vec![]),
+        );
+
+        // Held-out images, downscaled the way training does
+        let (mut learned, mut unhashed, mut bilinear) = (0.0, 0.0, 0.0);
+        for seed in 10..15 {
+            let truth = shapes(64, seed).to_rgba32f();
+            let low = Resampler::new(ResampleKernel::CATMULL_ROM, (64, 64), (32, 32)).apply(&truth);
+            learned += squared_error(&bank.apply(&low), &truth);
+            unhashed += squared_error(&single.apply(&low), &truth);
+            bilinear += squared_error(&cheap_upscale(&low, 2), &truth);
+        }
+        assert!(learned < bilinear * 0.6, "{learned} against {bilinear}");
+        // Hashing by structure beats one filter for everything
+        assert!(learned < unhashed, "{learned} against {unhashed}");
+
+        let low = shapes(32, 20);
+        let mut scaler = RaisrUpscaler::new(bank, 3.0);
+        scaler.load(&low).unwrap();
+        assert_eq!(scaler.upscale().unwrap().dimensions(), (96, 96));
+    }
+}
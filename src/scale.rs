@@ -0,0 +1,248 @@
+use image::{imageops, DynamicImage, GenericImageView};
+
+/// Describes how dimensions of an upscaled image are derived from the original ones
+///
+/// Rounding rules:
+/// - factors are applied as `round(side * factor)` with ties away from zero;
+/// - target-size modes use exact integer arithmetic, the binding axis always
+///   matches the target and the other one is rounded the same way;
+/// - centering offsets are floored, so odd leftovers go to the right/bottom;
+/// - every resulting side is at least 1 pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scale {
+    /// Same factor along both axes
+    Uniform(f32),
+
+    /// Independent horizontal and vertical factors
+    Anamorphic { x: f32, y: f32 },
+
+    /// Exactly the target size, aspect ratio is not preserved
+    Stretch { width: u32, height: u32 },
+
+    /// Largest size that fits into the target and keeps aspect ratio
+    Fit { width: u32, height: u32 },
+
+    /// Exactly the target size, source is center-cropped to its aspect ratio
+    Fill { width: u32, height: u32 },
+
+    /// Exactly the target size, [`Scale::Fit`] result is centered on a black canvas
+    Pad { width: u32, height: u32 },
+}
+
+impl From<f32> for Scale {
+    fn from(factor: f32) -> Self {
+        Self::Uniform(factor)
+    }
+}
+
+impl From<(f32, f32)> for Scale {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self::Anamorphic { x, y }
+    }
+}
+
+/// Axis-aligned pixel rectangle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Concrete resampling steps for a [`Scale`] applied to specific dimensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScalePlan {
+    /// Region of the original image that gets resampled
+    pub crop: Rect,
+
+    /// Dimensions the cropped region is resampled to
+    pub resized: (u32, u32),
+
+    /// Dimensions of the final image
+    pub canvas: (u32, u32),
+
+    /// Position of the resampled region on the canvas
+    pub offset: (u32, u32),
+}
+
+fn scale_side(side: u32, factor: f32) -> u32 {
+    ((side as f64 * factor as f64).round() as u32).max(1)
+}
+
+/// `round(a * b / c)` with ties away from zero
+fn mul_div_round(a: u32, b: u32, c: u32) -> u32 {
+    let (a, b, c) = (a as u64, b as u64, c.max(1) as u64);
+    ((2 * a * b + c) / (2 * c)).max(1) as u32
+}
+
+/// Dimensions of `(width, height)` scaled to fit into `(t_width, t_height)`
+fn fit(width: u32, height: u32, t_width: u32, t_height: u32) -> (u32, u32) {
+    if width as u64 * t_height as u64 >= height as u64 * t_width as u64 {
+        (t_width, mul_div_round(height, t_width, width).min(t_height))
+    } else {
+        (
+            mul_div_round(width, t_height, height).min(t_width),
+            t_height,
+        )
+    }
+}
+
+impl Scale {
+    /// Computes resampling steps for an image of given dimensions
+    pub fn plan(&self, (width, height): (u32, u32)) -> ScalePlan {
+        let (width, height) = (width.max(1), height.max(1));
+        let full = Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+
+        let stretched = |resized: (u32, u32)| ScalePlan {
+            crop: full,
+            resized,
+            canvas: resized,
+            offset: (0, 0),
+        };
+
+        match *self {
+            Self::Uniform(factor) => {
+                stretched((scale_side(width, factor), scale_side(height, factor)))
+            }
+            Self::Anamorphic { x, y } => stretched((scale_side(width, x), scale_side(height, y))),
+            Self::Stretch {
+                width: t_width,
+                height: t_height,
+            } => stretched((t_width.max(1), t_height.max(1))),
+            Self::Fit {
+                width: t_width,
+                height: t_height,
+            } => stretched(fit(width, height, t_width.max(1), t_height.max(1))),
+            Self::Fill {
+                width: t_width,
+                height: t_height,
+            } => {
+                let target = (t_width.max(1), t_height.max(1));
+                let (c_width, c_height) = fit(target.0, target.1, width, height);
+                ScalePlan {
+                    crop: Rect {
+                        x: (width - c_width) / 2,
+                        y: (height - c_height) / 2,
+                        width: c_width,
+                        height: c_height,
+                    },
+                    resized: target,
+                    canvas: target,
+                    offset: (0, 0),
+                }
+            }
+            Self::Pad {
+                width: t_width,
+                height: t_height,
+            } => {
+                let canvas = (t_width.max(1), t_height.max(1));
+                let resized = fit(width, height, canvas.0, canvas.1);
+                ScalePlan {
+                    crop: full,
+                    resized,
+                    canvas,
+                    offset: ((canvas.0 - resized.0) / 2, (canvas.1 - resized.1) / 2),
+                }
+            }
+        }
+    }
+}
+
+impl ScalePlan {
+    /// Effective horizontal and vertical scale factors
+    pub fn factors(&self) -> (f32, f32) {
+        (
+            self.resized.0 as f32 / self.crop.width as f32,
+            self.resized.1 as f32 / self.crop.height as f32,
+        )
+    }
+
+    /// Returns `true` if the plan neither crops nor pads
+    pub fn is_stretch(&self) -> bool {
+        self.crop.x == 0 && self.crop.y == 0 && self.resized == self.canvas
+    }
+
+    /// Cuts out the region of `image` that gets resampled
+    pub(crate) fn crop_image(&self, image: &DynamicImage) -> DynamicImage {
+        if image.dimensions() == (self.crop.width, self.crop.height) {
+            image.clone()
+        } else {
+            image.crop_imm(self.crop.x, self.crop.y, self.crop.width, self.crop.height)
+        }
+    }
+
+    /// Places resampled region onto the final canvas
+    pub(crate) fn compose(&self, resized: DynamicImage) -> DynamicImage {
+        if self.resized == self.canvas {
+            return resized;
+        }
+        let mut canvas = DynamicImage::new(self.canvas.0, self.canvas.1, resized.color());
+        imageops::replace(
+            &mut canvas,
+            &resized,
+            self.offset.0 as i64,
+            self.offset.1 as i64,
+        );
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factors_round_half_away_from_zero() {
+        let plan = Scale::Anamorphic {
+            x: 4.0 / 3.0,
+            y: 1.0,
+        }
+        .plan((1440, 1080));
+        assert_eq!(plan.canvas, (1920, 1080));
+
+        let plan = Scale::Uniform(1.5).plan((3, 5));
+        assert_eq!(plan.canvas, (5, 8));
+    }
+
+    #[test]
+    fn fit_fill_pad() {
+        let plan = Scale::Fit {
+            width: 1000,
+            height: 1000,
+        }
+        .plan((1920, 1080));
+        assert_eq!(plan.canvas, (1000, 563));
+
+        let plan = Scale::Pad {
+            width: 1000,
+            height: 1000,
+        }
+        .plan((1920, 1080));
+        assert_eq!(
+            (plan.canvas, plan.resized, plan.offset),
+            ((1000, 1000), (1000, 563), (0, 218))
+        );
+
+        let plan = Scale::Fill {
+            width: 1000,
+            height: 1000,
+        }
+        .plan((1920, 1080));
+        assert_eq!(
+            plan.crop,
+            Rect {
+                x: 420,
+                y: 0,
+                width: 1080,
+                height: 1080
+            }
+        );
+        assert_eq!(plan.canvas, (1000, 1000));
+    }
+}
@@ -0,0 +1,765 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::error::Error;
+
+/// Input name of the loaded image, reserved for it in every graph
+pub const ORIGINAL: &str = "original";
+
+/// Width or height of a pass output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Extent {
+    /// Same as the loaded (cropped) image
+    Source,
+
+    /// Same as the upscaled image
+    Output,
+
+    /// Source side times a factor, rounded and at least 1
+    Scaled(f32),
+}
+
+impl Extent {
+    pub(crate) fn resolve(self, source: u32, output: u32) -> u32 {
+        match self {
+            Self::Source => source,
+            Self::Output => output,
+            Self::Scaled(factor) => ((source as f64 * factor as f64).round() as u32).max(1),
+        }
+    }
+}
+
+/// Fragment shader of a pass
+#[derive(Debug, Clone, PartialEq)]
+pub enum PassShader {
+    /// Shader [loaded](PassShader::load) from a file when the upscaler is created
+    File(PathBuf),
+
+    /// WGSL source code
+    Wgsl(Cow<'static, str>),
+
+    /// Vulkan GLSL source code, translated by naga
+    ///
+    /// Textures and the sampler are declared separately (`texture2D` and `sampler`) and combined at
+    /// use, the `r_uniforms` block is declared by the shader itself as `layout(set = 1, binding =
+    /// 0) uniform`.
+    Glsl(Cow<'static, str>),
+
+    /// SPIR-V module with a `main` fragment entry point, bound like GLSL
+    SpirV(Cow<'static, [u32]>),
+}
+
+/// First word of every SPIR-V module
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+impl PassShader {
+    /// Reads a shader file
+    ///
+    /// SPIR-V is recognized by its magic number or a `.spv` extension, GLSL by a `.glsl`, `.frag`
+    /// or `.fs` extension, anything else is read as WGSL.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let extension = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(str::to_ascii_lowercase);
+        let magic = bytes.first_chunk().map(|&word| u32::from_le_bytes(word));
+        if magic == Some(SPIRV_MAGIC) || extension.as_deref() == Some("spv") {
+            if bytes.len() % 4 != 0 {
+                return Err(Error::ShaderTranslation {
+                    line: 0,
+                    column: 0,
+                    diagnostic: format!("{} isn't a whole number of SPIR-V words", path.display()),
+                });
+            }
+            let words: Vec<u32> = bytes
+                .chunks_exact(4)
+                .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+                .collect();
+            return Ok(Self::SpirV(words.into()));
+        }
+
+        let source = String::from_utf8(bytes)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(match extension.as_deref() {
+            Some("glsl" | "frag" | "fs") => Self::Glsl(source.into()),
+            _ => Self::Wgsl(source.into()),
+        })
+    }
+
+    /// Translates the shader into naga IR validated against `capabilities`, along with its header
+    /// parameters
+    ///
+    /// WGSL gets the `r_uniforms` declaration appended.
+    pub(crate) fn translate(
+        &self,
+        capabilities: naga::valid::Capabilities,
+    ) -> Result<(naga::Module, Vec<ShaderParam>), Error> {
+        let (mut module, params, source) = match self {
+            Self::File(path) => return Self::load(path)?.translate(capabilities),
+            Self::Wgsl(source) => {
+                let params = parse_params(source)?;
+                let source = format!("{source}{}", uniforms_declaration(&params));
+                let module = naga::front::wgsl::parse_str(&source).map_err(|error| {
+                    translation_error(error.location(&source), error.emit_to_string(&source))
+                })?;
+                (module, params, Cow::from(source))
+            }
+            Self::Glsl(source) => {
+                let options = naga::front::glsl::Options::from(naga::ShaderStage::Fragment);
+                let module = naga::front::glsl::Frontend::default()
+                    .parse(&options, source)
+                    .map_err(|errors| {
+                        let location = errors
+                            .errors
+                            .first()
+                            .and_then(|error| error.location(source))
+                            .map(|location| follow_line_directives(source, location));
+                        translation_error(location, errors.emit_to_string(source))
+                    })?;
+                (module, parse_params(source)?, source.clone())
+            }
+            Self::SpirV(words) => {
+                let module = naga::front::spv::parse_u8_slice(
+                    bytemuck::cast_slice(words),
+                    &Default::default(),
+                )
+                .map_err(|error| translation_error(None, error.to_string()))?;
+                (module, Vec::new(), Cow::from(""))
+            }
+        };
+
+        // GLSL and SPIR-V leave sampling unspecified where WGSL vertex outputs default to the pixel
+        // centre
+        for argument in module
+            .entry_points
+            .iter_mut()
+            .flat_map(|entry| &mut entry.function.arguments)
+        {
+            if let Some(naga::Binding::Location {
+                sampling: sampling @ None,
+                ..
+            }) = &mut argument.binding
+            {
+                *sampling = Some(naga::Sampling::Center);
+            }
+        }
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), capabilities)
+            .validate(&module)
+            .map_err(|error| {
+                let location = error.location(&source).map(|location| match self {
+                    Self::Glsl(_) => follow_line_directives(&source, location),
+                    _ => location,
+                });
+                translation_error(location, error.emit_to_string(&source))
+            })?;
+        Ok((module, params))
+    }
+}
+
+/// Shader capabilities of a device, as wgpu grants them when it validates shader modules
+pub(crate) fn device_capabilities(
+    device: &wgpu::Device,
+    adapter: &wgpu::Adapter,
+) -> naga::valid::Capabilities {
+    use naga::valid::Capabilities as Caps;
+    use wgpu::{DownlevelFlags, Features};
+
+    let (features, downlevel) = (
+        device.features(),
+        adapter.get_downlevel_capabilities().flags,
+    );
+    let by_feature = [
+        (Features::PUSH_CONSTANTS, Caps::PUSH_CONSTANT),
+        (Features::SHADER_F64, Caps::FLOAT64),
+        (Features::SHADER_PRIMITIVE_INDEX, Caps::PRIMITIVE_INDEX),
+        (
+            Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+            Caps::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
+                | Caps::SAMPLER_NON_UNIFORM_INDEXING,
+        ),
+        (
+            Features::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
+            Caps::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
+        ),
+        (
+            Features::TEXTURE_FORMAT_16BIT_NORM,
+            Caps::STORAGE_TEXTURE_16BIT_NORM_FORMATS,
+        ),
+        (Features::MULTIVIEW, Caps::MULTIVIEW),
+        (Features::SHADER_EARLY_DEPTH_TEST, Caps::EARLY_DEPTH_TEST),
+        (Features::SHADER_INT64, Caps::SHADER_INT64),
+        (
+            Features::SHADER_INT64_ATOMIC_MIN_MAX,
+            Caps::SHADER_INT64_ATOMIC_MIN_MAX,
+        ),
+        (
+            Features::SHADER_INT64_ATOMIC_ALL_OPS,
+            Caps::SHADER_INT64_ATOMIC_MIN_MAX | Caps::SHADER_INT64_ATOMIC_ALL_OPS,
+        ),
+        (Features::DUAL_SOURCE_BLENDING, Caps::DUAL_SOURCE_BLENDING),
+        (Features::SUBGROUP, Caps::SUBGROUP),
+        (
+            Features::SUBGROUP_VERTEX,
+            Caps::SUBGROUP | Caps::SUBGROUP_VERTEX_STAGE,
+        ),
+        (Features::SUBGROUP_BARRIER, Caps::SUBGROUP_BARRIER),
+    ];
+    let by_downlevel = [
+        (
+            DownlevelFlags::MULTISAMPLED_SHADING,
+            Caps::MULTISAMPLED_SHADING,
+        ),
+        (
+            DownlevelFlags::CUBE_ARRAY_TEXTURES,
+            Caps::CUBE_ARRAY_TEXTURES,
+        ),
+    ];
+
+    let granted = by_feature
+        .into_iter()
+        .filter(|&(feature, _)| features.contains(feature))
+        .map(|(_, capabilities)| capabilities);
+    let granted = granted.chain(
+        by_downlevel
+            .into_iter()
+            .filter(|&(flag, _)| downlevel.contains(flag))
+            .map(|(_, capabilities)| capabilities),
+    );
+    granted.fold(Caps::empty(), |all, capabilities| all | capabilities)
+}
+
+/// Renumbers the line of a GLSL location after the last `#line N` above it, which makes the next
+/// line number `N`
+fn follow_line_directives(
+    source: &str,
+    mut location: naga::SourceLocation,
+) -> naga::SourceLocation {
+    let directive = source
+        .lines()
+        .take(location.line_number.saturating_sub(1) as usize)
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let number = line
+                .trim()
+                .strip_prefix("#line")?
+                .split_whitespace()
+                .next()?;
+            Some((index as u32 + 1, number.parse::<u32>().ok()?))
+        })
+        .last();
+    if let Some((line, number)) = directive {
+        location.line_number = number + location.line_number - line - 1;
+    }
+    location
+}
+
+fn translation_error(location: Option<naga::SourceLocation>, diagnostic: String) -> Error {
+    let (line, column) = location.map_or((0, 0), |location| {
+        (location.line_number, location.line_position)
+    });
+    Error::ShaderTranslation {
+        line,
+        column,
+        diagnostic,
+    }
+}
+
+impl From<&Path> for PassShader {
+    fn from(path: &Path) -> Self {
+        Self::File(path.into())
+    }
+}
+
+impl From<PathBuf> for PassShader {
+    fn from(path: PathBuf) -> Self {
+        Self::File(path)
+    }
+}
+
+/// Runtime parameter declared in a shader header comment as `// @param name = default`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderParam {
+    pub name: String,
+    pub default: f32,
+}
+
+/// Uniform fields the upscaler fills in ahead of the parameters
+const BUILTIN_UNIFORMS: [&str; 3] = ["source_size", "target_size", "scale"];
+
+/// Reads parameters from the comment lines at the top of a WGSL source
+pub fn parse_params(source: &str) -> Result<Vec<ShaderParam>, Error> {
+    let mut params: Vec<ShaderParam> = Vec::new();
+    let header = source
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("//"));
+    for (index, line) in header.enumerate() {
+        let Some(declaration) = line
+            .trim_start_matches('/')
+            .trim_start()
+            .strip_prefix("@param")
+        else {
+            continue;
+        };
+        let invalid =
+            |message: &str| Error::InvalidShaderParam(format!("line {}: {message}", index + 1));
+
+        let (name, default) = declaration
+            .split_once('=')
+            .ok_or_else(|| invalid("expected `name = default`"))?;
+        let name = name.trim();
+        let mut chars = name.chars();
+        let identifier = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !identifier {
+            return Err(invalid(&format!("{name:?} isn't an identifier")));
+        }
+        if BUILTIN_UNIFORMS.contains(&name) || params.iter().any(|param| param.name == name) {
+            return Err(invalid(&format!("{name:?} is already declared")));
+        }
+        let default = default
+            .trim()
+            .parse()
+            .map_err(|_| invalid(&format!("bad default of {name:?}")))?;
+
+        params.push(ShaderParam {
+            name: name.to_string(),
+            default,
+        });
+    }
+    Ok(params)
+}
+
+/// WGSL declaration of `r_uniforms`, appended to pass sources so line numbers stay intact
+fn uniforms_declaration(params: &[ShaderParam]) -> String {
+    let fields: String = params
+        .iter()
+        .map(|param| format!("    {}: f32,\n", param.name))
+        .collect();
+    format!(
+        "\nstruct Uniforms {{\n    source_size: vec4<f32>,\n    target_size: vec4<f32>,\n    \
+         scale: vec2<f32>,\n{fields}}}\n@group(1) @binding(0) var<uniform> r_uniforms: Uniforms;\n"
+    )
+}
+
+/// Byte size of `r_uniforms` with `params` parameters
+pub(crate) fn uniforms_size(params: usize) -> u64 {
+    ((10 + params) as u64 * 4).next_multiple_of(16)
+}
+
+/// Fragment pass of a [`ShaderGraph`]
+///
+/// The shader's `main` entry point samples its first input as `@binding(0)` with the sampler at
+/// `@binding(1)`, further inputs follow from `@binding(2)` on. Without explicit inputs a pass reads
+/// the one before it, or the [original](ORIGINAL) image if it comes first.
+///
+/// Every pass can also read `r_uniforms` at `@group(1) @binding(0)`, declared by the upscaler:
+/// `source_size` and `target_size` hold width, height and their reciprocals of the first input and
+/// the rendered texture, `scale` is the overall upscaling factor, and every
+/// [parameter](parse_params) follows as an `f32` field of the same name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderPass {
+    pub name: String,
+    pub shader: PassShader,
+    pub inputs: Option<Vec<String>>,
+    pub width: Extent,
+    pub height: Extent,
+
+    /// Format of the rendered texture, must be renderable and filterable
+    pub format: wgpu::TextureFormat,
+
+    /// Values of pipeline-overridable constants
+    pub constants: HashMap<String, f64>,
+
+    /// Initial values of header parameters, the rest start at their defaults
+    pub params: HashMap<String, f32>,
+}
+
+impl ShaderPass {
+    /// Creates a pass rendering at output size into an `Rgba16Float` texture
+    pub fn new(name: impl Into<String>, shader: impl Into<PassShader>) -> Self {
+        Self {
+            name: name.into(),
+            shader: shader.into(),
+            inputs: None,
+            width: Extent::Output,
+            height: Extent::Output,
+            format: wgpu::TextureFormat::Rgba16Float,
+            constants: HashMap::new(),
+            params: HashMap::new(),
+        }
+    }
+
+    /// Sets the passes (or [`ORIGINAL`]) bound as inputs, in binding order
+    pub fn with_inputs<S: Into<String>>(mut self, inputs: impl IntoIterator<Item = S>) -> Self {
+        self.inputs = Some(inputs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_size(mut self, width: Extent, height: Extent) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn with_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Overrides the WGSL `override` declaration `name`
+    pub fn with_constant(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.constants.insert(name.into(), value);
+        self
+    }
+
+    /// Sets the initial value of the header parameter `name`
+    pub fn with_param(mut self, name: impl Into<String>, value: f32) -> Self {
+        self.params.insert(name.into(), value);
+        self
+    }
+}
+
+/// Texture with fixed contents, such as a lookup table, that passes can read by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphTexture {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+
+    /// Filterable format of `data`
+    pub format: wgpu::TextureFormat,
+
+    /// Tightly packed rows of texels
+    pub data: Vec<u8>,
+}
+
+/// Input of a resolved pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PassInput {
+    Original,
+    Pass(usize),
+    Texture(usize),
+}
+
+/// Ordered fragment passes, each reading the original image, earlier passes or fixed textures
+///
+/// The last pass renders into the upscaled image, so its size and format are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShaderGraph {
+    passes: Vec<ShaderPass>,
+    textures: Vec<GraphTexture>,
+}
+
+impl ShaderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pass(mut self, pass: ShaderPass) -> Self {
+        self.passes.push(pass);
+        self
+    }
+
+    pub fn with_texture(mut self, texture: GraphTexture) -> Self {
+        self.textures.push(texture);
+        self
+    }
+
+    pub fn passes(&self) -> &[ShaderPass] {
+        &self.passes
+    }
+
+    pub fn textures(&self) -> &[GraphTexture] {
+        &self.textures
+    }
+
+    /// Checks that names are unique and passes only read the original image, textures or earlier
+    /// passes
+    pub fn validate(&self) -> Result<(), Error> {
+        self.resolve().map(|_| ())
+    }
+
+    /// Maps input names of every pass to what they refer to
+    pub(crate) fn resolve(&self) -> Result<Vec<Vec<PassInput>>, Error> {
+        let invalid = Error::InvalidShaderGraph;
+        if self.passes.is_empty() {
+            return Err(invalid("no passes".to_string()));
+        }
+
+        let features = |format: wgpu::TextureFormat| {
+            format.guaranteed_format_features(wgpu::Features::empty())
+        };
+        let mut textures = HashMap::new();
+        for (index, texture) in self.textures.iter().enumerate() {
+            if texture.name == ORIGINAL || textures.insert(texture.name.as_str(), index).is_some() {
+                return Err(invalid(format!("texture name {:?} is taken", texture.name)));
+            }
+            if !features(texture.format)
+                .flags
+                .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+            {
+                return Err(invalid(format!(
+                    "texture {:?} isn't filterable",
+                    texture.name
+                )));
+            }
+            let texel = texture.format.block_copy_size(None).unwrap_or(0) as usize;
+            if texture.data.len() != texture.width as usize * texture.height as usize * texel {
+                return Err(invalid(format!(
+                    "texture {:?} doesn't match its size",
+                    texture.name
+                )));
+            }
+        }
+
+        let mut indices = HashMap::new();
+        let mut resolved = Vec::with_capacity(self.passes.len());
+        for (index, pass) in self.passes.iter().enumerate() {
+            let taken = pass.name == ORIGINAL || textures.contains_key(pass.name.as_str());
+            if taken || indices.contains_key(pass.name.as_str()) {
+                return Err(invalid(format!("pass name {:?} is taken", pass.name)));
+            }
+
+            // The last pass renders into the output texture instead
+            let last = index + 1 == self.passes.len();
+            let features = features(pass.format);
+            let renderable = features
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT);
+            let filterable = features
+                .flags
+                .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE);
+            if !(last || (renderable && filterable)) {
+                return Err(invalid(format!(
+                    "pass {:?} can't render into {:?}",
+                    pass.name, pass.format
+                )));
+            }
+
+            let inputs = match &pass.inputs {
+                Some(names) if names.is_empty() => {
+                    return Err(invalid(format!("pass {:?} has no inputs", pass.name)));
+                }
+                Some(names) => names
+                    .iter()
+                    .map(|name| match indices.get(name.as_str()) {
+                        _ if name == ORIGINAL => Ok(PassInput::Original),
+                        Some(&index) => Ok(PassInput::Pass(index)),
+                        None if textures.contains_key(name.as_str()) => {
+                            Ok(PassInput::Texture(textures[name.as_str()]))
+                        }
+                        None if self.passes.iter().any(|p| &p.name == name) => Err(invalid(
+                            format!("pass {:?} reads {name:?} before it is rendered", pass.name),
+                        )),
+                        None => Err(invalid(format!(
+                            "pass {:?} reads unknown input {name:?}",
+                            pass.name
+                        ))),
+                    })
+                    .collect::<Result<_, _>>()?,
+                None if index == 0 => vec![PassInput::Original],
+                None => vec![PassInput::Pass(index - 1)],
+            };
+
+            indices.insert(pass.name.as_str(), index);
+            resolved.push(inputs);
+        }
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pass(name: &str) -> ShaderPass {
+        ShaderPass::new(name, PassShader::Wgsl(Cow::Borrowed("")))
+    }
+
+    #[test]
+    fn default_inputs_chain() {
+        let graph = ShaderGraph::new()
+            .with_pass(pass("a"))
+            .with_pass(pass("b"))
+            .with_pass(pass("c"));
+        assert_eq!(
+            graph.resolve().unwrap(),
+            [
+                vec![PassInput::Original],
+                vec![PassInput::Pass(0)],
+                vec![PassInput::Pass(1)]
+            ]
+        );
+
+        let lut = GraphTexture {
+            name: "lut".to_string(),
+            width: 2,
+            height: 1,
+            format: wgpu::TextureFormat::Rg8Unorm,
+            data: vec![0; 4],
+        };
+        let graph = graph
+            .with_texture(lut)
+            .with_pass(pass("d").with_inputs(["b", ORIGINAL, "lut", "a"]));
+        assert_eq!(
+            graph.resolve().unwrap()[3],
+            [
+                PassInput::Pass(1),
+                PassInput::Original,
+                PassInput::Texture(0),
+                PassInput::Pass(0)
+            ]
+        );
+    }
+
+    fn texture(name: &str, bytes: usize) -> GraphTexture {
+        GraphTexture {
+            name: name.to_string(),
+            width: 3,
+            height: 1,
+            format: wgpu::TextureFormat::R8Unorm,
+            data: vec![0; bytes],
+        }
+    }
+
+    #[test]
+    fn invalid_graphs() {
+        let invalid = [
+            ShaderGraph::new(),
+            ShaderGraph::new().with_pass(pass("a")).with_pass(pass("a")),
+            ShaderGraph::new().with_pass(pass(ORIGINAL)),
+            ShaderGraph::new()
+                .with_pass(pass("a").with_inputs(["b"]))
+                .with_pass(pass("b")),
+            ShaderGraph::new().with_pass(pass("a").with_inputs(["missing"])),
+            ShaderGraph::new().with_pass(pass("a").with_inputs(Vec::<String>::new())),
+            ShaderGraph::new()
+                .with_pass(pass("a").with_format(wgpu::TextureFormat::Rgba32Uint))
+                .with_pass(pass("b")),
+            ShaderGraph::new()
+                .with_texture(texture("a", 3))
+                .with_pass(pass("a")),
+            ShaderGraph::new()
+                .with_texture(texture("t", 5))
+                .with_pass(pass("a")),
+        ];
+        for graph in invalid {
+            assert!(
+                matches!(graph.validate(), Err(Error::InvalidShaderGraph(_))),
+                "{graph:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn header_params() {
+        let source = "// Sharpener\n//\n// @param sharpness = 0.25\n//   @param ringing=1\n\n\
+                      fn f() {}\n// @param late = 2\n";
+        let params = parse_params(source).unwrap();
+        assert_eq!(
+            params,
+            [
+                ShaderParam {
+                    name: "sharpness".to_string(),
+                    default: 0.25
+                },
+                ShaderParam {
+                    name: "ringing".to_string(),
+                    default: 1.0
+                },
+            ]
+        );
+        let declaration = uniforms_declaration(&params);
+        assert!(declaration.contains("    sharpness: f32,\n    ringing: f32,\n}"));
+        assert_eq!(uniforms_size(0), 48);
+        assert_eq!(uniforms_size(3), 64);
+
+        let invalid = [
+            "// @param 1x = 0",
+            "// @param x",
+            "// @param x = y",
+            "// @param scale = 1",
+            "// @param a = 1\n// @param a = 2",
+        ];
+        for source in invalid {
+            assert!(
+                matches!(parse_params(source), Err(Error::InvalidShaderParam(_))),
+                "{source}"
+            );
+        }
+    }
+
+    fn translation_error(shader: PassShader) -> (u32, u32) {
+        match shader.translate(Default::default()) {
+            Err(Error::ShaderTranslation { line, column, .. }) => (line, column),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn error_locations() {
+        let wgsl =
+            "@fragment\nfn main() -> @location(0) vec4<f32> {\n    return vec4<f32>(missing);\n}\n";
+        assert_eq!(translation_error(PassShader::Wgsl(wgsl.into())), (3, 22));
+
+        let glsl = "#version 450\nlayout(location = 0) out vec4 color;\n\
+                    void main() {\n  color = missing;\n}\n";
+        assert_eq!(translation_error(PassShader::Glsl(glsl.into())), (4, 11));
+        let glsl = "#version 450\nlayout(location = 0) out vec4 color;\n#line 20\n\
+                    void main() {\n  color = missing;\n}\n";
+        assert_eq!(translation_error(PassShader::Glsl(glsl.into())), (21, 11));
+
+        // Parses, but returns the wrong type
+        let wgsl =
+            "@fragment\nfn main() -> @location(0) vec4<f32> {\n    return vec3<f32>(0.0);\n}\n";
+        assert_eq!(translation_error(PassShader::Wgsl(wgsl.into())).0, 3);
+
+        assert_eq!(
+            translation_error(PassShader::SpirV(vec![SPIRV_MAGIC, 0, 0].into())),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn formats_are_detected() {
+        assert!(matches!(
+            PassShader::load("shaders/passthrough.wgsl").unwrap(),
+            PassShader::Wgsl(_)
+        ));
+        assert!(matches!(
+            PassShader::load("shaders/passthrough.frag").unwrap(),
+            PassShader::Glsl(_)
+        ));
+
+        let module =
+            naga::front::wgsl::parse_str(include_str!("../shaders/passthrough.wgsl")).unwrap();
+        let info = naga::valid::Validator::new(Default::default(), Default::default())
+            .validate(&module)
+            .unwrap();
+        let words = naga::back::spv::write_vec(&module, &info, &Default::default(), None).unwrap();
+        let path = std::env::temp_dir().join("scale-benchmarks-passthrough.bin");
+        fs::write(&path, bytemuck::cast_slice(&words)).unwrap();
+        match PassShader::load(&path).unwrap() {
+            PassShader::SpirV(loaded) => assert_eq!(loaded, words),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn extents() {
+        assert_eq!(Extent::Source.resolve(10, 25), 10);
+        assert_eq!(Extent::Output.resolve(10, 25), 25);
+        assert_eq!(Extent::Scaled(1.5).resolve(11, 25), 17);
+        assert_eq!(Extent::Scaled(0.01).resolve(11, 25), 1);
+    }
+}
@@ -0,0 +1,123 @@
+//! Fragment shaders compiled into the crate, selectable by name
+//!
+//! The interpolating shaders lean on the sampler for bilinear taps, so they expect the default
+//! linear filtering of [`GpuShadingConfig`](crate::gpu_shading_cfg::GpuShadingConfig).
+//! Clamp-to-edge addressing repeats the border texels where the kernel leaves the image, except for
+//! Lanczos which renormalizes.
+
+use std::borrow::Cow;
+
+use crate::shader_graph::{PassShader, ShaderPass};
+
+/// Built-in single-pass shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinShader {
+    /// Bilinear interpolation, one linear tap
+    Bilinear,
+    /// Cubic B-spline in four linear taps, smooth without ringing
+    BSpline,
+    /// Catmull-Rom bicubic in nine linear taps
+    CatmullRom,
+    Lanczos2,
+    Lanczos3,
+    /// Integer nearest-neighbour prescale followed by bilinear, for pixel art
+    SharpBilinear,
+    /// Bilinear sharpened by AMD Contrast Adaptive Sharpening, with a `sharpness` parameter from
+    /// 0.0 to 1.0
+    Cas,
+}
+
+impl BuiltinShader {
+    pub const ALL: [Self; 7] = [
+        Self::Bilinear,
+        Self::BSpline,
+        Self::CatmullRom,
+        Self::Lanczos2,
+        Self::Lanczos3,
+        Self::SharpBilinear,
+        Self::Cas,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bilinear => "bilinear",
+            Self::BSpline => "bspline",
+            Self::CatmullRom => "catmull-rom",
+            Self::Lanczos2 => "lanczos2",
+            Self::Lanczos3 => "lanczos3",
+            Self::SharpBilinear => "sharp-bilinear",
+            Self::Cas => "cas",
+        }
+    }
+
+    /// Looks up a shader by its [name](Self::name)
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|shader| shader.name() == name)
+    }
+
+    /// WGSL source, both Lanczos shaders share one with a `lobes` override
+    pub fn source(self) -> &'static str {
+        match self {
+            Self::Bilinear => include_str!("../shaders/bilinear.wgsl"),
+            Self::BSpline => include_str!("../shaders/bicubic_bspline.wgsl"),
+            Self::CatmullRom => include_str!("../shaders/bicubic_catmull_rom.wgsl"),
+            Self::Lanczos2 | Self::Lanczos3 => include_str!("../shaders/lanczos.wgsl"),
+            Self::SharpBilinear => include_str!("../shaders/sharp_bilinear.wgsl"),
+            Self::Cas => include_str!("../shaders/cas.wgsl"),
+        }
+    }
+
+    /// Pass rendering the shader at output size
+    pub fn pass(self) -> ShaderPass {
+        let pass = ShaderPass::new(self.name(), PassShader::Wgsl(Cow::Borrowed(self.source())));
+        match self {
+            Self::Lanczos2 => pass.with_constant("lobes", 2.0),
+            Self::Lanczos3 => pass.with_constant("lobes", 3.0),
+            _ => pass,
+        }
+    }
+
+    /// Source pixels the shader reads around each output pixel
+    pub fn kernel_support(self) -> u32 {
+        match self {
+            Self::Bilinear | Self::SharpBilinear => 1,
+            Self::BSpline | Self::CatmullRom | Self::Lanczos2 => 2,
+            // Linear taps a texel away
+            Self::Cas => 2,
+            Self::Lanczos3 => 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for shader in BuiltinShader::ALL {
+            assert_eq!(BuiltinShader::from_name(shader.name()), Some(shader));
+        }
+        assert_eq!(BuiltinShader::from_name("lanczos4"), None);
+    }
+
+    #[test]
+    fn sources_translate() {
+        for shader in BuiltinShader::ALL {
+            let (_, params) = shader.pass().shader.translate(Default::default()).unwrap();
+            let expected: &[&str] = if shader == BuiltinShader::Cas {
+                &["sharpness"]
+            } else {
+                &[]
+            };
+            assert_eq!(
+                params
+                    .iter()
+                    .map(|param| param.name.as_str())
+                    .collect::<Vec<_>>(),
+                expected,
+                "{shader:?}"
+            );
+        }
+    }
+}
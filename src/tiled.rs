@@ -0,0 +1,592 @@
+//! Tiled upscaling of images that don't fit into memory or backend limits
+//!
+//! [`TiledUpscaler`] wraps any [`UpscaleImage`] and feeds it one tile at a time,
+//! with a margin of [`UpscaleImage::kernel_support`] source pixels around each
+//! tile that is cut off the upscaled result. Source rows are read and upscaled
+//! rows are written in bands one tile high, so only a few bands are ever held.
+//!
+//! Tile edges fall on multiples of the factor's period, the fewest source pixels
+//! that upscale to whole pixels, so every tile samples the whole image's grid.
+//! Sides that aren't a multiple of it end in a partial tile, and factors without
+//! a short period are tiled on single pixels. Those tiles are placed and sized by
+//! the whole image's plan, their sampling phase may drift from it by a fraction
+//! of a source pixel.
+
+use std::io::{self, BufRead, Seek, Write};
+
+use image::{imageops, ColorType, DynamicImage, GenericImageView};
+
+use crate::{
+    error::Error,
+    scale::Scale,
+    tiling::{convert, pad_edges},
+    upscaler::UpscaleImage,
+};
+
+/// Image read from top to bottom in bands of rows
+pub trait RowSource {
+    /// Returns `(width, height)` of the whole image
+    fn dimensions(&self) -> (u32, u32);
+
+    /// Reads up to `rows` next rows
+    fn read_rows(&mut self, rows: u32) -> Result<DynamicImage, Error>;
+}
+
+/// Image written from top to bottom in bands of rows
+pub trait RowSink {
+    /// Called once before the first band with dimensions of the whole image
+    fn begin(&mut self, dimensions: (u32, u32), color: ColorType) -> Result<(), Error>;
+
+    /// Appends a band of rows
+    fn write_rows(&mut self, rows: &DynamicImage) -> Result<(), Error>;
+
+    /// Called once after the last band
+    fn finish(&mut self) -> Result<(), Error>;
+}
+
+/// Rows of an image already in memory
+#[derive(Debug, Clone)]
+pub struct ImageRows<'a> {
+    image: &'a DynamicImage,
+    next: u32,
+}
+
+impl<'a> ImageRows<'a> {
+    pub fn new(image: &'a DynamicImage) -> Self {
+        Self { image, next: 0 }
+    }
+}
+
+impl RowSource for ImageRows<'_> {
+    fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn read_rows(&mut self, rows: u32) -> Result<DynamicImage, Error> {
+        let rows = rows.min(self.image.height() - self.next);
+        let band = self.image.crop_imm(0, self.next, self.image.width(), rows);
+        self.next += rows;
+        Ok(band)
+    }
+}
+
+/// Collects rows into an image in memory
+#[derive(Debug, Clone, Default)]
+pub struct ImageSink {
+    image: DynamicImage,
+    next: u32,
+}
+
+impl ImageSink {
+    pub fn into_image(self) -> DynamicImage {
+        self.image
+    }
+}
+
+impl RowSink for ImageSink {
+    fn begin(&mut self, (width, height): (u32, u32), color: ColorType) -> Result<(), Error> {
+        self.image = DynamicImage::new(width, height, color);
+        self.next = 0;
+        Ok(())
+    }
+
+    fn write_rows(&mut self, rows: &DynamicImage) -> Result<(), Error> {
+        imageops::replace(&mut self.image, rows, 0, self.next as i64);
+        self.next += rows.height();
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// 8-bit image from raw interleaved samples
+fn from_raw(width: u32, height: u32, color: ColorType, samples: Vec<u8>) -> Option<DynamicImage> {
+    Some(match color {
+        ColorType::L8 => {
+            DynamicImage::ImageLuma8(image::ImageBuffer::from_raw(width, height, samples)?)
+        }
+        ColorType::La8 => {
+            DynamicImage::ImageLumaA8(image::ImageBuffer::from_raw(width, height, samples)?)
+        }
+        ColorType::Rgb8 => {
+            DynamicImage::ImageRgb8(image::ImageBuffer::from_raw(width, height, samples)?)
+        }
+        _ => DynamicImage::ImageRgba8(image::ImageBuffer::from_raw(width, height, samples)?),
+    })
+}
+
+/// Rows of a non-interlaced PNG decoded on demand, samples are reduced to 8 bits
+pub struct PngRows<R: BufRead + Seek> {
+    reader: png::Reader<R>,
+    color: ColorType,
+    next: u32,
+}
+
+impl<R: BufRead + Seek> PngRows<R> {
+    pub fn new(reader: R) -> Result<Self, Error> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let reader = decoder.read_info()?;
+        if reader.info().interlaced {
+            return Err(Error::InterlacedImage);
+        }
+
+        let color = match reader.output_color_type().0 {
+            png::ColorType::Grayscale => ColorType::L8,
+            png::ColorType::GrayscaleAlpha => ColorType::La8,
+            png::ColorType::Rgb | png::ColorType::Indexed => ColorType::Rgb8,
+            png::ColorType::Rgba => ColorType::Rgba8,
+        };
+        Ok(Self {
+            reader,
+            color,
+            next: 0,
+        })
+    }
+}
+
+impl<R: BufRead + Seek> RowSource for PngRows<R> {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.reader.info().width, self.reader.info().height)
+    }
+
+    fn read_rows(&mut self, rows: u32) -> Result<DynamicImage, Error> {
+        let (width, height) = self.dimensions();
+        let rows = rows.min(height - self.next);
+
+        let mut samples = Vec::with_capacity(
+            rows as usize * width as usize * self.color.bytes_per_pixel() as usize,
+        );
+        for _ in 0..rows {
+            let row = self
+                .reader
+                .next_row()?
+                .ok_or(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            samples.extend_from_slice(row.data());
+        }
+        self.next += rows;
+
+        from_raw(width, rows, self.color, samples).ok_or(Error::MalformedOutput)
+    }
+}
+
+enum PngState<W: Write + 'static> {
+    Ready(W),
+    Writing(Box<png::StreamWriter<'static, W>>),
+    Done,
+}
+
+/// Encodes rows into an 8-bit PNG as they arrive
+pub struct PngSink<W: Write + 'static> {
+    state: PngState<W>,
+    color: ColorType,
+}
+
+impl<W: Write + 'static> PngSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            state: PngState::Ready(writer),
+            color: ColorType::Rgba8,
+        }
+    }
+}
+
+impl<W: Write + 'static> RowSink for PngSink<W> {
+    fn begin(&mut self, (width, height): (u32, u32), color: ColorType) -> Result<(), Error> {
+        let PngState::Ready(writer) = std::mem::replace(&mut self.state, PngState::Done) else {
+            return Err(io::Error::other("png sink has already begun").into());
+        };
+
+        let (color, png_color) = match (color.has_color(), color.has_alpha()) {
+            (false, false) => (ColorType::L8, png::ColorType::Grayscale),
+            (false, true) => (ColorType::La8, png::ColorType::GrayscaleAlpha),
+            (true, false) => (ColorType::Rgb8, png::ColorType::Rgb),
+            (true, true) => (ColorType::Rgba8, png::ColorType::Rgba),
+        };
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png_color);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        self.state = PngState::Writing(Box::new(encoder.write_header()?.into_stream_writer()?));
+        self.color = color;
+        Ok(())
+    }
+
+    fn write_rows(&mut self, rows: &DynamicImage) -> Result<(), Error> {
+        let PngState::Writing(writer) = &mut self.state else {
+            return Err(io::Error::other("png sink is not writing").into());
+        };
+        writer.write_all(convert(rows.clone(), self.color).as_bytes())?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let PngState::Writing(writer) = std::mem::replace(&mut self.state, PngState::Done) {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// `(start, length)` spans of at most `tile` splitting `length` without overlap
+fn partition(length: u32, tile: u32) -> impl Iterator<Item = (u32, u32)> {
+    (0..length.div_ceil(tile)).map(move |i| (i * tile, tile.min(length - i * tile)))
+}
+
+/// Position of a source coordinate on an axis of `side` pixels upscaled to `upscaled` pixels
+fn project(position: u32, side: u32, upscaled: u32) -> u32 {
+    (position as f64 * upscaled as f64 / side as f64).round() as u32
+}
+
+/// Fewest source pixels that upscale to a whole number of pixels, if there are few enough
+fn period(factor: f32) -> Option<u32> {
+    (1..=Tiling::MAX_PERIOD).find(|&period| {
+        let pixels = period as f64 * factor as f64;
+        (pixels - pixels.round()).abs() < 1e-4
+    })
+}
+
+/// Tile side and margin along one axis, both multiples of the factor's period
+#[derive(Debug, Clone, Copy)]
+struct Tiling {
+    tile: u32,
+    margin: u32,
+}
+
+impl Tiling {
+    /// Largest period tiles are aligned to, factors with longer ones are tiled on single pixels
+    const MAX_PERIOD: u32 = 64;
+
+    /// Aligns `tile` and `margin` for an axis scaled by `factor`
+    ///
+    /// The aligned tile with both margins is no larger than the unaligned one
+    /// unless that is below a single period.
+    fn new(tile: u32, margin: u32, factor: f32) -> Self {
+        let period = period(factor).unwrap_or(1);
+        let aligned = margin.div_ceil(period) * period;
+        let tile = (tile + 2 * margin).saturating_sub(2 * aligned) / period * period;
+        Self {
+            tile: tile.max(period),
+            margin: aligned,
+        }
+    }
+}
+
+/// Stacks `bottom` under `top`
+fn stack(top: &DynamicImage, bottom: &DynamicImage) -> DynamicImage {
+    let mut stacked = DynamicImage::new(top.width(), top.height() + bottom.height(), top.color());
+    imageops::replace(&mut stacked, top, 0, 0);
+    imageops::replace(&mut stacked, bottom, 0, top.height() as i64);
+    stacked
+}
+
+/// Upscales images tile by tile with any upscaler
+///
+/// The wrapped upscaler has to use [`Scale::Uniform`] or [`Scale::Anamorphic`],
+/// target-size modes depend on the whole image. Tiles are shrunk to fit into
+/// [`UpscaleImage::max_dimension`] of the upscaler together with their margins.
+/// Fractional factors match the whole image exactly on sides divisible by their
+/// period, e.g. even sides for 1.5x and 2.5x and multiples of 10 for 3.3x.
+#[derive(Debug, Clone)]
+pub struct TiledUpscaler<U> {
+    upscaler: U,
+    tile_size: u32,
+}
+
+impl<U> TiledUpscaler<U>
+where
+    U: UpscaleImage,
+    U::Error: From<Error>,
+{
+    /// Tile side in source pixels unless set otherwise
+    pub const DEFAULT_TILE_SIZE: u32 = 512;
+
+    pub fn new(upscaler: U) -> Self {
+        Self {
+            upscaler,
+            tile_size: Self::DEFAULT_TILE_SIZE,
+        }
+    }
+
+    /// Sets the largest tile side in source pixels, margins excluded
+    pub fn with_tile_size(mut self, side: u32) -> Self {
+        self.tile_size = side.max(1);
+        self
+    }
+
+    pub fn inner(&self) -> &U {
+        &self.upscaler
+    }
+
+    pub fn into_inner(self) -> U {
+        self.upscaler
+    }
+
+    /// Returns horizontal and vertical factors of the wrapped upscaler
+    pub fn factors(&self) -> Result<(f32, f32), Error> {
+        match self.upscaler.scale() {
+            Scale::Uniform(factor) => Ok((factor, factor)),
+            Scale::Anamorphic { x, y } => Ok((x, y)),
+            scale => Err(Error::UnsupportedTiledScale(scale)),
+        }
+    }
+
+    /// Returns tile side in source pixels after applying limits of the wrapped upscaler
+    pub fn tile_side(&self) -> Result<u32, Error> {
+        let (x, y) = self.factors()?;
+        let side = match self.upscaler.max_dimension() {
+            Some(max) => {
+                let input = (max as f32 / x.max(y).max(1.0)).floor() as u32;
+                self.tile_size
+                    .min(input.saturating_sub(2 * self.upscaler.kernel_support()))
+            }
+            None => self.tile_size,
+        };
+        Ok(side.max(1))
+    }
+
+    /// Upscales every row of `source` into `sink`
+    pub fn run(
+        &mut self,
+        source: &mut impl RowSource,
+        sink: &mut impl RowSink,
+    ) -> Result<(), U::Error> {
+        let (x_factor, y_factor) = self.factors()?;
+        let (width, height) = source.dimensions();
+        let (out_width, out_height) = self.upscaler.scale().plan((width, height)).canvas;
+        let (tile, margin) = (self.tile_side()?, self.upscaler.kernel_support());
+        let columns_tiling = Tiling::new(tile, margin, x_factor);
+        let rows_tiling = Tiling::new(tile, margin, y_factor);
+
+        // Source rows currently in memory, starting at `window_start`
+        let mut window = source.read_rows(0)?;
+        let mut window_start = 0;
+        let mut began = false;
+
+        for (y, rows) in partition(height, rows_tiling.tile) {
+            let context_top = y.saturating_sub(rows_tiling.margin);
+            let context_bottom = (y + rows + rows_tiling.margin).min(height);
+
+            let window_end = window_start + window.height();
+            window = window.crop_imm(
+                0,
+                context_top - window_start,
+                width,
+                window_end - context_top,
+            );
+            if context_bottom > window_end {
+                window = stack(&window, &source.read_rows(context_bottom - window_end)?);
+            }
+            window_start = context_top;
+
+            let out_top = project(y, height, out_height);
+            let out_rows = project(y + rows, height, out_height) - out_top;
+            let mut band: Option<DynamicImage> = None;
+
+            for (x, columns) in partition(width, columns_tiling.tile) {
+                let context_left = x.saturating_sub(columns_tiling.margin);
+                let context_right = (x + columns + columns_tiling.margin).min(width);
+                let context = window.crop_imm(
+                    context_left,
+                    0,
+                    context_right - context_left,
+                    window.height(),
+                );
+
+                self.upscaler.load(&context)?;
+                let upscaled = self.upscaler.upscale()?;
+
+                // Crops by the tile's own grid, which rounding may leave short of the plan
+                let out_left = project(x, width, out_width);
+                let out_columns = project(x + columns, width, out_width) - out_left;
+                let crop_left = project(x - context_left, context.width(), upscaled.width());
+                let crop_top = project(y - context_top, context.height(), upscaled.height());
+                let part = upscaled.crop_imm(crop_left, crop_top, out_columns, out_rows);
+                let part = pad_edges(&part, (out_columns, out_rows));
+
+                let band = band
+                    .get_or_insert_with(|| DynamicImage::new(out_width, out_rows, part.color()));
+                imageops::replace(band, &part, out_left as i64, 0);
+            }
+
+            if let Some(band) = band {
+                if !began {
+                    sink.begin((out_width, out_height), band.color())?;
+                    began = true;
+                }
+                sink.write_rows(&band)?;
+            }
+        }
+
+        sink.finish()?;
+        Ok(())
+    }
+
+    /// Upscales an image in memory tile by tile
+    pub fn upscale_image(&mut self, image: &DynamicImage) -> Result<DynamicImage, U::Error> {
+        let mut sink = ImageSink::default();
+        self.run(&mut ImageRows::new(image), &mut sink)?;
+        Ok(sink.into_image())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{imageops::FilterType, RgbImage};
+
+    use super::*;
+    use crate::cpu_algo::CPUAlgoUpscaler;
+
+    fn pattern(width: u32, height: u32) -> DynamicImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x * 7 + y * 3) as u8, (x * y) as u8, ((x ^ y) * 5) as u8])
+        })
+        .into()
+    }
+
+    fn whole(scale: impl Into<Scale>, filter: FilterType, image: &DynamicImage) -> DynamicImage {
+        let mut scaler = CPUAlgoUpscaler::new(scale, filter);
+        scaler.load(image).unwrap();
+        scaler.upscale().unwrap()
+    }
+
+    fn max_difference(a: &DynamicImage, b: &DynamicImage) -> u8 {
+        assert_eq!(a.dimensions(), b.dimensions());
+        let (a, b) = (a.to_rgb8(), b.to_rgb8());
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn tiles_match_whole_image() {
+        let image = pattern(53, 41);
+        // Sampling positions of non-power-of-two factors may round differently in tiles
+        for (scale, filter, tolerance) in [
+            (Scale::Uniform(2.0), FilterType::Nearest, 0),
+            (Scale::Uniform(2.0), FilterType::CatmullRom, 0),
+            (
+                Scale::Anamorphic { x: 3.0, y: 2.0 },
+                FilterType::Lanczos3,
+                1,
+            ),
+        ] {
+            let mut tiled =
+                TiledUpscaler::new(CPUAlgoUpscaler::new(scale, filter)).with_tile_size(16);
+            let difference = max_difference(
+                &tiled.upscale_image(&image).unwrap(),
+                &whole(scale, filter, &image),
+            );
+            assert!(
+                difference <= tolerance,
+                "{scale:?} {filter:?} differs by {difference}"
+            );
+        }
+    }
+
+    #[test]
+    fn fractional_factors_match_whole_image() {
+        let image = pattern(54, 40);
+        for (scale, filter) in [
+            (Scale::Uniform(1.5), FilterType::Triangle),
+            (Scale::Uniform(2.5), FilterType::Triangle),
+            (Scale::Uniform(2.5), FilterType::CatmullRom),
+            (Scale::Anamorphic { x: 1.5, y: 2.5 }, FilterType::Lanczos3),
+        ] {
+            let mut tiled =
+                TiledUpscaler::new(CPUAlgoUpscaler::new(scale, filter)).with_tile_size(16);
+            let difference = max_difference(
+                &tiled.upscale_image(&image).unwrap(),
+                &whole(scale, filter, &image),
+            );
+            assert!(
+                difference <= 1,
+                "{scale:?} {filter:?} differs by {difference}"
+            );
+        }
+    }
+
+    #[test]
+    fn unaligned_sides_end_in_partial_tiles() {
+        let image = pattern(53, 41);
+        let aligned = image.crop_imm(0, 0, 52, 40);
+        for (scale, filter) in [
+            (Scale::Uniform(1.5), FilterType::Triangle),
+            (Scale::Uniform(2.5), FilterType::CatmullRom),
+            (Scale::Anamorphic { x: 1.5, y: 2.5 }, FilterType::Lanczos3),
+        ] {
+            let mut tiled =
+                TiledUpscaler::new(CPUAlgoUpscaler::new(scale, filter)).with_tile_size(16);
+            let tiled = tiled.upscale_image(&image).unwrap();
+            assert_eq!(tiled.dimensions(), scale.plan(image.dimensions()).canvas);
+
+            // Tiles before the partial ones sample the grid of the aligned part
+            let (x, y) = TiledUpscaler::new(CPUAlgoUpscaler::new(scale, filter))
+                .factors()
+                .unwrap();
+            let (width, height) = ((28.0 * x) as u32, (28.0 * y) as u32);
+            let difference = max_difference(
+                &tiled.crop_imm(0, 0, width, height),
+                &whole(scale, filter, &aligned).crop_imm(0, 0, width, height),
+            );
+            assert!(
+                difference <= 1,
+                "{scale:?} {filter:?} differs by {difference}"
+            );
+        }
+
+        // Sides unaligned to long periods, and factors without one, still take the plan's size
+        for scale in [
+            Scale::Uniform(1.0 + 1.0 / 97.0),
+            Scale::Anamorphic { x: 3.3, y: 1.5 },
+        ] {
+            let mut tiled = TiledUpscaler::new(CPUAlgoUpscaler::new(scale, FilterType::Triangle))
+                .with_tile_size(16);
+            let tiled = tiled.upscale_image(&image).unwrap();
+            assert_eq!(tiled.dimensions(), scale.plan(image.dimensions()).canvas);
+        }
+    }
+
+    #[test]
+    fn png_streaming() {
+        let image = pattern(37, 29);
+        let mut encoded = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut encoded), image::ImageFormat::Png)
+            .unwrap();
+
+        let path = std::env::temp_dir().join("scale-benchmarks-tiled.png");
+        let mut source = PngRows::new(Cursor::new(encoded)).unwrap();
+        let mut sink = PngSink::new(std::fs::File::create(&path).unwrap());
+        let mut tiled =
+            TiledUpscaler::new(CPUAlgoUpscaler::new(2.0, FilterType::Triangle)).with_tile_size(8);
+        tiled.run(&mut source, &mut sink).unwrap();
+
+        let decoded = image::open(&path).unwrap();
+        assert_eq!(
+            decoded.to_rgb8(),
+            whole(2.0, FilterType::Triangle, &image).to_rgb8()
+        );
+    }
+
+    #[test]
+    fn target_size_is_rejected() {
+        let scaler = CPUAlgoUpscaler::new(
+            Scale::Fit {
+                width: 64,
+                height: 64,
+            },
+            FilterType::Nearest,
+        );
+        assert!(matches!(
+            TiledUpscaler::new(scaler).tile_side(),
+            Err(Error::UnsupportedTiledScale(_))
+        ));
+    }
+}
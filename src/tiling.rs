@@ -0,0 +1,258 @@
+use image::{ColorType, DynamicImage, GenericImageView, Rgba32FImage};
+
+use crate::scale::Rect;
+
+/// Placement of overlapping tiles along one axis as `(start, length)` spans
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AxisTiles(Vec<(u32, u32)>);
+
+impl AxisTiles {
+    /// Covers `length` with the fewest `tile`-long spans overlapping by at least `overlap`
+    ///
+    /// Spans are spread evenly, so actual overlaps may be larger than requested,
+    /// overlap is capped at half a tile. An axis no longer than `tile` gets a
+    /// single span of its own length.
+    pub fn new(length: u32, tile: u32, overlap: u32) -> Self {
+        let tile = tile.max(1);
+        if length <= tile {
+            return Self(vec![(0, length)]);
+        }
+
+        let overlap = overlap.min(tile / 2);
+        let count = (length - overlap).div_ceil(tile - overlap) as u64;
+        let last = (length - tile) as u64;
+        Self(
+            (0..count)
+                .map(|i| {
+                    (
+                        ((2 * i * last + count - 1) / (2 * (count - 1))) as u32,
+                        tile,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Maps spans onto an axis scaled by `factor` and clipped to `length`
+    pub fn scaled(&self, factor: f32, length: u32) -> Self {
+        let map = |position: u32| ((position as f64 * factor as f64).round() as u32).min(length);
+        Self(
+            self.0
+                .iter()
+                .map(|&(start, span)| (map(start), map(start + span) - map(start)))
+                .collect(),
+        )
+    }
+
+    pub fn spans(&self) -> &[(u32, u32)] {
+        &self.0
+    }
+
+    /// Blending weights of span `index`, linear ramps over overlaps with its neighbours
+    pub fn weights(&self, index: usize) -> Vec<f32> {
+        let (start, length) = self.0[index];
+        let end = start + length;
+
+        let left = match index {
+            0 => 0,
+            _ => (self.0[index - 1].0 + self.0[index - 1].1).saturating_sub(start),
+        };
+        let right = match self.0.get(index + 1) {
+            Some(&(next, _)) => end.saturating_sub(next),
+            None => 0,
+        };
+        let ramp = |distance: u32, overlap: u32| match overlap {
+            0 => 1.0,
+            _ => ((distance as f32 + 0.5) / overlap as f32).min(1.0),
+        };
+
+        (0..length)
+            .map(|offset| ramp(offset, left) * ramp(length - 1 - offset, right))
+            .collect()
+    }
+}
+
+/// Row-major grid of overlapping tiles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileGrid {
+    pub columns: AxisTiles,
+    pub rows: AxisTiles,
+}
+
+impl TileGrid {
+    pub fn new(
+        (width, height): (u32, u32),
+        (tile_width, tile_height): (u32, u32),
+        overlap: u32,
+    ) -> Self {
+        Self {
+            columns: AxisTiles::new(width, tile_width, overlap),
+            rows: AxisTiles::new(height, tile_height, overlap),
+        }
+    }
+
+    /// Maps the grid onto an image scaled by `factor` with given dimensions
+    pub fn scaled(&self, factor: f32, (width, height): (u32, u32)) -> Self {
+        Self {
+            columns: self.columns.scaled(factor, width),
+            rows: self.rows.scaled(factor, height),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns.0.len() * self.rows.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Tile rectangles in row-major order
+    pub fn tiles(&self) -> impl Iterator<Item = Rect> + '_ {
+        self.rows.0.iter().flat_map(|&(y, height)| {
+            self.columns.0.iter().map(move |&(x, width)| Rect {
+                x,
+                y,
+                width,
+                height,
+            })
+        })
+    }
+}
+
+/// Accumulates overlapping tiles into a seamless image with feathered transitions
+#[derive(Debug)]
+pub struct FeatherBlender {
+    grid: TileGrid,
+    width: u32,
+    sum: Vec<[f32; 4]>,
+    weight: Vec<f32>,
+}
+
+impl FeatherBlender {
+    /// Creates a blender for tiles of `grid` (in output coordinates) covering `(width, height)`
+    pub fn new(grid: TileGrid, (width, height): (u32, u32)) -> Self {
+        let pixels = width as usize * height as usize;
+        Self {
+            grid,
+            width,
+            sum: vec![[0.0; 4]; pixels],
+            weight: vec![0.0; pixels],
+        }
+    }
+
+    /// Adds upscaled tile `index` in row-major order, pixels beyond its span are ignored
+    pub fn add(&mut self, index: usize, tile: &DynamicImage) {
+        let columns = self.grid.columns.spans().len();
+        let (row, column) = (index / columns, index % columns);
+        let (x0, width) = self.grid.columns.spans()[column];
+        let (y0, height) = self.grid.rows.spans()[row];
+        let x_weights = self.grid.columns.weights(column);
+        let y_weights = self.grid.rows.weights(row);
+
+        let tile = tile.to_rgba32f();
+        for y in 0..height.min(tile.height()) {
+            for x in 0..width.min(tile.width()) {
+                let weight = x_weights[x as usize] * y_weights[y as usize];
+                let index = (y0 + y) as usize * self.width as usize + (x0 + x) as usize;
+                let pixel = tile.get_pixel(x, y).0;
+                for (sum, value) in self.sum[index].iter_mut().zip(pixel) {
+                    *sum += value * weight;
+                }
+                self.weight[index] += weight;
+            }
+        }
+    }
+
+    /// Normalizes accumulated tiles into an image of given colour type
+    pub fn finish(self, color: ColorType) -> DynamicImage {
+        let height = (self.weight.len() / self.width.max(1) as usize) as u32;
+        let mut pixels = Vec::with_capacity(self.sum.len() * 4);
+        for (sum, weight) in self.sum.into_iter().zip(self.weight) {
+            let weight = if weight > 0.0 { weight } else { 1.0 };
+            pixels.extend(sum.map(|value| value / weight));
+        }
+
+        let blended =
+            Rgba32FImage::from_raw(self.width, height, pixels).expect("buffer matches dimensions");
+        convert(DynamicImage::ImageRgba32F(blended), color)
+    }
+}
+
+/// Converts `image` into colour type `color`
+pub(crate) fn convert(image: DynamicImage, color: ColorType) -> DynamicImage {
+    if image.color() == color {
+        return image;
+    }
+    match color {
+        ColorType::L8 => image.to_luma8().into(),
+        ColorType::La8 => image.to_luma_alpha8().into(),
+        ColorType::Rgb8 => image.to_rgb8().into(),
+        ColorType::L16 => image.to_luma16().into(),
+        ColorType::La16 => image.to_luma_alpha16().into(),
+        ColorType::Rgb16 => image.to_rgb16().into(),
+        ColorType::Rgba16 => image.to_rgba16().into(),
+        ColorType::Rgb32F => image.to_rgb32f().into(),
+        ColorType::Rgba32F => image.to_rgba32f().into(),
+        _ => image.to_rgba8().into(),
+    }
+}
+
+/// Edge-replicating extension of `image` to `(width, height)`
+pub fn pad_edges(image: &DynamicImage, (width, height): (u32, u32)) -> DynamicImage {
+    if image.dimensions() == (width, height) {
+        return image.clone();
+    }
+    let mut padded = DynamicImage::new(width, height, image.color());
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = image.get_pixel(x.min(image.width() - 1), y.min(image.height() - 1));
+            image::GenericImage::put_pixel(&mut padded, x, y, pixel);
+        }
+    }
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axis_tiles_cover_with_overlap() {
+        let tiles = AxisTiles::new(100, 32, 8);
+        let spans = tiles.spans();
+        assert_eq!(spans.first(), Some(&(0, 32)));
+        assert_eq!(spans.last(), Some(&(68, 32)));
+        for pair in spans.windows(2) {
+            assert!(pair[0].0 + pair[0].1 >= pair[1].0 + 8);
+        }
+
+        assert_eq!(AxisTiles::new(20, 32, 8).spans(), &[(0, 20)]);
+    }
+
+    #[test]
+    fn weights_cross_fade() {
+        let tiles = AxisTiles::new(48, 32, 16);
+        let (first, second) = (tiles.weights(0), tiles.weights(1));
+        for offset in 16..32 {
+            assert!((first[offset] + second[offset - 16] - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(first[0], 1.0);
+        assert_eq!(second[31], 1.0);
+    }
+
+    #[test]
+    fn constant_image_is_seam_free() {
+        let color = image::Rgb([200, 37, 91]);
+        let tile = DynamicImage::from(image::RgbImage::from_pixel(32, 32, color));
+        let grid = TileGrid::new((100, 70), (32, 32), 6);
+
+        let mut blender = FeatherBlender::new(grid.clone(), (100, 70));
+        for index in 0..grid.len() {
+            blender.add(index, &tile);
+        }
+
+        let blended = blender.finish(ColorType::Rgb8).to_rgb8();
+        assert!(blended.pixels().all(|pixel| *pixel == color));
+    }
+}
@@ -1,6 +1,66 @@
 use image::DynamicImage;
 
+use crate::{
+    error::Error,
+    scale::{Scale, ScalePlan},
+};
+
+/// Allocation-effecient upscaling of images of arbitrary dimensions
+pub trait UpscaleImage {
+    type Error;
+
+    /// Stores an image in an optimal way, prepares it for upscaling
+    fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error>;
+
+    /// Upscales currently loaded image and returns it
+    fn upscale(&self) -> Result<DynamicImage, Self::Error>;
+
+    /// Upscales currently loaded image into Self's field
+    fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error>;
+
+    /// Returns the requested scaling
+    fn scale(&self) -> Scale;
+
+    /// Returns `(width, height)` of the currently loaded image
+    fn original_dimensions(&self) -> (u32, u32);
+
+    /// Returns resampling steps for the currently loaded image
+    fn plan(&self) -> ScalePlan {
+        self.scale().plan(self.original_dimensions())
+    }
+
+    /// Returns effective horizontal and vertical upscaling factors
+    fn upscale_factors(&self) -> (f32, f32) {
+        self.plan().factors()
+    }
+
+    /// Returns post-upscale `(width, height)` of the currently loaded image
+    fn upscaled_dimensions(&self) -> (u32, u32) {
+        self.plan().canvas
+    }
+
+    /// Returns how many source pixels around a region affect its upscaled pixels
+    ///
+    /// Used as tile margin by [`TiledUpscaler`](crate::tiled::TiledUpscaler).
+    fn kernel_support(&self) -> u32 {
+        0
+    }
+
+    /// Returns the largest input or output side the upscaler can handle
+    fn max_dimension(&self) -> Option<u32> {
+        None
+    }
+
+    /// *Convenience function for benchmarking.*
+    ///
+    /// Repeats `upscale` multiple times with overwriting.
+    fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error>;
+}
+
 /// Allocation-effecient upscaling of square images
+///
+/// Thin wrapper over [`UpscaleImage`] that rejects non-square input,
+/// implemented for every upscaler whose errors can represent it.
 pub trait UpscaleSquareImage {
     type Error;
 
@@ -29,3 +89,42 @@ pub trait UpscaleSquareImage {
     /// Repeats `upscale` multiple times with overwriting.
     fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error>;
 }
+
+impl<T> UpscaleSquareImage for T
+where
+    T: UpscaleImage,
+    T::Error: From<Error>,
+{
+    type Error = T::Error;
+
+    fn load(&mut self, image: &DynamicImage) -> Result<(), Self::Error> {
+        if image.width() != image.height() {
+            return Err(Error::UnsquareImage.into());
+        }
+        UpscaleImage::load(self, image)
+    }
+
+    fn upscale(&self) -> Result<DynamicImage, Self::Error> {
+        UpscaleImage::upscale(self)
+    }
+
+    fn upscale_inplace(&mut self) -> Result<&DynamicImage, Self::Error> {
+        UpscaleImage::upscale_inplace(self)
+    }
+
+    fn upscale_factor(&self) -> f32 {
+        self.upscale_factors().0
+    }
+
+    fn original_resolution(&self) -> u32 {
+        self.original_dimensions().0
+    }
+
+    fn upscaled_resolution(&self) -> u32 {
+        self.upscaled_dimensions().0
+    }
+
+    fn upscale_repeat(&mut self, times: usize) -> Result<&DynamicImage, Self::Error> {
+        UpscaleImage::upscale_repeat(self, times)
+    }
+}
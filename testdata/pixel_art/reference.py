@@ -0,0 +1,124 @@
+"""Writes the Scale2x/Scale3x (EPX) and Eagle golden images from the published rules.
+
+Written independently of src/pixel_art.rs after the AdvanceMAME Scale2x/Scale3x
+description and the original Eagle rule, with edge pixels repeated. Needs only the
+standard library: python3 testdata/pixel_art/reference.py
+"""
+
+import os
+import struct
+import zlib
+
+PALETTE = {
+    "#": (20, 12, 28, 255),
+    "r": (208, 70, 72, 255),
+    "y": (218, 212, 94, 255),
+    "g": (109, 170, 44, 255),
+    "b": (89, 125, 206, 255),
+    "w": (222, 238, 214, 255),
+    ".": (0, 0, 0, 0),
+}
+
+# Same sprite as `mushroom` in the pixel_art tests
+MUSHROOM = [
+    "................",
+    ".....######.....",
+    "...##rrwwrr##...",
+    "..#rrrwwwwrrr#..",
+    ".#wrrrrwwrrrrw#.",
+    ".#wwrrrrrrrrww#.",
+    "#rwwrr####rrwwr#",
+    "#rrrr#bbbb#rrrr#",
+    "#rrrr#bbbb#rrrr#",
+    ".#rr##wbbw##rr#.",
+    "..##.#wwww#.##..",
+    ".....#wwwy#.....",
+    ".....#wwyy#.....",
+    "......####......",
+    "..gg........gg..",
+    "gggggggggggggggg",
+]
+
+
+def neighbourhood(image, x, y):
+    """A B C / D E F / G H I around (x, y), clamped to the image"""
+    height, width = len(image), len(image[0])
+
+    def at(dx, dy):
+        return image[min(max(y + dy, 0), height - 1)][min(max(x + dx, 0), width - 1)]
+
+    return [at(dx, dy) for dy in (-1, 0, 1) for dx in (-1, 0, 1)]
+
+
+def scale(image, factor, block):
+    height, width = len(image), len(image[0])
+    output = [[None] * (width * factor) for _ in range(height * factor)]
+    for y in range(height):
+        for x in range(width):
+            pixels = block(*neighbourhood(image, x, y))
+            for index, pixel in enumerate(pixels):
+                output[y * factor + index // factor][x * factor + index % factor] = pixel
+    return output
+
+
+def scale2x(a, b, c, d, e, f, g, h, i):
+    return [
+        d if d == b and b != f and d != h else e,
+        f if b == f and b != d and f != h else e,
+        d if d == h and d != b and h != f else e,
+        f if h == f and d != h and b != f else e,
+    ]
+
+
+def scale3x(a, b, c, d, e, f, g, h, i):
+    top_left = d == b and b != f and d != h
+    top_right = b == f and b != d and f != h
+    bottom_left = d == h and d != b and h != f
+    bottom_right = h == f and d != h and b != f
+    return [
+        d if top_left else e,
+        b if (top_left and e != c) or (top_right and e != a) else e,
+        f if top_right else e,
+        d if (top_left and e != g) or (bottom_left and e != a) else e,
+        e,
+        f if (top_right and e != i) or (bottom_right and e != c) else e,
+        d if bottom_left else e,
+        h if (bottom_left and e != i) or (bottom_right and e != g) else e,
+        f if bottom_right else e,
+    ]
+
+
+def eagle(a, b, c, d, e, f, g, h, i):
+    def corner(vertical, horizontal, diagonal):
+        return diagonal if vertical == horizontal == diagonal else e
+
+    return [corner(b, d, a), corner(b, f, c), corner(h, d, g), corner(h, f, i)]
+
+
+def write_png(path, image):
+    height, width = len(image), len(image[0])
+    rows = b"".join(b"\0" + bytes(channel for pixel in row for channel in pixel) for row in image)
+
+    def chunk(kind, data):
+        body = kind + data
+        return struct.pack(">I", len(data)) + body + struct.pack(">I", zlib.crc32(body))
+
+    header = struct.pack(">IIBBBBB", width, height, 8, 6, 0, 0, 0)
+    with open(path, "wb") as file:
+        file.write(b"\x89PNG\r\n\x1a\n")
+        file.write(chunk(b"IHDR", header) + chunk(b"IDAT", zlib.compress(rows, 9)))
+        file.write(chunk(b"IEND", b""))
+
+
+if __name__ == "__main__":
+    directory = os.path.dirname(os.path.abspath(__file__))
+    sprite = [[PALETTE[symbol] for symbol in row] for row in MUSHROOM]
+    goldens = {
+        "epx_2x": scale(sprite, 2, scale2x),
+        "epx_3x": scale(sprite, 3, scale3x),
+        "epx_4x": scale(scale(sprite, 2, scale2x), 2, scale2x),
+        "eagle_2x": scale(sprite, 2, eagle),
+        "eagle_4x": scale(scale(sprite, 2, eagle), 2, eagle),
+    }
+    for name, image in goldens.items():
+        write_png(os.path.join(directory, name + ".png"), image)